@@ -0,0 +1,90 @@
+// Run with `cargo bench`. Compares node lookups on dense tracks: a linear scan as the tracks used
+// to do it, binary search through get_value_at, and sequential playback through a TrackCursor.
+
+extern crate demy;
+
+use demy::*;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const TRACKS: usize = 100;
+const FRAMES: usize = 2_000;
+
+fn build_track(tl: &mut Timeline, name: &str, node_count: usize) {
+    let track = tl.get_track_mut(name);
+    for i in 1..node_count {
+        assert!(track.add_node(&Node::new(i as f64, (i % 17) as f64, InterpType::Linear)).is_ok());
+    }
+}
+
+// Reference for the old behaviour: walk the nodes until the segment containing `time`.
+fn linear_scan(track: &Track, time: f64) -> f64 {
+    let mut prev: Option<&Node> = None;
+    for node in track.nodes() {
+        if node.get_time() >= time {
+            return match prev {
+                Some(prev) => {
+                    let t = (time - prev.get_time()) / (node.get_time() - prev.get_time());
+                    prev.get_value() * (1_f64 - t) + node.get_value() * t
+                }
+                None => node.get_value()
+            }
+        }
+        prev = Some(node);
+    }
+    prev.unwrap().get_value()
+}
+
+fn report(name: &str, node_count: usize, elapsed: Duration) {
+    let samples = (TRACKS * FRAMES) as f64;
+    println!("{:>14} {:>7} nodes: {:>10.1} ns/sample", name, node_count, elapsed.as_secs_f64() * 1e9 / samples);
+}
+
+fn bench(node_count: usize) {
+    let mut tl = Timeline::new();
+    let names = (0..TRACKS).map(|i| format!("track.{}", i)).collect::<Vec<_>>();
+
+    let start = Instant::now();
+    for name in &names {
+        build_track(&mut tl, name, node_count);
+    }
+    println!("{:>14} {:>7} nodes: {:>10.1} ns/node", "add_node", node_count,
+             start.elapsed().as_secs_f64() * 1e9 / (TRACKS * node_count) as f64);
+
+    let tracks = tl.tracks().collect::<Vec<_>>();
+    let frame_time = |frame: usize| frame as f64 * node_count as f64 / FRAMES as f64 + 0.25_f64;
+
+    if node_count <= 10_000 {
+        let start = Instant::now();
+        for frame in 0..FRAMES {
+            for track in &tracks {
+                black_box(linear_scan(track, frame_time(frame)));
+            }
+        }
+        report("linear scan", node_count, start.elapsed());
+    }
+
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        for track in &tracks {
+            black_box(track.get_value_at(frame_time(frame)));
+        }
+    }
+    report("binary search", node_count, start.elapsed());
+
+    let mut cursors = vec![TrackCursor::new(); TRACKS];
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        for (track, cursor) in tracks.iter().zip(cursors.iter_mut()) {
+            black_box(track.get_value_at_cursor(frame_time(frame), cursor));
+        }
+    }
+    report("cursor", node_count, start.elapsed());
+}
+
+fn main() {
+    for &node_count in &[100, 1_000, 10_000, 50_000] {
+        bench(node_count);
+        println!();
+    }
+}
@@ -0,0 +1,31 @@
+// With the `header` feature, generates demy.h from the ffi module into OUT_DIR. tests/c_api.rs
+// checks the include/demy.h in the repository against it, so the prototypes can't drift.
+#[cfg(feature = "header")]
+extern crate cbindgen;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "header")]
+    generate_header();
+}
+
+#[cfg(feature = "header")]
+fn generate_header() {
+    use std::env;
+    use std::path::Path;
+
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let config = cbindgen::Config::from_file(Path::new(&crate_dir).join("cbindgen.toml")).unwrap();
+
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src");
+
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Unable to generate the C header.")
+        .write_to_file(Path::new(&out_dir).join("demy.h"));
+}
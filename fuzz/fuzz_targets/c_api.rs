@@ -0,0 +1,10 @@
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate demy;
+
+#[path = "../ops.rs"]
+mod ops;
+
+fuzz_target!(|data: &[u8]| ops::run(data));
@@ -0,0 +1,351 @@
+// Turns arbitrary bytes into a sequence of C API calls. Shared by the libFuzzer target and by
+// tests/ffi_fuzz.rs, which replays pseudo-random inputs on stable.
+//
+// Track handles are kept across calls and reused after their track is deleted, so stale handles get
+// exercised too. Whatever the input, no call may panic and every track must keep its node at time 0
+// with the rest in strictly increasing time order.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+
+use demy::ffi::*;
+use demy::{ErrorCode, Handle, InterpType, Key, Node, Timeline};
+
+const TIMES: [f64; 10] = [0_f64, -0_f64, 1_f64, 10_f64, 10.5_f64, -1_f64, f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1e300_f64];
+
+const NAMES: [&[u8]; 5] = [b"camera\0", b"fade\0", b"\xc3\xa9clair\0", b"\xff\xfe\0", b"\0"];
+
+struct Input<'a> {
+    data: &'a [u8],
+}
+
+// Handles handed out so far, live or stale.
+#[derive(Default)]
+struct Held {
+    tracks: Vec<TrackHandle>,
+    event_tracks: Vec<EventTrackHandle>,
+    node_iters: Vec<NodeIterHandle>,
+}
+
+impl Held {
+    fn keep(list: &mut Vec<Handle>, handle: Handle) -> Handle {
+        if list.len() < 16 { list.push(handle) }
+        handle
+    }
+}
+
+impl<'a> Input<'a> {
+    fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((&byte, rest)) => { self.data = rest; byte }
+            None => 0
+        }
+    }
+
+    fn is_empty(&self) -> bool { self.data.is_empty() }
+
+    fn flag(&mut self) -> bool { self.byte() & 1 == 1 }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T { items[self.byte() as usize % items.len()] }
+
+    // Mostly small times that collide with each other, sometimes raw bits.
+    fn time(&mut self) -> f64 {
+        match self.byte() % 4 {
+            0 => self.pick(&TIMES),
+            1 => {
+                let mut bits = [0_u8; 8];
+                for byte in bits.iter_mut() { *byte = self.byte() }
+                f64::from_bits(u64::from_le_bytes(bits))
+            }
+            _ => self.byte() as f64 / 4_f64
+        }
+    }
+
+    fn name(&mut self) -> *const c_char { self.pick(&NAMES).as_ptr() as *const c_char }
+
+    fn text(&mut self) -> CString {
+        let len = self.byte() as usize % 16;
+        let bytes = (0..len).map(|_| self.byte()).filter(|&byte| byte != 0).collect::<Vec<u8>>();
+        CString::new(bytes).unwrap()
+    }
+
+    // Raw integers for enum parameters, mostly one of the `count` variants or just past them.
+    fn variant(&mut self, count: u32) -> u32 {
+        match self.byte() % 4 {
+            0 => u32::from_le_bytes([self.byte(), self.byte(), self.byte(), self.byte()]),
+            _ => self.byte() as u32 % (count + 2)
+        }
+    }
+
+    fn value(&mut self, kind: u32) -> CValue {
+        let mut components = [0_f64; 4];
+        for component in components.iter_mut() { *component = self.time() }
+        CValue { kind, components }
+    }
+}
+
+extern "C" fn half_step(_prev: *const Key, from: *const Key, to: *const Key, _next: *const Key, t: f64) -> f64 {
+    unsafe { if t < 0.5_f64 { (*from).get_value() } else { (*to).get_value() } }
+}
+
+pub fn run(data: &[u8]) {
+    let mut input = Input { data };
+    let mut held = Held::default();
+
+    unsafe {
+        let tl = demy_tl_new();
+
+        while !input.is_empty() {
+            demy_clear_last_error();
+            step(tl, &mut input, &mut held);
+            assert!(demy_last_error_code() != ErrorCode::Panic, "{:?}", std::ffi::CStr::from_ptr(demy_last_error_message()));
+            check_tracks(tl);
+        }
+
+        demy_tl_free(tl);
+    }
+}
+
+// Either a fresh lookup by name or a handle from earlier, which may have gone stale since.
+unsafe fn track(tl: *mut Timeline, input: &mut Input, held: &mut Held) -> TrackHandle {
+    if input.flag() && !held.tracks.is_empty() {
+        return input.pick(&held.tracks)
+    }
+    Held::keep(&mut held.tracks, demy_tl_track_get(tl, input.name()))
+}
+
+unsafe fn event_track(tl: *mut Timeline, input: &mut Input, held: &mut Held) -> EventTrackHandle {
+    if input.flag() && !held.event_tracks.is_empty() {
+        return input.pick(&held.event_tracks)
+    }
+    Held::keep(&mut held.event_tracks, demy_tl_event_track_get(tl, input.name()))
+}
+
+unsafe fn step(tl: *mut Timeline, input: &mut Input, held: &mut Held) {
+    match input.byte() % 23 {
+        0 => {
+            let tr = track(tl, input, held);
+            demy_tr_add_node(tl, tr, input.time(), input.time(), input.variant(31));
+        }
+        1 => {
+            let kind = input.variant(6);
+            let tr = Held::keep(&mut held.tracks, demy_tl_typed_track_get(tl, input.name(), kind));
+            let value = input.value(kind);
+            demy_tr_add_typed_node(tl, tr, input.time(), value, input.variant(31));
+        }
+        2 => {
+            let tr = track(tl, input, held);
+            demy_tr_del_node(tl, tr, input.time());
+        }
+        3 => {
+            let tr = track(tl, input, held);
+            let index = input.byte() as usize % 4;
+            let node = demy_tr_get_node_by_index(tl, tr, index);
+            if node.is_null() { return }
+
+            let time = demy_node_get_time(node);
+            let edit = demy_node_clone(node);
+            demy_node_set_time(edit, input.time());
+            if input.flag() { demy_node_set_value(edit, input.time()) }
+            demy_node_set_interp(edit, input.variant(31));
+            if input.byte() & 3 == 0 { demy_node_set_custom_interp(edit, input.byte() as u32 % 3) }
+            demy_node_update_at(tl, tr, time, edit);
+            demy_node_free(edit);
+        }
+        4 => {
+            let tr = track(tl, input, held);
+            demy_tr_get_value_at(tl, tr, input.time());
+            demy_tr_get_typed_value_at(tl, tr, input.time());
+        }
+        5 => { demy_tl_track_del(tl, input.name()); }
+        6 => {
+            demy_tl_set_bpm(tl, input.time(), input.time());
+            demy_tl_set_ticks_per_second(tl, input.time());
+            demy_tl_set_rows_per_beat(tl, input.byte() as u32 % 3);
+        }
+        7 => {
+            let time = input.time();
+            demy_tl_ticks_to_beats(tl, time);
+            demy_tl_beats_to_ticks(tl, time);
+            demy_tl_ticks_to_rows(tl, time);
+            demy_tl_rows_to_ticks(tl, time);
+            demy_tl_get_bpm(tl, time);
+        }
+        8 => {
+            let ev = event_track(tl, input, held);
+            if input.flag() {
+                demy_ev_add_int(tl, ev, input.time(), input.byte() as i64);
+            } else {
+                let text = input.text();
+                demy_ev_add_text(tl, ev, input.time(), text.as_ptr());
+            }
+        }
+        9 => {
+            let ev = event_track(tl, input, held);
+            let fired = demy_ev_fired(tl, ev, input.time(), input.time());
+            let mut buffer = [0 as c_char; 4];
+            loop {
+                let event = demy_ev_iter_next(tl, fired);
+                if event.is_null() { break }
+                demy_event_get_text(event, buffer.as_mut_ptr(), buffer.len());
+            }
+            demy_ev_iter_free(tl, fired);
+        }
+        10 => {
+            let ev = event_track(tl, input, held);
+            demy_ev_del(tl, ev, input.time());
+        }
+        11 => {
+            let tr = track(tl, input, held);
+            let mut iter = demy_tr_iter_begin(tl, tr);
+            let end = demy_tr_iter_end(tl, tr);
+            // walks past the end on purpose, iter_get has to refuse the end iterator
+            for _ in 0..input.byte() % 8 {
+                demy_tr_iter_get(tl, iter);
+                demy_tr_iter_next(tl, &mut iter);
+                if iter.is_null() { break }
+            }
+            demy_tr_iter_are_eq(tl, iter, end);
+            demy_tr_iter_get(tl, end);
+            demy_tr_iter_free(tl, iter);
+            demy_tr_iter_free(tl, end);
+        }
+        12 => {
+            demy_tl_register_interp(tl, input.name(), if input.flag() { Some(half_step) } else { None });
+        }
+        13 => {
+            let tracks = demy_tl_track_iter(tl);
+            let mut name = [0 as c_char; 8];
+            loop {
+                let tr = demy_tl_track_iter_next(tl, tracks);
+                if tr.is_null() { break }
+                demy_tr_get_name(tl, tr, name.as_mut_ptr(), name.len());
+            }
+            demy_tl_track_iter_free(tl, tracks);
+        }
+        14 => {
+            // every entry point has to cope with null arguments
+            let node: *const Node = ptr::null();
+            let tr = track(tl, input, held);
+            demy_tr_add_node(ptr::null_mut(), tr, input.time(), 0_f64, InterpType::Linear as u32);
+            demy_tr_get_value_at(ptr::null(), tr, input.time());
+            demy_tr_add_node(tl, Handle::null(), input.time(), 0_f64, InterpType::Linear as u32);
+            demy_tr_iter_get(tl, Handle::null());
+            demy_tr_iter_next(tl, ptr::null_mut());
+            demy_node_get_time(node);
+            demy_node_update_at(tl, tr, input.time(), node);
+            demy_tl_track_get(ptr::null_mut(), input.name());
+            demy_tl_track_get(tl, ptr::null());
+            demy_ev_iter_next(tl, Handle::null());
+        }
+        15 => {
+            // iterators kept across calls, their track may have changed or gone since
+            let tr = track(tl, input, held);
+            let iter = Held::keep(&mut held.node_iters, demy_tr_iter_begin(tl, tr));
+            let mut other = input.pick(&held.node_iters);
+            demy_tr_iter_get(tl, other);
+            demy_tr_iter_next(tl, &mut other);
+            demy_tr_iter_are_eq(tl, iter, other);
+        }
+        16 => {
+            if held.node_iters.is_empty() { return }
+            let iter = input.pick(&held.node_iters);
+            demy_tr_iter_free(tl, iter);
+        }
+        17 => {
+            // whatever the API accepted has to load again with the same registrations, the copies
+            // are dropped afterwards
+            let json = demy_tl_save_to_buffer(tl);
+            let copy = demy_tl_load_from_memory_with(tl, json.data, json.len);
+            assert!(!copy.is_null(), "{:?}", std::ffi::CStr::from_ptr(demy_last_error_message()));
+            demy_buffer_free(json);
+
+            let packed = demy_tl_save_binary_to_buffer(tl, input.variant(2));
+            if !packed.data.is_null() {
+                let binary = demy_tl_load_binary_from_memory_with(tl, packed.data, packed.len);
+                assert!(!binary.is_null(), "{:?}", std::ffi::CStr::from_ptr(demy_last_error_message()));
+                demy_tl_free(binary);
+            }
+            demy_buffer_free(packed);
+            demy_tl_free(copy);
+        }
+        18 => {
+            let len = input.byte() as usize;
+            let bytes = (0..len).map(|_| input.byte()).collect::<Vec<u8>>();
+            demy_tl_free(demy_tl_load_from_memory(bytes.as_ptr(), bytes.len()));
+            demy_tl_free(demy_tl_load_binary_from_memory(bytes.as_ptr(), bytes.len()));
+            demy_tl_free(demy_tl_load_from_memory_with(tl, bytes.as_ptr(), bytes.len()));
+            demy_tl_free(demy_tl_load_binary_from_memory_with(tl, bytes.as_ptr(), bytes.len()));
+            demy_tl_load_from_memory(ptr::null(), 1);
+        }
+        19 => {
+            // recorded edits, mixed with the unrecorded ones above so undo runs into conflicts
+            let tr = track(tl, input, held);
+            match input.byte() % 6 {
+                0 => {
+                    let node = demy_node_new(input.time(), input.time(), input.variant(31));
+                    demy_tr_edit_add_node(tl, tr, node);
+                    demy_node_free(node);
+                }
+                1 => {
+                    let node = demy_tr_get_node_by_index(tl, tr, input.byte() as usize % 4);
+                    if node.is_null() { return }
+                    let time = demy_node_get_time(node);
+                    let edit = demy_node_clone(node);
+                    demy_node_set_time(edit, input.time());
+                    demy_node_set_value(edit, input.time());
+                    demy_tr_edit_update_node(tl, tr, time, edit);
+                    demy_node_free(edit);
+                }
+                2 => { demy_tr_edit_del_node(tl, tr, input.time()); }
+                3 => { demy_tl_edit_del_track(tl, input.name()); }
+                4 => { demy_tl_begin_transaction(tl); }
+                _ => { demy_tl_commit_transaction(tl); }
+            }
+        }
+        20 => {
+            if input.flag() { demy_tl_undo(tl); } else { demy_tl_redo(tl); }
+        }
+        21 => {
+            let tr = track(tl, input, held);
+            let (start, end, amount) = (input.time(), input.time(), input.time());
+            match input.byte() % 12 {
+                0 => { demy_tr_shift_range(tl, tr, start, end, amount); }
+                1 => { demy_tr_scale_range(tl, tr, start, end, input.time(), amount); }
+                2 => { demy_tr_insert_time(tl, tr, start, amount); }
+                3 => { demy_tr_remove_time(tl, tr, start, end); }
+                4 => { demy_tl_shift_range(tl, start, end, amount); }
+                5 => { demy_tl_scale_range(tl, start, end, input.time(), amount); }
+                6 => { demy_tl_insert_time(tl, start, amount); }
+                7 => { demy_tl_remove_time(tl, start, end); }
+                8 => { demy_tl_edit_shift_range(tl, start, end, amount); }
+                9 => { demy_tl_edit_scale_range(tl, start, end, input.time(), amount); }
+                10 => { demy_tl_edit_insert_time(tl, start, amount); }
+                _ => { demy_tl_edit_remove_time(tl, start, end); }
+            }
+        }
+        _ => {
+            demy_tl_check_interps(tl);
+        }
+    }
+}
+
+unsafe fn check_tracks(tl: *mut Timeline) {
+    let tracks = demy_tl_track_iter(tl);
+    loop {
+        let tr = demy_tl_track_iter_next(tl, tracks);
+        if tr.is_null() { break }
+
+        let count = demy_tr_node_count(tl, tr);
+        assert!(count >= 1, "a track lost its node at time 0");
+        assert_eq!(demy_node_get_time(demy_tr_get_node_by_index(tl, tr, 0)), 0_f64);
+
+        for i in 1..count {
+            let prev = demy_node_get_time(demy_tr_get_node_by_index(tl, tr, i - 1));
+            let time = demy_node_get_time(demy_tr_get_node_by_index(tl, tr, i));
+            assert!(prev < time && time.is_finite(), "nodes out of order: {} then {}", prev, time);
+        }
+    }
+    demy_tl_track_iter_free(tl, tracks);
+}
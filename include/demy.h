@@ -0,0 +1,426 @@
+/* Generated by cbindgen from src/lib.rs, do not edit by hand. */
+
+#ifndef DEMY_H
+#define DEMY_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define FORMAT_VERSION 1
+
+typedef enum ErrorCode {
+  ErrorCode_None = 0,
+  ErrorCode_ZeroTimeInsert = 1,
+  ErrorCode_ZeroTimeDelete = 2,
+  ErrorCode_DuplicateTime = 3,
+  ErrorCode_InvalidTime = 4,
+  ErrorCode_NodeNotFound = 5,
+  ErrorCode_EventNotFound = 6,
+  ErrorCode_TempoChangeNotFound = 7,
+  ErrorCode_InvalidBpm = 8,
+  ErrorCode_InvalidTempo = 9,
+  ErrorCode_ValueKindMismatch = 10,
+  ErrorCode_UnknownInterp = 11,
+  ErrorCode_MissingCustomInterp = 12,
+  ErrorCode_UnregisteredInterp = 13,
+  ErrorCode_Parse = 14,
+  ErrorCode_Serialize = 15,
+  ErrorCode_Io = 16,
+  ErrorCode_NullPointer = 17,
+  ErrorCode_InvalidUtf8 = 18,
+  ErrorCode_IndexOutOfRange = 19,
+  ErrorCode_Panic = 20,
+  ErrorCode_InvalidEnum = 21,
+  ErrorCode_StaleHandle = 22,
+  ErrorCode_UnsupportedVersion = 23,
+  ErrorCode_InvalidBinary = 24,
+  ErrorCode_InvalidValue = 25,
+  ErrorCode_InvalidTimeline = 26,
+  ErrorCode_Protocol = 27,
+  ErrorCode_TrackNotFound = 28,
+  ErrorCode_TrackExists = 29,
+  ErrorCode_InvalidRange = 30,
+  ErrorCode_RangeCollision = 31,
+} ErrorCode;
+
+typedef enum ValueKind {
+  ValueKind_Scalar = 0,
+  ValueKind_Vec2 = 1,
+  ValueKind_Vec3 = 2,
+  ValueKind_Vec4 = 3,
+  ValueKind_Color = 4,
+  ValueKind_Quat = 5,
+} ValueKind;
+
+typedef enum PayloadKind {
+  PayloadKind_Int = 0,
+  PayloadKind_Text = 1,
+} PayloadKind;
+
+typedef enum InterpType {
+  InterpType_None = 0,
+  InterpType_Linear = 1,
+  InterpType_Bezier = 2,
+  InterpType_CatmullRom = 3,
+  InterpType_Hermite = 4,
+  InterpType_MonotoneCubic = 5,
+  InterpType_QuadIn = 6,
+  InterpType_QuadOut = 7,
+  InterpType_QuadInOut = 8,
+  InterpType_CubicIn = 9,
+  InterpType_CubicOut = 10,
+  InterpType_CubicInOut = 11,
+  InterpType_QuartIn = 12,
+  InterpType_QuartOut = 13,
+  InterpType_QuartInOut = 14,
+  InterpType_ExpoIn = 15,
+  InterpType_ExpoOut = 16,
+  InterpType_ExpoInOut = 17,
+  InterpType_SineIn = 18,
+  InterpType_SineOut = 19,
+  InterpType_SineInOut = 20,
+  InterpType_BackIn = 21,
+  InterpType_BackOut = 22,
+  InterpType_BackInOut = 23,
+  InterpType_ElasticIn = 24,
+  InterpType_ElasticOut = 25,
+  InterpType_ElasticInOut = 26,
+  InterpType_BounceIn = 27,
+  InterpType_BounceOut = 28,
+  InterpType_BounceInOut = 29,
+  InterpType_Custom = 30,
+} InterpType;
+
+typedef enum Precision {
+  Precision_F64 = 0,
+  Precision_F32 = 1,
+} Precision;
+
+typedef struct Event Event;
+
+typedef struct Node Node;
+
+typedef struct Timeline Timeline;
+
+typedef struct Handle {
+  uint32_t index;
+  uint32_t generation;
+  uint32_t table;
+} Handle;
+
+typedef struct Handle TrackHandle;
+
+typedef struct Value {
+  unsigned int kind;
+  double components[4];
+} Value;
+
+typedef struct Handle NodeIterHandle;
+
+typedef struct Handle TrackIterHandle;
+
+typedef struct TimeSignature {
+  uint32_t beats_per_bar;
+  uint32_t beat_unit;
+} TimeSignature;
+
+typedef struct Handle EventTrackHandle;
+
+typedef struct Handle EventIterHandle;
+
+typedef struct CBuffer {
+  uint8_t *data;
+  size_t len;
+} CBuffer;
+
+typedef struct Tangent {
+  double time;
+  double value;
+} Tangent;
+
+typedef struct Key {
+  double time;
+  double value;
+  struct Tangent in_tangent;
+  struct Tangent out_tangent;
+} Key;
+
+typedef double (*CInterpolator)(const struct Key *prev,
+                                const struct Key *from,
+                                const struct Key *to,
+                                const struct Key *next,
+                                double t);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+enum ErrorCode demy_last_error_code(void);
+
+const char *demy_last_error_message(void);
+
+void demy_clear_last_error(void);
+
+struct Timeline *demy_tl_new(void);
+
+void demy_tl_free(struct Timeline *tl);
+
+TrackHandle demy_tl_track_get(struct Timeline *tl, const char *name);
+
+TrackHandle demy_tl_typed_track_get(struct Timeline *tl, const char *name, unsigned int kind);
+
+bool demy_tl_track_del(struct Timeline *tl, const char *name);
+
+bool demy_tr_is_valid(const struct Timeline *tl, TrackHandle tr);
+
+bool demy_tr_add_node(struct Timeline *tl,
+                      TrackHandle tr,
+                      double time,
+                      double value,
+                      unsigned int interp);
+
+bool demy_tr_add_typed_node(struct Timeline *tl,
+                            TrackHandle tr,
+                            double time,
+                            struct Value value,
+                            unsigned int interp);
+
+enum ValueKind demy_tr_get_kind(const struct Timeline *tl, TrackHandle tr);
+
+bool demy_tr_del_node(struct Timeline *tl, TrackHandle tr, double time);
+
+const struct Node *demy_tr_get_node(const struct Timeline *tl, TrackHandle tr, double time);
+
+NodeIterHandle demy_tr_iter_begin(struct Timeline *tl, TrackHandle tr);
+
+NodeIterHandle demy_tr_iter_end(struct Timeline *tl, TrackHandle tr);
+
+void demy_tr_iter_next(struct Timeline *tl, NodeIterHandle *iter);
+
+void demy_tr_iter_free(struct Timeline *tl, NodeIterHandle iter);
+
+bool demy_tr_iter_are_eq(const struct Timeline *tl, NodeIterHandle a, NodeIterHandle b);
+
+const struct Node *demy_tr_iter_get(const struct Timeline *tl, NodeIterHandle iter);
+
+double demy_tr_get_value_at(const struct Timeline *tl, TrackHandle tr, double time);
+
+struct Value demy_tr_get_typed_value_at(const struct Timeline *tl, TrackHandle tr, double time);
+
+size_t demy_tr_get_name(const struct Timeline *tl, TrackHandle tr, char *buffer, size_t size);
+
+size_t demy_tr_node_count(const struct Timeline *tl, TrackHandle tr);
+
+const struct Node *demy_tr_get_node_by_index(const struct Timeline *tl,
+                                             TrackHandle tr,
+                                             size_t index);
+
+TrackIterHandle demy_tl_track_iter(struct Timeline *tl);
+
+TrackHandle demy_tl_track_iter_next(struct Timeline *tl, TrackIterHandle iter);
+
+void demy_tl_track_iter_free(struct Timeline *tl, TrackIterHandle iter);
+
+bool demy_tl_set_bpm(struct Timeline *tl, double time, double bpm);
+
+double demy_tl_get_bpm(const struct Timeline *tl, double time);
+
+bool demy_tl_set_ticks_per_second(struct Timeline *tl, double ticks_per_second);
+
+bool demy_tl_set_rows_per_beat(struct Timeline *tl, unsigned int rows_per_beat);
+
+bool demy_tl_set_time_signature(struct Timeline *tl, struct TimeSignature signature);
+
+double demy_tl_ticks_to_seconds(const struct Timeline *tl, double ticks);
+
+double demy_tl_seconds_to_ticks(const struct Timeline *tl, double seconds);
+
+double demy_tl_ticks_to_beats(const struct Timeline *tl, double ticks);
+
+double demy_tl_beats_to_ticks(const struct Timeline *tl, double beats);
+
+double demy_tl_ticks_to_rows(const struct Timeline *tl, double ticks);
+
+double demy_tl_rows_to_ticks(const struct Timeline *tl, double rows);
+
+EventTrackHandle demy_tl_event_track_get(struct Timeline *tl, const char *name);
+
+bool demy_tl_event_track_del(struct Timeline *tl, const char *name);
+
+bool demy_ev_add_int(struct Timeline *tl, EventTrackHandle ev, double time, int64_t value);
+
+bool demy_ev_add_text(struct Timeline *tl, EventTrackHandle ev, double time, const char *text);
+
+bool demy_ev_del(struct Timeline *tl, EventTrackHandle ev, double time);
+
+EventIterHandle demy_ev_fired(struct Timeline *tl,
+                              EventTrackHandle ev,
+                              double prev_time,
+                              double now);
+
+const struct Event *demy_ev_iter_next(struct Timeline *tl, EventIterHandle iter);
+
+void demy_ev_iter_free(struct Timeline *tl, EventIterHandle iter);
+
+double demy_event_get_time(const struct Event *event);
+
+enum PayloadKind demy_event_get_kind(const struct Event *event);
+
+int64_t demy_event_get_int(const struct Event *event);
+
+size_t demy_event_get_text(const struct Event *event, char *buffer, size_t size);
+
+bool demy_node_update_at(struct Timeline *tl, TrackHandle tr, double time, const struct Node *node);
+
+struct Node *demy_node_clone(const struct Node *node);
+
+struct Node *demy_node_new(double time, double value, unsigned int interp);
+
+struct Node *demy_node_new_typed(double time, struct Value value, unsigned int interp);
+
+void demy_node_free(struct Node *node);
+
+bool demy_tl_save(const struct Timeline *tl, const char *path);
+
+bool demy_tl_save_pretty(const struct Timeline *tl, const char *path);
+
+struct Timeline *demy_tl_load(const char *path);
+
+struct Timeline *demy_tl_load_with(const struct Timeline *registered, const char *path);
+
+struct Timeline *demy_tl_load_repaired(const char *path, size_t *repaired);
+
+struct Timeline *demy_tl_load_repaired_with(const struct Timeline *registered,
+                                            const char *path,
+                                            size_t *repaired);
+
+bool demy_tl_save_binary(const struct Timeline *tl, const char *path, unsigned int precision);
+
+struct Timeline *demy_tl_load_binary(const char *path);
+
+struct Timeline *demy_tl_load_binary_with(const struct Timeline *registered, const char *path);
+
+struct Timeline *demy_tl_load_from_memory(const uint8_t *data, size_t len);
+
+struct Timeline *demy_tl_load_from_memory_with(const struct Timeline *registered,
+                                               const uint8_t *data,
+                                               size_t len);
+
+struct CBuffer demy_tl_save_to_buffer(const struct Timeline *tl);
+
+struct Timeline *demy_tl_load_binary_from_memory(const uint8_t *data, size_t len);
+
+struct Timeline *demy_tl_load_binary_from_memory_with(const struct Timeline *registered,
+                                                      const uint8_t *data,
+                                                      size_t len);
+
+struct CBuffer demy_tl_save_binary_to_buffer(const struct Timeline *tl, unsigned int precision);
+
+void demy_buffer_free(struct CBuffer buffer);
+
+bool demy_tr_edit_add_node(struct Timeline *tl, TrackHandle tr, const struct Node *node);
+
+bool demy_tr_edit_update_node(struct Timeline *tl,
+                              TrackHandle tr,
+                              double time,
+                              const struct Node *node);
+
+bool demy_tr_edit_del_node(struct Timeline *tl, TrackHandle tr, double time);
+
+bool demy_tl_edit_del_track(struct Timeline *tl, const char *name);
+
+bool demy_tl_begin_transaction(struct Timeline *tl);
+
+bool demy_tl_commit_transaction(struct Timeline *tl);
+
+int demy_tl_undo(struct Timeline *tl);
+
+int demy_tl_redo(struct Timeline *tl);
+
+int demy_tl_poll_reload(struct Timeline *tl, const char *path);
+
+bool demy_tr_shift_range(struct Timeline *tl,
+                         TrackHandle tr,
+                         double start,
+                         double end,
+                         double offset);
+
+bool demy_tr_scale_range(struct Timeline *tl,
+                         TrackHandle tr,
+                         double start,
+                         double end,
+                         double pivot,
+                         double factor);
+
+bool demy_tr_insert_time(struct Timeline *tl, TrackHandle tr, double at, double duration);
+
+bool demy_tr_remove_time(struct Timeline *tl, TrackHandle tr, double start, double end);
+
+bool demy_tl_shift_range(struct Timeline *tl, double start, double end, double offset);
+
+bool demy_tl_scale_range(struct Timeline *tl,
+                         double start,
+                         double end,
+                         double pivot,
+                         double factor);
+
+bool demy_tl_insert_time(struct Timeline *tl, double at, double duration);
+
+bool demy_tl_remove_time(struct Timeline *tl, double start, double end);
+
+bool demy_tl_edit_shift_range(struct Timeline *tl, double start, double end, double offset);
+
+bool demy_tl_edit_scale_range(struct Timeline *tl,
+                              double start,
+                              double end,
+                              double pivot,
+                              double factor);
+
+bool demy_tl_edit_insert_time(struct Timeline *tl, double at, double duration);
+
+bool demy_tl_edit_remove_time(struct Timeline *tl, double start, double end);
+
+int demy_tl_register_interp(struct Timeline *tl, const char *name, double (*func)(const struct Key*,
+                                                                                  const struct Key*,
+                                                                                  const struct Key*,
+                                                                                  const struct Key*,
+                                                                                  double));
+
+bool demy_tl_check_interps(const struct Timeline *tl);
+
+void demy_node_set_interp(struct Node *node, unsigned int interp);
+
+void demy_node_set_custom_interp(struct Node *node, unsigned int interp);
+
+int demy_node_get_custom_interp(const struct Node *node);
+
+enum InterpType demy_node_get_interp(const struct Node *node);
+
+void demy_node_set_in_tangent(struct Node *node, struct Tangent tangent);
+
+struct Tangent demy_node_get_in_tangent(const struct Node *node);
+
+void demy_node_set_out_tangent(struct Node *node, struct Tangent tangent);
+
+struct Tangent demy_node_get_out_tangent(const struct Node *node);
+
+void demy_node_set_value(struct Node *node, double value);
+
+double demy_node_get_value(const struct Node *node);
+
+void demy_node_set_typed_value(struct Node *node, struct Value value);
+
+struct Value demy_node_get_typed_value(const struct Node *node);
+
+void demy_node_set_time(struct Node *node, double time);
+
+double demy_node_get_time(const struct Node *node);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* DEMY_H */
@@ -0,0 +1,416 @@
+// Header-only C++ wrapper over demy.h. Timelines and nodes are owned by RAII handles, tracks are
+// referred to through their timeline, and failures from the C API are thrown as demy::Error.
+
+#ifndef DEMY_HPP
+#define DEMY_HPP
+
+#include "demy.h"
+
+#include <cstddef>
+#include <cstdint>
+#include <iterator>
+#include <stdexcept>
+#include <string>
+#include <utility>
+#include <vector>
+
+namespace demy {
+
+class Error : public std::runtime_error {
+public:
+    // Captures the calling thread's last error, construct it right after the failing call.
+    Error() : std::runtime_error(demy_last_error_message()), code_(demy_last_error_code()) {}
+
+    ErrorCode code() const { return code_; }
+
+private:
+    ErrorCode code_;
+};
+
+namespace detail {
+    inline void check(bool ok) { if (!ok) throw Error(); }
+    inline void check_last() { if (demy_last_error_code() != ErrorCode_None) throw Error(); }
+
+    template <typename T>
+    T* check_ptr(T* ptr) { if (ptr == nullptr) throw Error(); return ptr; }
+
+    inline Handle check_handle(Handle handle) { if (handle.generation == 0) throw Error(); return handle; }
+
+    // Calls `func`, for C functions whose failure value is also a valid result: the last error is
+    // cleared first and thrown if the call set it.
+    template <typename R, typename... P, typename... A>
+    R call(R (*func)(P...), A&&... args) {
+        demy_clear_last_error();
+        R result = func(std::forward<A>(args)...);
+        check_last();
+        return result;
+    }
+
+    template <typename... P, typename... A>
+    void call(void (*func)(P...), A&&... args) {
+        demy_clear_last_error();
+        func(std::forward<A>(args)...);
+        check_last();
+    }
+}
+
+// A node owned by a track, valid until the track is modified.
+class NodeRef {
+public:
+    explicit NodeRef(const ::Node* node) : node_(node) {}
+
+    double time() const { return detail::call(demy_node_get_time, node_); }
+    double value() const { return detail::call(demy_node_get_value, node_); }
+    Value typed_value() const { return detail::call(demy_node_get_typed_value, node_); }
+    InterpType interp() const { return detail::call(demy_node_get_interp, node_); }
+    int custom_interp() const { return detail::call(demy_node_get_custom_interp, node_); }
+    Tangent in_tangent() const { return detail::call(demy_node_get_in_tangent, node_); }
+    Tangent out_tangent() const { return detail::call(demy_node_get_out_tangent, node_); }
+
+    const ::Node* get() const { return node_; }
+
+private:
+    const ::Node* node_;
+};
+
+// A node owned by the caller, e.g. to edit a copy of a track's node and write it back.
+class Node {
+public:
+    Node(double time, double value, InterpType interp)
+        : node_(detail::check_ptr(demy_node_new(time, value, interp))) {}
+    Node(double time, Value value, InterpType interp)
+        : node_(detail::check_ptr(demy_node_new_typed(time, value, interp))) {}
+    explicit Node(NodeRef node) : node_(detail::check_ptr(demy_node_clone(node.get()))) {}
+
+    Node(const Node& other) : node_(detail::check_ptr(demy_node_clone(other.node_))) {}
+    Node(Node&& other) noexcept : node_(other.node_) { other.node_ = nullptr; }
+    Node& operator=(Node other) noexcept { std::swap(node_, other.node_); return *this; }
+    ~Node() { demy_node_free(node_); }
+
+    double time() const { return detail::call(demy_node_get_time, node_); }
+    void set_time(double time) { detail::call(demy_node_set_time, node_, time); }
+
+    double value() const { return detail::call(demy_node_get_value, node_); }
+    void set_value(double value) { detail::call(demy_node_set_value, node_, value); }
+
+    Value typed_value() const { return detail::call(demy_node_get_typed_value, node_); }
+    void set_typed_value(Value value) { detail::call(demy_node_set_typed_value, node_, value); }
+
+    InterpType interp() const { return detail::call(demy_node_get_interp, node_); }
+    void set_interp(InterpType interp) { detail::call(demy_node_set_interp, node_, interp); }
+
+    int custom_interp() const { return detail::call(demy_node_get_custom_interp, node_); }
+    void set_custom_interp(unsigned int id) { detail::call(demy_node_set_custom_interp, node_, id); }
+
+    Tangent in_tangent() const { return detail::call(demy_node_get_in_tangent, node_); }
+    void set_in_tangent(Tangent tangent) { detail::call(demy_node_set_in_tangent, node_, tangent); }
+
+    Tangent out_tangent() const { return detail::call(demy_node_get_out_tangent, node_); }
+    void set_out_tangent(Tangent tangent) { detail::call(demy_node_set_out_tangent, node_, tangent); }
+
+    const ::Node* get() const { return node_; }
+    ::Node* get() { return node_; }
+
+private:
+    ::Node* node_;
+};
+
+// Walks a track's nodes in time order through a node iterator handle.
+class NodeIterator {
+public:
+    typedef std::input_iterator_tag iterator_category;
+    typedef NodeRef value_type;
+    typedef std::ptrdiff_t difference_type;
+    typedef void pointer;
+    typedef NodeRef reference;
+
+    NodeIterator(::Timeline* tl, NodeIterHandle iter) : tl_(tl), iter_(iter) {}
+
+    NodeIterator(const NodeIterator&) = delete;
+    NodeIterator& operator=(const NodeIterator&) = delete;
+    NodeIterator(NodeIterator&& other) noexcept : tl_(other.tl_), iter_(other.iter_) { other.iter_ = NodeIterHandle(); }
+    NodeIterator& operator=(NodeIterator&& other) noexcept {
+        std::swap(tl_, other.tl_);
+        std::swap(iter_, other.iter_);
+        return *this;
+    }
+    ~NodeIterator() { demy_tr_iter_free(tl_, iter_); }
+
+    NodeRef operator*() const { return NodeRef(detail::check_ptr(demy_tr_iter_get(tl_, iter_))); }
+    NodeIterator& operator++() { detail::call(demy_tr_iter_next, tl_, &iter_); return *this; }
+
+    bool operator==(const NodeIterator& other) const { return demy_tr_iter_are_eq(tl_, iter_, other.iter_); }
+    bool operator!=(const NodeIterator& other) const { return !(*this == other); }
+
+private:
+    ::Timeline* tl_;
+    NodeIterHandle iter_;
+};
+
+// A track of a timeline. Calls on a track that has since been deleted fail with ErrorCode_StaleHandle.
+class Track {
+public:
+    Track(::Timeline* tl, TrackHandle track) : tl_(tl), track_(track) {}
+
+    bool valid() const { return demy_tr_is_valid(tl_, track_); }
+
+    ValueKind kind() const { return detail::call(demy_tr_get_kind, tl_, track_); }
+
+    std::string name() const {
+        std::size_t len = detail::call(demy_tr_get_name, tl_, track_, nullptr, 0);
+        std::string name(len, '\0');
+        if (!name.empty()) demy_tr_get_name(tl_, track_, &name[0], name.size() + 1);
+        return name;
+    }
+
+    double value_at(double time) const { return detail::call(demy_tr_get_value_at, tl_, track_, time); }
+    Value typed_value_at(double time) const { return detail::call(demy_tr_get_typed_value_at, tl_, track_, time); }
+
+    std::size_t node_count() const { return detail::call(demy_tr_node_count, tl_, track_); }
+
+    NodeRef node(std::size_t index) const {
+        const ::Node* node = detail::call(demy_tr_get_node_by_index, tl_, track_, index);
+        if (node == nullptr) throw std::out_of_range("demy::Track::node");
+        return NodeRef(node);
+    }
+
+    void add_node(double time, double value, InterpType interp) {
+        detail::check(demy_tr_add_node(tl_, track_, time, value, interp));
+    }
+
+    void add_node(double time, Value value, InterpType interp) {
+        detail::check(demy_tr_add_typed_node(tl_, track_, time, value, interp));
+    }
+
+    void update_node(double time, const Node& node) {
+        detail::check(demy_node_update_at(tl_, track_, time, node.get()));
+    }
+
+    void del_node(double time) { detail::check(demy_tr_del_node(tl_, track_, time)); }
+
+    // Same as the above, but recorded for Timeline::undo. The unrecorded edits clear the undo history.
+    void edit_add_node(const Node& node) { detail::check(demy_tr_edit_add_node(tl_, track_, node.get())); }
+    void edit_update_node(double time, const Node& node) {
+        detail::check(demy_tr_edit_update_node(tl_, track_, time, node.get()));
+    }
+    void edit_del_node(double time) { detail::check(demy_tr_edit_del_node(tl_, track_, time)); }
+
+    // Range edits, a failed one throws and leaves the track as it was.
+    void shift_range(double start, double end, double offset) {
+        detail::check(demy_tr_shift_range(tl_, track_, start, end, offset));
+    }
+    void scale_range(double start, double end, double pivot, double factor) {
+        detail::check(demy_tr_scale_range(tl_, track_, start, end, pivot, factor));
+    }
+    void insert_time(double at, double duration) { detail::check(demy_tr_insert_time(tl_, track_, at, duration)); }
+    void remove_time(double start, double end) { detail::check(demy_tr_remove_time(tl_, track_, start, end)); }
+
+    // Returns false and leaves `out` alone if there is no node at `time`.
+    bool find_node(double time, NodeRef& out) const {
+        const ::Node* node = detail::call(demy_tr_get_node, tl_, track_, time);
+        if (node == nullptr) return false;
+        out = NodeRef(node);
+        return true;
+    }
+
+    NodeIterator begin() const { return NodeIterator(tl_, detail::check_handle(demy_tr_iter_begin(tl_, track_))); }
+    NodeIterator end() const { return NodeIterator(tl_, detail::check_handle(demy_tr_iter_end(tl_, track_))); }
+
+    TrackHandle get() const { return track_; }
+
+private:
+    ::Timeline* tl_;
+    TrackHandle track_;
+};
+
+class Timeline {
+public:
+    Timeline() : tl_(detail::check_ptr(demy_tl_new())) {}
+
+    static Timeline load(const std::string& path) {
+        ::Timeline* tl = demy_tl_load(path.c_str());
+        if (tl == nullptr) throw Error();
+        return Timeline(tl);
+    }
+
+    // Loads a file that uses the custom interpolators registered on `registered`, load throws for it.
+    static Timeline load_with(const std::string& path, const Timeline& registered) {
+        ::Timeline* tl = demy_tl_load_with(registered.tl_, path.c_str());
+        if (tl == nullptr) throw Error();
+        return Timeline(tl);
+    }
+
+    Timeline(const Timeline&) = delete;
+    Timeline& operator=(const Timeline&) = delete;
+    Timeline(Timeline&& other) noexcept : tl_(other.tl_) { other.tl_ = nullptr; }
+    Timeline& operator=(Timeline&& other) noexcept { std::swap(tl_, other.tl_); return *this; }
+    ~Timeline() { demy_tl_free(tl_); }
+
+    // Fixes broken tracks instead of throwing, `repaired` receives the number of fixed problems.
+    static Timeline load_repaired(const std::string& path, std::size_t* repaired = nullptr) {
+        ::Timeline* tl = demy_tl_load_repaired(path.c_str(), repaired);
+        if (tl == nullptr) throw Error();
+        return Timeline(tl);
+    }
+
+    static Timeline load_repaired_with(const std::string& path, const Timeline& registered, std::size_t* repaired = nullptr) {
+        ::Timeline* tl = demy_tl_load_repaired_with(registered.tl_, path.c_str(), repaired);
+        if (tl == nullptr) throw Error();
+        return Timeline(tl);
+    }
+
+    static Timeline load_binary(const std::string& path) {
+        ::Timeline* tl = demy_tl_load_binary(path.c_str());
+        if (tl == nullptr) throw Error();
+        return Timeline(tl);
+    }
+
+    static Timeline load_binary_with(const std::string& path, const Timeline& registered) {
+        ::Timeline* tl = demy_tl_load_binary_with(registered.tl_, path.c_str());
+        if (tl == nullptr) throw Error();
+        return Timeline(tl);
+    }
+
+    static Timeline load_from_memory(const void* data, std::size_t size) {
+        ::Timeline* tl = demy_tl_load_from_memory(static_cast<const uint8_t*>(data), size);
+        if (tl == nullptr) throw Error();
+        return Timeline(tl);
+    }
+
+    static Timeline load_binary_from_memory(const void* data, std::size_t size) {
+        ::Timeline* tl = demy_tl_load_binary_from_memory(static_cast<const uint8_t*>(data), size);
+        if (tl == nullptr) throw Error();
+        return Timeline(tl);
+    }
+
+    static Timeline load_from_memory_with(const void* data, std::size_t size, const Timeline& registered) {
+        ::Timeline* tl = demy_tl_load_from_memory_with(registered.tl_, static_cast<const uint8_t*>(data), size);
+        if (tl == nullptr) throw Error();
+        return Timeline(tl);
+    }
+
+    static Timeline load_binary_from_memory_with(const void* data, std::size_t size, const Timeline& registered) {
+        ::Timeline* tl = demy_tl_load_binary_from_memory_with(registered.tl_, static_cast<const uint8_t*>(data), size);
+        if (tl == nullptr) throw Error();
+        return Timeline(tl);
+    }
+
+    void save(const std::string& path) const { detail::check(demy_tl_save(tl_, path.c_str())); }
+    void save_pretty(const std::string& path) const { detail::check(demy_tl_save_pretty(tl_, path.c_str())); }
+
+    void save_binary(const std::string& path, Precision precision = Precision_F64) const {
+        detail::check(demy_tl_save_binary(tl_, path.c_str(), precision));
+    }
+
+    std::string save_to_string() const {
+        CBuffer buffer = demy_tl_save_to_buffer(tl_);
+        if (buffer.data == nullptr) throw Error();
+        std::string json(reinterpret_cast<const char*>(buffer.data), buffer.len);
+        demy_buffer_free(buffer);
+        return json;
+    }
+
+    std::vector<uint8_t> save_binary_to_buffer(Precision precision = Precision_F64) const {
+        CBuffer buffer = demy_tl_save_binary_to_buffer(tl_, precision);
+        if (buffer.data == nullptr) throw Error();
+        std::vector<uint8_t> bytes(buffer.data, buffer.data + buffer.len);
+        demy_buffer_free(buffer);
+        return bytes;
+    }
+
+    int register_interp(const std::string& name, CInterpolator func) {
+        int id = demy_tl_register_interp(tl_, name.c_str(), func);
+        if (id < 0) throw Error();
+        return id;
+    }
+
+    // Reloads from `path` if the file changed since the previous call and returns whether it did. The
+    // first call only starts watching the file. Throws if the changed file fails to load, the
+    // timeline then stays as it was.
+    bool poll_reload(const std::string& path) {
+        int reloaded = demy_tl_poll_reload(tl_, path.c_str());
+        if (reloaded < 0) throw Error();
+        return reloaded == 1;
+    }
+
+    Track track(const std::string& name) {
+        return Track(tl_, detail::check_handle(demy_tl_track_get(tl_, name.c_str())));
+    }
+
+    Track typed_track(const std::string& name, ValueKind kind) {
+        return Track(tl_, detail::check_handle(demy_tl_typed_track_get(tl_, name.c_str(), kind)));
+    }
+
+    bool del_track(const std::string& name) { return detail::call(demy_tl_track_del, tl_, name.c_str()); }
+    bool edit_del_track(const std::string& name) { return detail::call(demy_tl_edit_del_track, tl_, name.c_str()); }
+
+    void begin_transaction() { detail::check(demy_tl_begin_transaction(tl_)); }
+    void commit_transaction() { detail::check(demy_tl_commit_transaction(tl_)); }
+
+    // Return false if there was nothing to undo or redo.
+    bool undo() {
+        int undone = demy_tl_undo(tl_);
+        if (undone < 0) throw Error();
+        return undone == 1;
+    }
+
+    bool redo() {
+        int redone = demy_tl_redo(tl_);
+        if (redone < 0) throw Error();
+        return redone == 1;
+    }
+
+    // The range edits of Track over every track and event track.
+    void shift_range(double start, double end, double offset) { detail::check(demy_tl_shift_range(tl_, start, end, offset)); }
+    void scale_range(double start, double end, double pivot, double factor) {
+        detail::check(demy_tl_scale_range(tl_, start, end, pivot, factor));
+    }
+    void insert_time(double at, double duration) { detail::check(demy_tl_insert_time(tl_, at, duration)); }
+    void remove_time(double start, double end) { detail::check(demy_tl_remove_time(tl_, start, end)); }
+
+    // Same as the above, but recorded for undo as a single step.
+    void edit_shift_range(double start, double end, double offset) {
+        detail::check(demy_tl_edit_shift_range(tl_, start, end, offset));
+    }
+    void edit_scale_range(double start, double end, double pivot, double factor) {
+        detail::check(demy_tl_edit_scale_range(tl_, start, end, pivot, factor));
+    }
+    void edit_insert_time(double at, double duration) { detail::check(demy_tl_edit_insert_time(tl_, at, duration)); }
+    void edit_remove_time(double start, double end) { detail::check(demy_tl_edit_remove_time(tl_, start, end)); }
+
+    // Ordered by name.
+    std::vector<Track> tracks() {
+        std::vector<Track> tracks;
+        TrackIterHandle iter = detail::check_handle(demy_tl_track_iter(tl_));
+        // the null handle ends the walk, and is also what a failed call returns
+        demy_clear_last_error();
+        for (TrackHandle track = demy_tl_track_iter_next(tl_, iter); track.generation != 0;
+             track = demy_tl_track_iter_next(tl_, iter)) {
+            tracks.push_back(Track(tl_, track));
+        }
+        demy_tl_track_iter_free(tl_, iter);
+        detail::check_last();
+        return tracks;
+    }
+
+    void set_bpm(double time, double bpm) { detail::check(demy_tl_set_bpm(tl_, time, bpm)); }
+    double bpm(double time) const { return detail::call(demy_tl_get_bpm, tl_, time); }
+
+    double ticks_to_seconds(double ticks) const { return detail::call(demy_tl_ticks_to_seconds, tl_, ticks); }
+    double seconds_to_ticks(double seconds) const { return detail::call(demy_tl_seconds_to_ticks, tl_, seconds); }
+    double ticks_to_beats(double ticks) const { return detail::call(demy_tl_ticks_to_beats, tl_, ticks); }
+    double beats_to_ticks(double beats) const { return detail::call(demy_tl_beats_to_ticks, tl_, beats); }
+    double ticks_to_rows(double ticks) const { return detail::call(demy_tl_ticks_to_rows, tl_, ticks); }
+    double rows_to_ticks(double rows) const { return detail::call(demy_tl_rows_to_ticks, tl_, rows); }
+
+    ::Timeline* get() const { return tl_; }
+
+private:
+    explicit Timeline(::Timeline* tl) : tl_(tl) {}
+
+    ::Timeline* tl_;
+};
+
+} // namespace demy
+
+#endif /* DEMY_HPP */
@@ -0,0 +1,427 @@
+// Compact binary timelines for size-constrained releases. The layout, all little endian:
+//
+//   "DEMY", varint version, flags byte (bit 0: values are f32)
+//   tempo:   f64 ticks per second, varint rows per beat, beats per bar and beat unit,
+//            varint change count, then a time and an f64 bpm per change
+//   interps: varint count, custom interpolator names
+//   tags:    varint count, names of the InterpTypes used by nodes
+//   tracks:  varint count, then per track its name, kind byte, varint node count and per node:
+//            time, varint (tag index << 2 | has custom id << 1 | has tangents),
+//            the value's components, [varint custom id], [in and out tangents]
+//   events:  varint count, then per track its name, varint event count and per event:
+//            time, payload byte, then a zigzag varint or a string
+//
+// Strings are a varint byte length followed by UTF-8. Times are stored relative to the previous
+// time in the same list: an integral step is written as varint (step << 1), anything else as
+// varint 1 followed by the absolute f64 time, so every time round trips exactly.
+
+use std::convert::TryFrom;
+use std::str;
+
+use Error;
+use Event;
+use EventTrack;
+use InterpRegistry;
+use InterpType;
+use Node;
+use Payload;
+use Tangent;
+use TimeSignature;
+use Timeline;
+use Track;
+use Value;
+use ValueKind;
+
+const MAGIC: &[u8; 4] = b"DEMY";
+const BINARY_VERSION: u32 = 1;
+
+const FLAG_F32: u8 = 1;
+
+// Steps at or above 2^53 could lose precision when added back to the previous time.
+const MAX_STEP: f64 = 9007199254740992_f64;
+
+const INTERP_TYPES: [InterpType; 31] = [
+    InterpType::None, InterpType::Linear, InterpType::Bezier, InterpType::CatmullRom, InterpType::Hermite,
+    InterpType::MonotoneCubic, InterpType::QuadIn, InterpType::QuadOut, InterpType::QuadInOut,
+    InterpType::CubicIn, InterpType::CubicOut, InterpType::CubicInOut, InterpType::QuartIn,
+    InterpType::QuartOut, InterpType::QuartInOut, InterpType::ExpoIn, InterpType::ExpoOut,
+    InterpType::ExpoInOut, InterpType::SineIn, InterpType::SineOut, InterpType::SineInOut,
+    InterpType::BackIn, InterpType::BackOut, InterpType::BackInOut, InterpType::ElasticIn,
+    InterpType::ElasticOut, InterpType::ElasticInOut, InterpType::BounceIn, InterpType::BounceOut,
+    InterpType::BounceInOut, InterpType::Custom,
+];
+
+const VALUE_KINDS: [ValueKind; 6] = [
+    ValueKind::Scalar, ValueKind::Vec2, ValueKind::Vec3, ValueKind::Vec4, ValueKind::Color, ValueKind::Quat,
+];
+
+// How node values and tangents are stored. Times and the tempo map always keep full precision, F32
+// values saturate at the edges of its range.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Precision {
+    F64 = 0,
+    F32 = 1,
+}
+
+impl TryFrom<u32> for Precision {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Error> {
+        match value {
+            0 => Ok(Precision::F64),
+            1 => Ok(Precision::F32),
+            _ => Err(Error::InvalidEnum { name: "Precision", value })
+        }
+    }
+}
+
+struct Writer {
+    buffer: Vec<u8>,
+    precision: Precision,
+}
+
+impl Writer {
+    fn byte(&mut self, byte: u8) { self.buffer.push(byte) }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.byte((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.byte(value as u8);
+    }
+
+    fn f64(&mut self, value: f64) { self.buffer.extend_from_slice(&value.to_le_bytes()) }
+
+    fn value(&mut self, value: f64) {
+        match self.precision {
+            Precision::F64 => self.f64(value),
+            // saturate rather than turn values outside the f32 range into infinities
+            Precision::F32 => self.buffer.extend_from_slice(&(value.clamp(f32::MIN as f64, f32::MAX as f64) as f32).to_le_bytes())
+        }
+    }
+
+    fn str(&mut self, text: &str) {
+        self.varint(text.len() as u64);
+        self.buffer.extend_from_slice(text.as_bytes());
+    }
+
+    fn time(&mut self, prev: f64, time: f64) {
+        let step = time - prev;
+        if prev.fract() == 0_f64 && time.fract() == 0_f64 && (0_f64..MAX_STEP).contains(&step) {
+            self.varint((step as u64) << 1);
+        } else {
+            self.varint(1);
+            self.f64(time);
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    precision: Precision,
+}
+
+impl<'a> Reader<'a> {
+    fn error<T>(&self, msg: &'static str) -> Result<T, Error> {
+        Err(Error::InvalidBinary { offset: self.offset, msg })
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.data.len() - self.offset < len {
+            return self.error("unexpected end of data")
+        }
+        let bytes = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> { Ok(self.bytes(1)?[0]) }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value)
+            }
+        }
+        self.error("varint is too long")
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let value = self.varint()?;
+        if value > u32::MAX as u64 {
+            return self.error("number is out of range")
+        }
+        Ok(value as u32)
+    }
+
+    // Counts are checked against the remaining data so a corrupt file can't request a huge allocation.
+    fn count(&mut self) -> Result<usize, Error> {
+        let count = self.varint()?;
+        if count > (self.data.len() - self.offset) as u64 {
+            return self.error("count is larger than the remaining data")
+        }
+        Ok(count as usize)
+    }
+
+    fn f64(&mut self) -> Result<f64, Error> {
+        let mut bytes = [0_u8; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(f64::from_le_bytes(bytes))
+    }
+
+    fn value(&mut self) -> Result<f64, Error> {
+        match self.precision {
+            Precision::F64 => self.f64(),
+            Precision::F32 => {
+                let mut bytes = [0_u8; 4];
+                bytes.copy_from_slice(self.bytes(4)?);
+                Ok(f32::from_le_bytes(bytes) as f64)
+            }
+        }
+    }
+
+    fn str(&mut self) -> Result<&'a str, Error> {
+        let len = self.count()?;
+        Ok(str::from_utf8(self.bytes(len)?)?)
+    }
+
+    fn time(&mut self, prev: f64) -> Result<f64, Error> {
+        match self.varint()? {
+            1 => self.f64(),
+            step if step & 1 == 0 => Ok(prev + (step >> 1) as f64),
+            _ => self.error("invalid time encoding")
+        }
+    }
+}
+
+pub(crate) fn internal_save(tl: &Timeline, precision: Precision) -> Vec<u8> {
+    let mut w = Writer { buffer: vec![], precision };
+
+    w.buffer.extend_from_slice(MAGIC);
+    w.varint(BINARY_VERSION as u64);
+    w.byte(if precision == Precision::F32 { FLAG_F32 } else { 0 });
+
+    let tempo = &tl.tempo;
+    w.f64(tempo.get_ticks_per_second());
+    w.varint(tempo.get_rows_per_beat() as u64);
+    w.varint(tempo.get_time_signature().beats_per_bar as u64);
+    w.varint(tempo.get_time_signature().beat_unit as u64);
+    w.varint(tempo.changes().len() as u64);
+    let mut prev = 0_f64;
+    for change in tempo.changes() {
+        w.time(prev, change.get_time());
+        w.f64(change.get_bpm());
+        prev = change.get_time();
+    }
+
+    w.varint(tl.interps.len() as u64);
+    for name in &tl.interps {
+        w.str(name);
+    }
+
+    let mut tags: Vec<InterpType> = vec![];
+    for node in tl.tracks.values().flat_map(|track| track.nodes.iter()) {
+        if !tags.contains(&node.interp) {
+            tags.push(node.interp);
+        }
+    }
+
+    w.varint(tags.len() as u64);
+    for interp in &tags {
+        w.str(&format!("{:?}", interp));
+    }
+
+    w.varint(tl.tracks.len() as u64);
+    for track in tl.tracks.values() {
+        w.str(&track.name);
+        w.byte(track.kind as u8);
+        w.varint(track.nodes.len() as u64);
+
+        let mut prev = 0_f64;
+        for node in &track.nodes {
+            w.time(prev, node.time);
+            prev = node.time;
+
+            let is_zero = |tangent: &Tangent| tangent.time == 0_f64 && tangent.value == 0_f64;
+            let has_tangents = !is_zero(&node.in_tangent) || !is_zero(&node.out_tangent);
+            let tag = tags.iter().position(|&interp| interp == node.interp).unwrap_or(0) as u64;
+            w.varint(tag << 2 | (node.custom_interp.is_some() as u64) << 1 | has_tangents as u64);
+
+            for &component in node.value.components() {
+                w.value(component);
+            }
+
+            if let Some(id) = node.custom_interp {
+                w.varint(id as u64);
+            }
+
+            if has_tangents {
+                for tangent in &[node.in_tangent, node.out_tangent] {
+                    w.value(tangent.time);
+                    w.value(tangent.value);
+                }
+            }
+        }
+    }
+
+    w.varint(tl.events.len() as u64);
+    for track in tl.events.values() {
+        w.str(track.get_name());
+        w.varint(track.events().len() as u64);
+
+        let mut prev = 0_f64;
+        for event in track.events() {
+            w.time(prev, event.get_time());
+            prev = event.get_time();
+
+            match *event.get_payload() {
+                Payload::Int(value) => {
+                    w.byte(0);
+                    w.varint(((value << 1) ^ (value >> 63)) as u64);
+                }
+                Payload::Text(ref text) => {
+                    w.byte(1);
+                    w.str(text);
+                }
+            }
+        }
+    }
+
+    w.buffer
+}
+
+pub(crate) fn internal_load(data: &[u8]) -> Result<Timeline, Error> {
+    let mut r = Reader { data, offset: 0, precision: Precision::F64 };
+
+    if r.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err(Error::InvalidBinary { offset: 0, msg: "not a binary timeline" })
+    }
+
+    let version = r.u32()?;
+    if version > BINARY_VERSION {
+        return Err(Error::UnsupportedVersion { found: version, supported: BINARY_VERSION })
+    }
+
+    let flags = r.byte()?;
+    if flags & !FLAG_F32 != 0 {
+        return r.error("unknown flags")
+    }
+    if flags & FLAG_F32 != 0 {
+        r.precision = Precision::F32;
+    }
+
+    let mut tl = Timeline::new();
+
+    tl.tempo.set_ticks_per_second(r.f64()?)?;
+    tl.tempo.set_rows_per_beat(r.u32()?)?;
+    let beats_per_bar = r.u32()?;
+    tl.tempo.set_time_signature(TimeSignature { beats_per_bar, beat_unit: r.u32()? })?;
+    let mut prev = 0_f64;
+    for _ in 0..r.count()? {
+        let time = r.time(prev)?;
+        tl.tempo.set_bpm_at(time, r.f64()?)?;
+        prev = time;
+    }
+
+    for _ in 0..r.count()? {
+        tl.interps.push(String::from(r.str()?));
+    }
+
+    let mut tags = vec![];
+    for _ in 0..r.count()? {
+        let name = r.str()?;
+        match INTERP_TYPES.iter().find(|interp| format!("{:?}", interp) == name) {
+            Some(&interp) => tags.push(interp),
+            None => return r.error("unknown interpolation type")
+        }
+    }
+
+    for _ in 0..r.count()? {
+        let name = String::from(r.str()?);
+        let kind = match VALUE_KINDS.get(r.byte()? as usize) {
+            Some(&kind) => kind,
+            None => return r.error("unknown value kind")
+        };
+
+        let mut nodes: Vec<Node> = vec![];
+        for _ in 0..r.count()? {
+            let time = r.time(nodes.last().map_or(0_f64, |node| node.time))?;
+            let valid = match nodes.last() {
+                Some(last) => last.time < time && time.is_finite(),
+                None => time == 0_f64
+            };
+            if !valid {
+                return r.error("node times must start at 0 and increase")
+            }
+
+            let tag = r.varint()?;
+            let interp = match tags.get((tag >> 2) as usize) {
+                Some(&interp) => interp,
+                None => return r.error("unknown interpolation tag")
+            };
+
+            let mut value = Value::zero(kind);
+            for i in 0..kind.component_count() {
+                let component = r.value()?;
+                value.set(i, component);
+            }
+
+            let mut node = Node::new_typed(time, value, interp);
+            if tag & 2 != 0 {
+                node.custom_interp = Some(r.u32()?);
+            }
+            if tag & 1 != 0 {
+                node.in_tangent = Tangent::new(r.value()?, r.value()?);
+                node.out_tangent = Tangent::new(r.value()?, r.value()?);
+            }
+            nodes.push(node);
+        }
+
+        if nodes.is_empty() {
+            return r.error("track has no node at time 0")
+        }
+
+        if tl.tracks.contains_key(&name) {
+            return r.error("duplicate track name")
+        }
+        tl.tracks.insert(name.clone(), Track { nodes, name, kind, custom_interps: InterpRegistry::default() });
+    }
+
+    for _ in 0..r.count()? {
+        let name = String::from(r.str()?);
+        let mut track = EventTrack::new(&name);
+
+        let mut prev = 0_f64;
+        for _ in 0..r.count()? {
+            let time = r.time(prev)?;
+            prev = time;
+
+            let payload = match r.byte()? {
+                0 => {
+                    let zigzag = r.varint()?;
+                    Payload::Int(((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64))
+                }
+                1 => Payload::Text(String::from(r.str()?)),
+                _ => return r.error("unknown payload kind")
+            };
+            track.add_event(Event::new(time, payload))?;
+        }
+
+        if tl.events.contains_key(&name) {
+            return r.error("duplicate event track name")
+        }
+        tl.events.insert(name, track);
+    }
+
+    if r.offset != data.len() {
+        return r.error("trailing data")
+    }
+
+    Ok(tl)
+}
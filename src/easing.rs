@@ -0,0 +1,71 @@
+// Easing curves map normalized time in [0, 1] to normalized progress, starting at 0 and ending at 1.
+// Only the `_in` forms are written out, the `_out` and `_in_out` forms are mirrored from them.
+
+use std::f64::consts::PI;
+
+pub fn quad_in(t: f64) -> f64 { t * t }
+pub fn cubic_in(t: f64) -> f64 { t * t * t }
+pub fn quart_in(t: f64) -> f64 { t * t * t * t }
+pub fn sine_in(t: f64) -> f64 { 1_f64 - (t * PI * 0.5_f64).cos() }
+
+pub fn expo_in(t: f64) -> f64 {
+    if t <= 0_f64 { return 0_f64 }
+    2_f64.powf(10_f64 * (t - 1_f64))
+}
+
+pub fn back_in(t: f64) -> f64 {
+    const OVERSHOOT: f64 = 1.70158;
+    (OVERSHOOT + 1_f64) * t * t * t - OVERSHOOT * t * t
+}
+
+pub fn elastic_in(t: f64) -> f64 {
+    if t <= 0_f64 { return 0_f64 }
+    if t >= 1_f64 { return 1_f64 }
+    -(2_f64.powf(10_f64 * t - 10_f64)) * ((t * 10_f64 - 10.75_f64) * (2_f64 * PI / 3_f64)).sin()
+}
+
+pub fn bounce_in(t: f64) -> f64 {
+    const N: f64 = 7.5625;
+    const D: f64 = 2.75;
+
+    let t = 1_f64 - t;
+    let bounce = if t < 1_f64 / D {
+        N * t * t
+    } else if t < 2_f64 / D {
+        let t = t - 1.5_f64 / D;
+        N * t * t + 0.75_f64
+    } else if t < 2.5_f64 / D {
+        let t = t - 2.25_f64 / D;
+        N * t * t + 0.9375_f64
+    } else {
+        let t = t - 2.625_f64 / D;
+        N * t * t + 0.984375_f64
+    };
+
+    1_f64 - bounce
+}
+
+macro_rules! mirrored_easings {
+    ($($ease_in:ident => $ease_out:ident, $ease_in_out:ident;)*) => {$(
+        pub fn $ease_out(t: f64) -> f64 { 1_f64 - $ease_in(1_f64 - t) }
+
+        pub fn $ease_in_out(t: f64) -> f64 {
+            if t < 0.5_f64 {
+                $ease_in(2_f64 * t) * 0.5_f64
+            } else {
+                1_f64 - $ease_in(2_f64 - 2_f64 * t) * 0.5_f64
+            }
+        }
+    )*}
+}
+
+mirrored_easings! {
+    quad_in => quad_out, quad_in_out;
+    cubic_in => cubic_out, cubic_in_out;
+    quart_in => quart_out, quart_in_out;
+    expo_in => expo_out, expo_in_out;
+    sine_in => sine_out, sine_in_out;
+    back_in => back_out, back_in_out;
+    elastic_in => elastic_out, elastic_in_out;
+    bounce_in => bounce_out, bounce_in_out;
+}
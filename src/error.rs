@@ -0,0 +1,211 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::str;
+
+use serde_json;
+
+use InterpId;
+use ValueKind;
+use Violation;
+
+#[derive(Debug)]
+pub enum Error {
+    // Tracks always start with a node at time 0, it can be updated but not inserted or removed.
+    ZeroTimeInsert,
+    ZeroTimeDelete,
+    DuplicateTime(f64),
+    InvalidTime(f64),
+    NodeNotFound(f64),
+    EventNotFound(f64),
+    TrackNotFound(String),
+    TrackExists(String),
+    TempoChangeNotFound(f64),
+    InvalidBpm(f64),
+    InvalidValue(f64),
+    InvalidTempo(&'static str),
+    InvalidRange(&'static str),
+    // A range edit would move a node or event onto or past the one at this time.
+    RangeCollision(f64),
+    ValueKindMismatch { expected: ValueKind, found: ValueKind },
+    UnknownInterp(InterpId),
+    // An InterpType::Custom node without an InterpId.
+    MissingCustomInterp,
+    UnregisteredInterp { name: String, track: String },
+    Parse { line: usize, column: usize, msg: String },
+    // The file was written by a newer version of the library.
+    UnsupportedVersion { found: u32, supported: u32 },
+    InvalidBinary { offset: usize, msg: &'static str },
+    // Every node ordering or value problem found while loading.
+    InvalidTimeline(Vec<Violation>),
+    // The other end of a sync connection broke the Rocket protocol.
+    Protocol(&'static str),
+    Serialize(String),
+    Io(io::Error),
+    // Only raised by the C API.
+    NullPointer(&'static str),
+    InvalidUtf8(str::Utf8Error),
+    IndexOutOfRange { index: usize, len: usize },
+    Panic(String),
+    // An enum passed through the C API as an integer that none of its variants have.
+    InvalidEnum { name: &'static str, value: u32 },
+    StaleHandle,
+}
+
+// Stable numeric codes for the C API, existing values must never be renumbered.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ErrorCode {
+    None = 0,
+    ZeroTimeInsert = 1,
+    ZeroTimeDelete = 2,
+    DuplicateTime = 3,
+    InvalidTime = 4,
+    NodeNotFound = 5,
+    EventNotFound = 6,
+    TempoChangeNotFound = 7,
+    InvalidBpm = 8,
+    InvalidTempo = 9,
+    ValueKindMismatch = 10,
+    UnknownInterp = 11,
+    MissingCustomInterp = 12,
+    UnregisteredInterp = 13,
+    Parse = 14,
+    Serialize = 15,
+    Io = 16,
+    NullPointer = 17,
+    InvalidUtf8 = 18,
+    IndexOutOfRange = 19,
+    Panic = 20,
+    InvalidEnum = 21,
+    StaleHandle = 22,
+    UnsupportedVersion = 23,
+    InvalidBinary = 24,
+    InvalidValue = 25,
+    InvalidTimeline = 26,
+    Protocol = 27,
+    TrackNotFound = 28,
+    TrackExists = 29,
+    InvalidRange = 30,
+    RangeCollision = 31,
+}
+
+impl Error {
+    pub fn code(&self) -> ErrorCode {
+        match *self {
+            Error::ZeroTimeInsert => ErrorCode::ZeroTimeInsert,
+            Error::ZeroTimeDelete => ErrorCode::ZeroTimeDelete,
+            Error::DuplicateTime(_) => ErrorCode::DuplicateTime,
+            Error::InvalidTime(_) => ErrorCode::InvalidTime,
+            Error::NodeNotFound(_) => ErrorCode::NodeNotFound,
+            Error::EventNotFound(_) => ErrorCode::EventNotFound,
+            Error::TrackNotFound(_) => ErrorCode::TrackNotFound,
+            Error::TrackExists(_) => ErrorCode::TrackExists,
+            Error::TempoChangeNotFound(_) => ErrorCode::TempoChangeNotFound,
+            Error::InvalidBpm(_) => ErrorCode::InvalidBpm,
+            Error::InvalidTempo(_) => ErrorCode::InvalidTempo,
+            Error::InvalidRange(_) => ErrorCode::InvalidRange,
+            Error::RangeCollision(_) => ErrorCode::RangeCollision,
+            Error::ValueKindMismatch { .. } => ErrorCode::ValueKindMismatch,
+            Error::UnknownInterp(_) => ErrorCode::UnknownInterp,
+            Error::MissingCustomInterp => ErrorCode::MissingCustomInterp,
+            Error::UnregisteredInterp { .. } => ErrorCode::UnregisteredInterp,
+            Error::Parse { .. } => ErrorCode::Parse,
+            Error::Serialize(_) => ErrorCode::Serialize,
+            Error::Io(_) => ErrorCode::Io,
+            Error::NullPointer(_) => ErrorCode::NullPointer,
+            Error::InvalidUtf8(_) => ErrorCode::InvalidUtf8,
+            Error::IndexOutOfRange { .. } => ErrorCode::IndexOutOfRange,
+            Error::Panic(_) => ErrorCode::Panic,
+            Error::InvalidEnum { .. } => ErrorCode::InvalidEnum,
+            Error::StaleHandle => ErrorCode::StaleHandle,
+            Error::UnsupportedVersion { .. } => ErrorCode::UnsupportedVersion,
+            Error::InvalidBinary { .. } => ErrorCode::InvalidBinary,
+            Error::InvalidValue(_) => ErrorCode::InvalidValue,
+            Error::InvalidTimeline(_) => ErrorCode::InvalidTimeline,
+            Error::Protocol(_) => ErrorCode::Protocol,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ZeroTimeInsert => write!(f, "Inserting a node with at_time=0 is not allowed."),
+            Error::ZeroTimeDelete => write!(f, "The node or tempo at time 0 can be changed but not deleted."),
+            Error::DuplicateTime(time) => write!(f, "Something already exists at time {}.", time),
+            Error::InvalidTime(time) => write!(f, "Time {} is not a finite, non-negative number.", time),
+            Error::NodeNotFound(time) => write!(f, "Could not find a node at time {}.", time),
+            Error::EventNotFound(time) => write!(f, "Could not find an event at time {}.", time),
+            Error::TrackNotFound(ref name) => write!(f, "Could not find track '{}'.", name),
+            Error::TrackExists(ref name) => write!(f, "Track '{}' already exists.", name),
+            Error::TempoChangeNotFound(time) => write!(f, "Could not find a tempo change at time {}.", time),
+            Error::InvalidBpm(bpm) => write!(f, "BPM {} is not a finite, positive number.", bpm),
+            Error::InvalidValue(value) => write!(f, "Value {} is not a finite number.", value),
+            Error::InvalidTempo(msg) => write!(f, "{}", msg),
+            Error::InvalidRange(msg) => write!(f, "{}", msg),
+            Error::RangeCollision(time) => write!(f, "The edit would move something onto or past the node or event at time {}.", time),
+            Error::ValueKindMismatch { expected, found } =>
+                write!(f, "Expected a {:?} value but found a {:?} value.", expected, found),
+            Error::UnknownInterp(id) => write!(f, "A node references interpolator id {}, which the timeline does not name.", id),
+            Error::MissingCustomInterp => write!(f, "A node uses the custom interpolator type without naming an interpolator."),
+            Error::UnregisteredInterp { ref name, ref track } =>
+                write!(f, "Interpolator '{}' used by track '{}' was never registered.", name, track),
+            Error::Parse { line, column, ref msg } => write!(f, "Parse error at line {}, column {}: {}", line, column, msg),
+            Error::Serialize(ref msg) => write!(f, "Failed to save timeline: {}", msg),
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::NullPointer(arg) => write!(f, "Argument '{}' must not be null.", arg),
+            Error::InvalidUtf8(ref err) => write!(f, "String argument is not valid UTF-8: {}", err),
+            Error::IndexOutOfRange { index, len } => write!(f, "Index {} is out of range for length {}.", index, len),
+            Error::Panic(ref msg) => write!(f, "Internal error: {}", msg),
+            Error::InvalidEnum { name, value } => write!(f, "{} is not a valid {}.", value, name),
+            Error::StaleHandle => write!(f, "The handle is stale or does not belong to this timeline."),
+            Error::UnsupportedVersion { found, supported } =>
+                write!(f, "The file is format version {}, but only versions up to {} can be read.", found, supported),
+            Error::InvalidBinary { offset, msg } => write!(f, "Invalid binary timeline at byte {}: {}.", offset, msg),
+            Error::Protocol(msg) => write!(f, "Sync protocol error: {}.", msg),
+            Error::InvalidTimeline(ref violations) => {
+                write!(f, "The timeline is invalid:")?;
+                for violation in violations {
+                    write!(f, "\n  {}", violation)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            Error::InvalidUtf8(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self { Error::Io(err) }
+}
+
+impl From<str::Utf8Error> for Error {
+    fn from(err: str::Utf8Error) -> Self { Error::InvalidUtf8(err) }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        if err.is_io() {
+            return Error::Io(io::Error::from(err))
+        }
+
+        let msg = err.to_string();
+        // serde_json appends the position to its message, it is already in the line and column
+        let msg = match msg.rfind(" at line ") {
+            Some(index) => String::from(&msg[..index]),
+            None => msg
+        };
+
+        Error::Parse { line: err.line(), column: err.column(), msg }
+    }
+}
@@ -0,0 +1,108 @@
+use std::cmp::Ordering;
+use std::slice;
+
+use Error;
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Payload {
+    Int(i64),
+    Text(String),
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PayloadKind {
+    Int = 0,
+    Text = 1
+}
+
+impl Payload {
+    pub fn get_kind(&self) -> PayloadKind {
+        match *self {
+            Payload::Int(_) => PayloadKind::Int,
+            Payload::Text(_) => PayloadKind::Text
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Event {
+    time: f64,
+    payload: Payload,
+}
+
+impl Event {
+    pub fn new(time: f64, payload: Payload) -> Self {
+        Event { time, payload }
+    }
+
+    pub fn get_time(&self) -> f64 { self.time }
+    pub fn get_payload(&self) -> &Payload { &self.payload }
+}
+
+// A track of discrete events, kept sorted by time. Unlike a Track it has no implicit node at time 0.
+#[derive(Serialize, Deserialize)]
+pub struct EventTrack {
+    events: Vec<Event>,
+    name: String,
+}
+
+impl EventTrack {
+    pub fn new(name: &str) -> Self {
+        EventTrack {
+            events: vec![],
+            name: String::from(name),
+        }
+    }
+
+    pub fn get_name(&self) -> &str { &self.name }
+
+    // For the crate's own edits that bypass add_event: validate.rs repairing loaded event tracks,
+    // Timeline's range edits and undoing them in history.rs.
+    pub(crate) fn internal_parts_mut(&mut self) -> (&mut String, &mut Vec<Event>) { (&mut self.name, &mut self.events) }
+
+    pub fn add_event(&mut self, event: Event) -> Result<(), Error> {
+        if !(event.time >= 0_f64 && event.time.is_finite()) {
+            return Err(Error::InvalidTime(event.time));
+        }
+
+        match self.internal_find(event.time) {
+            Ok(_index) => Err(Error::DuplicateTime(event.time)),
+            Err(index) => { self.events.insert(index, event); Ok(()) }
+        }
+    }
+
+    pub fn del_event_at(&mut self, time: f64) -> Result<(), Error> {
+        match self.internal_find(time) {
+            Ok(index) => { self.events.remove(index); Ok(()) }
+            Err(_index) => Err(Error::EventNotFound(time))
+        }
+    }
+
+    pub fn get_event_at(&self, time: f64) -> Option<&Event> {
+        self.internal_find(time).ok().map(|index| &self.events[index])
+    }
+
+    pub fn events(&self) -> slice::Iter<'_, Event> { self.events.iter() }
+
+    // Events with prev_time < time <= now, i.e. the ones fired since the previous frame at prev_time.
+    // Nothing fires when playback moved backwards.
+    pub fn fired(&self, prev_time: f64, now: f64) -> slice::Iter<'_, Event> {
+        let (begin, end) = self.internal_fired_range(prev_time, now);
+        self.events[begin..end].iter()
+    }
+
+    pub(crate) fn internal_fired_range(&self, prev_time: f64, now: f64) -> (usize, usize) {
+        if now.is_nan() || prev_time.is_nan() || now < prev_time { return (0, 0) }
+
+        let begin = self.events.partition_point(|e| e.time <= prev_time);
+        let end = self.events.partition_point(|e| e.time <= now);
+        (begin, end)
+    }
+
+    fn internal_find(&self, time: f64) -> Result<usize, usize> {
+        self.events.binary_search_by(|e| e.time.partial_cmp(&time).unwrap_or(Ordering::Less))
+    }
+
+    pub(crate) fn internal_get(&self, index: usize) -> Option<&Event> { self.events.get(index) }
+}
@@ -0,0 +1,144 @@
+// Versioning of saved timelines. Files carry a "version" field next to the timeline's own fields,
+// files from before it existed count as version 0. Older files are migrated forward as JSON before
+// they are deserialized, so the structs only ever have to read the current version.
+
+use std::io;
+
+use serde::Serialize;
+use serde_json;
+use serde_json::Value as Json;
+use serde_json::ser::{CompactFormatter, Formatter, PrettyFormatter};
+
+use Error;
+use Timeline;
+
+pub const FORMAT_VERSION: u32 = 1;
+
+type Migration = fn(&mut Json) -> Result<(), Error>;
+
+// MIGRATIONS[n] turns a version n file into a version n + 1 file.
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize] = [
+    internal_migrate_v0,
+];
+
+#[derive(Deserialize)]
+struct Header {
+    #[serde(default)]
+    version: u32,
+}
+
+#[derive(Serialize)]
+struct Saved<'a> {
+    version: u32,
+    #[serde(flatten)]
+    timeline: &'a Timeline,
+}
+
+// Indents like PrettyFormatter, except that every element of an array goes on a single line. Each
+// node, event and tempo change then gets its own line and shows up as one line in a diff.
+struct LineFormatter {
+    pretty: PrettyFormatter<'static>,
+    // Whether each indented container is an array.
+    open: Vec<bool>,
+    // How many containers deep into a single line element we are.
+    inline: usize,
+}
+
+impl LineFormatter {
+    fn begin<W: ?Sized + io::Write>(&mut self, writer: &mut W, is_array: bool) -> io::Result<()> {
+        if self.inline > 0 || self.open.last() == Some(&true) {
+            self.inline += 1;
+        } else {
+            self.open.push(is_array);
+        }
+
+        match (self.inline > 0, is_array) {
+            (true, true) => CompactFormatter.begin_array(writer),
+            (true, false) => CompactFormatter.begin_object(writer),
+            (false, true) => self.pretty.begin_array(writer),
+            (false, false) => self.pretty.begin_object(writer)
+        }
+    }
+
+    fn end<W: ?Sized + io::Write>(&mut self, writer: &mut W, is_array: bool) -> io::Result<()> {
+        if self.inline > 0 {
+            self.inline -= 1;
+            return if is_array { CompactFormatter.end_array(writer) } else { CompactFormatter.end_object(writer) }
+        }
+
+        self.open.pop();
+        if is_array { self.pretty.end_array(writer) } else { self.pretty.end_object(writer) }
+    }
+}
+
+impl Formatter for LineFormatter {
+    fn begin_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> { self.begin(writer, true) }
+    fn end_array<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> { self.end(writer, true) }
+    fn begin_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> { self.begin(writer, false) }
+    fn end_object<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> { self.end(writer, false) }
+
+    fn begin_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        if self.inline > 0 { CompactFormatter.begin_array_value(writer, first) } else { self.pretty.begin_array_value(writer, first) }
+    }
+
+    fn end_array_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.inline > 0 { CompactFormatter.end_array_value(writer) } else { self.pretty.end_array_value(writer) }
+    }
+
+    fn begin_object_key<W: ?Sized + io::Write>(&mut self, writer: &mut W, first: bool) -> io::Result<()> {
+        if self.inline > 0 { CompactFormatter.begin_object_key(writer, first) } else { self.pretty.begin_object_key(writer, first) }
+    }
+
+    fn begin_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.inline > 0 { CompactFormatter.begin_object_value(writer) } else { self.pretty.begin_object_value(writer) }
+    }
+
+    fn end_object_value<W: ?Sized + io::Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        if self.inline > 0 { CompactFormatter.end_object_value(writer) } else { self.pretty.end_object_value(writer) }
+    }
+}
+
+pub(crate) fn internal_save(tl: &Timeline, pretty: bool) -> Result<String, Error> {
+    let saved = Saved { version: FORMAT_VERSION, timeline: tl };
+    let mut buffer = vec![];
+
+    let result = if pretty {
+        let formatter = LineFormatter { pretty: PrettyFormatter::new(), open: vec![], inline: 0 };
+        saved.serialize(&mut serde_json::Serializer::with_formatter(&mut buffer, formatter))
+    } else {
+        saved.serialize(&mut serde_json::Serializer::new(&mut buffer))
+    };
+
+    match result {
+        Ok(()) => Ok(String::from_utf8(buffer).expect("serde_json writes UTF-8")),
+        Err(err) => Err(Error::Serialize(err.to_string()))
+    }
+}
+
+pub(crate) fn internal_load(buffer: &str) -> Result<Timeline, Error> {
+    let header: Header = serde_json::from_str(buffer)?;
+
+    if header.version > FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion { found: header.version, supported: FORMAT_VERSION })
+    }
+
+    // current files skip the detour through Json, which would lose the position of errors
+    if header.version == FORMAT_VERSION {
+        return Ok(serde_json::from_str(buffer)?)
+    }
+
+    let mut doc: Json = serde_json::from_str(buffer)?;
+    for migration in &MIGRATIONS[header.version as usize..] {
+        migration(&mut doc)?;
+    }
+
+    Ok(serde_json::from_value(doc)?)
+}
+
+// Version 1 only added the version field itself.
+fn internal_migrate_v0(doc: &mut Json) -> Result<(), Error> {
+    if let Some(obj) = doc.as_object_mut() {
+        obj.insert(String::from("version"), Json::from(1));
+    }
+    Ok(())
+}
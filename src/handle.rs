@@ -0,0 +1,129 @@
+// Generational handles for the C API. A handle names a slot and the generation the slot was in when
+// the handle was issued, freeing the slot bumps its generation so stale handles are detected rather
+// than dereferenced. Generation 0 is never issued, so a zeroed handle is the null handle. Every
+// table also stamps its handles with its own tag, so a handle given to another table (or to another
+// timeline) is refused as well.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Handle {
+    pub index: u32,
+    pub generation: u32,
+    pub table: u32,
+}
+
+impl Handle {
+    pub fn null() -> Self { Handle { index: 0, generation: 0, table: 0 } }
+    pub fn is_null(&self) -> bool { self.generation == 0 }
+}
+
+static NEXT_TABLE: AtomicU32 = AtomicU32::new(1);
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+pub(crate) struct HandleTable<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    tag: u32,
+}
+
+impl<T> Default for HandleTable<T> {
+    fn default() -> Self {
+        HandleTable { slots: vec![], free: vec![], tag: NEXT_TABLE.fetch_add(1, Ordering::Relaxed) }
+    }
+}
+
+impl<T> HandleTable<T> {
+    pub fn insert(&mut self, value: T) -> Handle {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(value);
+                Handle { index, generation: slot.generation, table: self.tag }
+            }
+            None => {
+                self.slots.push(Slot { generation: 1, value: Some(value) });
+                Handle { index: (self.slots.len() - 1) as u32, generation: 1, table: self.tag }
+            }
+        }
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        if handle.table != self.tag { return None }
+
+        match self.slots.get(handle.index as usize) {
+            Some(slot) if slot.generation == handle.generation => slot.value.as_ref(),
+            _ => None
+        }
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        if handle.table != self.tag { return None }
+
+        match self.slots.get_mut(handle.index as usize) {
+            Some(slot) if slot.generation == handle.generation => slot.value.as_mut(),
+            _ => None
+        }
+    }
+
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        if handle.table != self.tag { return None }
+
+        let slot = match self.slots.get_mut(handle.index as usize) {
+            Some(slot) if slot.generation == handle.generation && slot.value.is_some() => slot,
+            _ => return None
+        };
+
+        // skip 0 when wrapping around, it marks the null handle
+        slot.generation = slot.generation.checked_add(1).unwrap_or(1);
+        self.free.push(handle.index);
+        slot.value.take()
+    }
+
+}
+
+// Handles to values the timeline keeps by name, at most one live handle per name. The index finds
+// a name's handle without scanning the slots.
+#[derive(Default)]
+pub(crate) struct NameHandles {
+    table: HandleTable<String>,
+    by_name: HashMap<String, Handle>,
+}
+
+impl NameHandles {
+    pub fn get_or_insert(&mut self, name: &str) -> Handle {
+        if let Some(&handle) = self.by_name.get(name) {
+            return handle
+        }
+
+        let handle = self.table.insert(String::from(name));
+        self.by_name.insert(String::from(name), handle);
+        handle
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&String> { self.table.get(handle) }
+
+    pub fn remove(&mut self, name: &str) {
+        if let Some(handle) = self.by_name.remove(name) {
+            self.table.remove(handle);
+        }
+    }
+
+    // Removes the handles of every name `keep` rejects.
+    pub fn retain<F: FnMut(&str) -> bool>(&mut self, mut keep: F) {
+        let table = &mut self.table;
+        self.by_name.retain(|name, &mut handle| {
+            let kept = keep(name);
+            if !kept {
+                table.remove(handle);
+            }
+            kept
+        });
+    }
+}
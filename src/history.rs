@@ -0,0 +1,167 @@
+// Undo and redo for the Timeline::edit_* methods. Every edit is recorded as a Change holding enough
+// to apply it in either direction, and the changes made between begin_transaction and
+// commit_transaction are undone and redone as one step.
+
+use Error;
+use Event;
+use Node;
+use Timeline;
+use Track;
+
+#[derive(Clone)]
+pub(crate) enum Change {
+    NodeAdded { track: String, node: Node },
+    NodeDeleted { track: String, node: Node },
+    NodeUpdated { track: String, old: Node, new: Node },
+    TrackAdded { name: String },
+    TrackDeleted { track: Track },
+    // The nodes and events from before a range edit, of the tracks it changed.
+    Retimed { nodes: Vec<(String, Vec<Node>)>, events: Vec<(String, Vec<Event>)> },
+}
+
+impl Change {
+    // Undoes the change and returns the change that undoes that in turn.
+    fn internal_revert(&self, tl: &mut Timeline) -> Result<Change, Error> {
+        let inverse = match *self {
+            Change::NodeAdded { ref track, node } => {
+                internal_track_mut(tl, track)?.del_node_at(node.time)?;
+                Change::NodeDeleted { track: track.clone(), node }
+            }
+            Change::NodeDeleted { ref track, node } => {
+                internal_track_mut(tl, track)?.add_node(&node)?;
+                Change::NodeAdded { track: track.clone(), node }
+            }
+            Change::NodeUpdated { ref track, old, new } => {
+                internal_track_mut(tl, track)?.update_node_at(new.time, &old)?;
+                Change::NodeUpdated { track: track.clone(), old: new, new: old }
+            }
+            Change::TrackAdded { ref name } => {
+                let track = tl.tracks.get(name).cloned().ok_or_else(|| Error::TrackNotFound(name.clone()))?;
+                tl.internal_del_track(name);
+                Change::TrackDeleted { track }
+            }
+            Change::TrackDeleted { ref track } => {
+                if tl.tracks.contains_key(&track.name) {
+                    return Err(Error::TrackExists(track.name.clone()))
+                }
+
+                let mut restored = track.clone();
+                restored.custom_interps = tl.custom_interps.clone();
+                tl.tracks.insert(track.name.clone(), restored);
+                Change::TrackAdded { name: track.name.clone() }
+            }
+            Change::Retimed { ref nodes, ref events } => {
+                // find every track first, so a missing one leaves the rest untouched
+                for (name, _) in nodes {
+                    internal_track_mut(tl, name)?;
+                }
+                if let Some((name, _)) = events.iter().find(|(name, _)| !tl.events.contains_key(name)) {
+                    return Err(Error::TrackNotFound(name.clone()))
+                }
+
+                let nodes = nodes.iter().map(|(name, old)| {
+                    let track = tl.tracks.get_mut(name).unwrap();
+                    (name.clone(), std::mem::replace(&mut track.nodes, old.clone()))
+                }).collect();
+                let events = events.iter().map(|(name, old)| {
+                    let track = tl.events.get_mut(name).unwrap();
+                    (name.clone(), std::mem::replace(track.internal_parts_mut().1, old.clone()))
+                }).collect();
+                Change::Retimed { nodes, events }
+            }
+        };
+
+        Ok(inverse)
+    }
+}
+
+fn internal_track_mut<'a>(tl: &'a mut Timeline, name: &str) -> Result<&'a mut Track, Error> {
+    tl.tracks.get_mut(name).ok_or_else(|| Error::TrackNotFound(String::from(name)))
+}
+
+// Reverts the changes of a transaction, last one first. Either all of them are reverted, or none are
+// and the error of the first that couldn't be is returned.
+fn internal_revert_all(tl: &mut Timeline, changes: &[Change]) -> Result<Vec<Change>, Error> {
+    let mut inverses = Vec::with_capacity(changes.len());
+    for change in changes.iter().rev() {
+        match change.internal_revert(tl) {
+            Ok(inverse) => inverses.push(inverse),
+            Err(err) => {
+                // each of these just succeeded the other way around
+                for inverse in inverses.iter().rev() {
+                    inverse.internal_revert(tl).expect("re-applying a reverted change");
+                }
+                return Err(err)
+            }
+        }
+    }
+    Ok(inverses)
+}
+
+#[derive(Default)]
+pub(crate) struct History {
+    undo: Vec<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    // Changes of the transaction that is still open.
+    open: Vec<Change>,
+    depth: usize,
+}
+
+impl History {
+    pub fn begin(&mut self) { self.depth += 1 }
+
+    pub fn commit(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 && !self.open.is_empty() {
+            self.undo.push(std::mem::take(&mut self.open));
+        }
+    }
+
+    pub fn record(&mut self, change: Change) {
+        self.redo.clear();
+        self.open.push(change);
+    }
+
+    pub fn can_undo(&self) -> bool { !self.undo.is_empty() || !self.open.is_empty() }
+    pub fn can_redo(&self) -> bool { !self.redo.is_empty() }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.open.clear();
+    }
+
+    // Closes any open transaction, undo and redo always work on whole ones.
+    fn internal_close(&mut self) {
+        self.depth = 0;
+        if !self.open.is_empty() {
+            self.undo.push(std::mem::take(&mut self.open));
+        }
+    }
+}
+
+pub(crate) fn internal_undo(tl: &mut Timeline) -> Result<bool, Error> {
+    tl.history.internal_close();
+    let changes = match tl.history.undo.pop() {
+        Some(changes) => changes,
+        None => return Ok(false)
+    };
+
+    match internal_revert_all(tl, &changes) {
+        Ok(inverses) => { tl.history.redo.push(inverses); Ok(true) }
+        Err(err) => { tl.history.undo.push(changes); Err(err) }
+    }
+}
+
+pub(crate) fn internal_redo(tl: &mut Timeline) -> Result<bool, Error> {
+    tl.history.internal_close();
+    let changes = match tl.history.redo.pop() {
+        Some(changes) => changes,
+        None => return Ok(false)
+    };
+
+    match internal_revert_all(tl, &changes) {
+        Ok(inverses) => { tl.history.undo.push(inverses); Ok(true) }
+        Err(err) => { tl.history.redo.push(changes); Err(err) }
+    }
+}
@@ -1,6 +1,10 @@
-use std::collections::HashMap;
-use std::collections::hash_map;
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+use std::convert::TryFrom;
 use std::slice;
+use std::sync::{Arc, RwLock};
+
+use range::Retime;
 
 #[macro_use]
 extern crate serde_derive;
@@ -8,42 +12,170 @@ extern crate serde_derive;
 extern crate serde;
 extern crate serde_json;
 
-#[derive(Serialize, Deserialize)]
+mod binary;
+pub mod easing;
+mod error;
+mod event;
+mod format;
+mod handle;
+mod history;
+mod range;
+mod reload;
+mod rocket;
+pub mod sync;
+mod tempo;
+mod validate;
+mod value;
+
+pub use binary::Precision;
+pub use error::{Error, ErrorCode};
+pub use event::{Event, EventTrack, Payload, PayloadKind};
+pub use format::FORMAT_VERSION;
+pub use handle::Handle;
+pub use reload::Reloader;
+pub use rocket::Inexact;
+pub use tempo::{TempoChange, TempoMap, TimeSignature};
+pub use validate::Violation;
+pub use value::{Value, ValueKind};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Track {
     nodes: Vec<Node>,
     name: String,
+    #[serde(default, skip_serializing_if = "ValueKind::is_scalar")]
+    kind: ValueKind,
+    // Shared with the owning timeline.
+    #[serde(skip)]
+    custom_interps: InterpRegistry,
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum InterpType {
     None = 0,
-    Linear = 1
-}
-
-#[repr(C)]
-pub struct CAPINodeIterator {
-    track: *const Track,
-    index: usize,
+    Linear = 1,
+    Bezier = 2,
+    CatmullRom = 3,
+    Hermite = 4,
+    MonotoneCubic = 5,
+    QuadIn = 6,
+    QuadOut = 7,
+    QuadInOut = 8,
+    CubicIn = 9,
+    CubicOut = 10,
+    CubicInOut = 11,
+    QuartIn = 12,
+    QuartOut = 13,
+    QuartInOut = 14,
+    ExpoIn = 15,
+    ExpoOut = 16,
+    ExpoInOut = 17,
+    SineIn = 18,
+    SineOut = 19,
+    SineInOut = 20,
+    BackIn = 21,
+    BackOut = 22,
+    BackInOut = 23,
+    ElasticIn = 24,
+    ElasticOut = 25,
+    ElasticInOut = 26,
+    BounceIn = 27,
+    BounceOut = 28,
+    BounceInOut = 29,
+    Custom = 30
 }
 
 impl InterpType {
+    // Custom interpolators live in the timeline's registry, see Timeline::register_interp. Without
+    // access to it a custom segment holds its starting value.
     pub fn to_func(&self) -> Interpolator {
-        match self {
-            &InterpType::None => interp_none,
-            &InterpType::Linear => interp_linear
+        match *self {
+            InterpType::None => interp_none,
+            InterpType::Linear => interp_linear,
+            InterpType::Bezier => interp_bezier,
+            InterpType::CatmullRom => interp_catmull_rom,
+            InterpType::Hermite => interp_hermite,
+            InterpType::MonotoneCubic => interp_monotone_cubic,
+            InterpType::QuadIn => interp_quad_in,
+            InterpType::QuadOut => interp_quad_out,
+            InterpType::QuadInOut => interp_quad_in_out,
+            InterpType::CubicIn => interp_cubic_in,
+            InterpType::CubicOut => interp_cubic_out,
+            InterpType::CubicInOut => interp_cubic_in_out,
+            InterpType::QuartIn => interp_quart_in,
+            InterpType::QuartOut => interp_quart_out,
+            InterpType::QuartInOut => interp_quart_in_out,
+            InterpType::ExpoIn => interp_expo_in,
+            InterpType::ExpoOut => interp_expo_out,
+            InterpType::ExpoInOut => interp_expo_in_out,
+            InterpType::SineIn => interp_sine_in,
+            InterpType::SineOut => interp_sine_out,
+            InterpType::SineInOut => interp_sine_in_out,
+            InterpType::BackIn => interp_back_in,
+            InterpType::BackOut => interp_back_out,
+            InterpType::BackInOut => interp_back_in_out,
+            InterpType::ElasticIn => interp_elastic_in,
+            InterpType::ElasticOut => interp_elastic_out,
+            InterpType::ElasticInOut => interp_elastic_in_out,
+            InterpType::BounceIn => interp_bounce_in,
+            InterpType::BounceOut => interp_bounce_out,
+            InterpType::BounceInOut => interp_bounce_in_out,
+            InterpType::Custom => interp_none
+        }
+    }
+}
+
+impl TryFrom<u32> for InterpType {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Error> {
+        match value {
+            0 => Ok(InterpType::None),
+            1 => Ok(InterpType::Linear),
+            2 => Ok(InterpType::Bezier),
+            3 => Ok(InterpType::CatmullRom),
+            4 => Ok(InterpType::Hermite),
+            5 => Ok(InterpType::MonotoneCubic),
+            6 => Ok(InterpType::QuadIn),
+            7 => Ok(InterpType::QuadOut),
+            8 => Ok(InterpType::QuadInOut),
+            9 => Ok(InterpType::CubicIn),
+            10 => Ok(InterpType::CubicOut),
+            11 => Ok(InterpType::CubicInOut),
+            12 => Ok(InterpType::QuartIn),
+            13 => Ok(InterpType::QuartOut),
+            14 => Ok(InterpType::QuartInOut),
+            15 => Ok(InterpType::ExpoIn),
+            16 => Ok(InterpType::ExpoOut),
+            17 => Ok(InterpType::ExpoInOut),
+            18 => Ok(InterpType::SineIn),
+            19 => Ok(InterpType::SineOut),
+            20 => Ok(InterpType::SineInOut),
+            21 => Ok(InterpType::BackIn),
+            22 => Ok(InterpType::BackOut),
+            23 => Ok(InterpType::BackInOut),
+            24 => Ok(InterpType::ElasticIn),
+            25 => Ok(InterpType::ElasticOut),
+            26 => Ok(InterpType::ElasticInOut),
+            27 => Ok(InterpType::BounceIn),
+            28 => Ok(InterpType::BounceOut),
+            29 => Ok(InterpType::BounceInOut),
+            30 => Ok(InterpType::Custom),
+            _ => Err(Error::InvalidEnum { name: "InterpType", value })
         }
     }
 }
 
 impl Track {
-    fn new(name: &str) -> Self {
+    fn new(name: &str, kind: ValueKind) -> Self {
         let mut track = Track {
             nodes: vec![],
             name: String::from(name),
+            kind,
+            custom_interps: InterpRegistry::default(),
         };
 
-        track.internal_add_node(0, &Node::new(0,0_f64, InterpType::None));
+        track.internal_add_node(0, &Node::new_typed(0_f64, Value::zero(kind), InterpType::None));
         track
     }
 
@@ -57,124 +189,328 @@ impl Track {
     }
 
     pub fn get_name(&self) -> &str { &self.name }
+    pub fn get_kind(&self) -> ValueKind { self.kind }
 
-    pub fn add_node(&mut self, add_node: &Node)-> Option<&'static str> {
-        if add_node.get_time() == 0 { 
-            return Some("Inserting a node with at_time=0 is not allowed."); 
-        }
+    pub fn add_node(&mut self, add_node: &Node)-> Result<(), Error> {
+        let add_time = add_node.get_time();
 
-        let mut prev_time = self.nodes[0].get_time();
-        let mut insert_index = None;
+        if add_time == 0_f64 { 
+            return Err(Error::ZeroTimeInsert); 
+        }
 
-        for (i, cur_node) in self.nodes.iter().enumerate().skip(1) {
-            let cur_time = cur_node.get_time();
-            let add_time = add_node.get_time();
+        if !(add_time > 0_f64 && add_time.is_finite()) {
+            return Err(Error::InvalidTime(add_time));
+        }
 
-            if cur_time == add_time { 
-                return Some("A node already exists at this time point.");
-            }
+        self.internal_check_node(add_node)?;
+        self.internal_check_interp(add_node)?;
 
-            if cur_time > add_time && add_time > prev_time {
-                insert_index = Some(i);
-                break;
-            }
+        let index = self.nodes.partition_point(|node| node.get_time() < add_time);
 
-            prev_time = cur_time;
+        if index < self.nodes.len() && self.nodes[index].get_time() == add_time {
+            return Err(Error::DuplicateTime(add_time));
         }
 
-        let index = match insert_index { Some(index) => index, None => self.nodes.len() };
-
         self.internal_add_node(index, add_node);
-        None
+        Ok(())
     }
 
-    pub fn get_node_at(&self, time: u32) -> Option<&Node> {
+    pub fn get_node_at(&self, time: f64) -> Option<&Node> {
         let (_i, node) = self.internal_get_node_at(time);
-        match node {
-            Some(node) => Some(node),
-            None => None
-        }
+        node
+    }
+
+    // The first component of the value, which is the whole value on scalar tracks.
+    pub fn get_value_at(&self, time: f64) -> f64 {
+        self.get_typed_value_at(time).get(0)
+    }
+
+    // Vectors and colors are interpolated component by component. Quaternions are slerped, with the
+    // interpolation mode only shaping the progress along the arc.
+    pub fn get_typed_value_at(&self, time: f64) -> Value {
+        let nodes_between = self.internal_get_nodes_between(time);
+        self.internal_sample(time, nodes_between)
+    }
+
+    // Same as get_value_at, but starts looking for the segment where the cursor's previous lookup
+    // ended. Sampling with steadily increasing times is then amortized O(1) instead of O(log n).
+    pub fn get_value_at_cursor(&self, time: f64, cursor: &mut TrackCursor) -> f64 {
+        self.get_typed_value_at_cursor(time, cursor).get(0)
     }
 
-    pub fn get_value_at(&self, time: u32) -> f64 {
-        let (left, right) = self.internal_get_nodes_between(time);
+    pub fn get_typed_value_at_cursor(&self, time: f64, cursor: &mut TrackCursor) -> Value {
+        let nodes_between = self.internal_get_nodes_between_from(time, cursor);
+        self.internal_sample(time, nodes_between)
+    }
+
+    fn internal_sample(&self, time: f64, (left, right): (usize, Option<usize>)) -> Value {
         let right = match right {
-            Some(node) => node,
-            None => return left.get_value()
+            Some(index) => index,
+            None => return self.nodes[left].value
+        };
+
+        let from = &self.nodes[left];
+        let to = &self.nodes[right];
+        let t = (time - from.get_time()) / (to.get_time() - from.get_time());
+
+        if self.kind == ValueKind::Quat {
+            let progress = self.internal_interp(to, &self.internal_segment(right, None), t);
+            return from.value.slerp(&to.value, progress)
+        }
+
+        let mut value = Value::zero(self.kind);
+        for component in 0..self.kind.component_count() {
+            value.set(component, self.internal_interp(to, &self.internal_segment(right, Some(component)), t));
+        }
+        value
+    }
+
+    // Scalar view of the segment ending at `right`, either of a single value component or, for `None`,
+    // of the progress from 0 at `from` to 1 at `to`.
+    fn internal_segment(&self, right: usize, component: Option<usize>) -> Segment {
+        let scalar_tangents = self.kind.is_scalar();
+        let key = |node: &Node, progress: f64| {
+            let value = match component {
+                Some(component) => node.value.get(component),
+                None => progress
+            };
+            Key::from_node(node, value, scalar_tangents)
         };
 
-        let t = (time as f64 - left.get_time() as f64) / (right.get_time() as f64 - left.get_time() as f64);
+        Segment {
+            prev: if right > 1 { Some(key(&self.nodes[right - 2], 0_f64)) } else { None },
+            from: key(&self.nodes[right - 1], 0_f64),
+            to: key(&self.nodes[right], 1_f64),
+            next: self.nodes.get(right + 1).map(|node| key(node, 1_f64)),
+        }
+    }
+
+    fn internal_interp(&self, to: &Node, segment: &Segment, t: f64) -> f64 {
+        match to.interp {
+            InterpType::Custom => self.internal_interp_custom(to.custom_interp, segment, t),
+            interp => (interp.to_func())(segment, t)
+        }
+    }
 
-        (right.interp.to_func())(left, right, t)
+    // Nodes are checked when they are added or loaded, so only a track taken out of its timeline
+    // falls back to holding the starting value.
+    fn internal_interp_custom(&self, id: Option<InterpId>, segment: &Segment, t: f64) -> f64 {
+        let custom = id.and_then(|id| internal_registered(&self.custom_interps, id));
+
+        match custom {
+            Some(CustomInterp::Rust(func)) => func(segment, t),
+            Some(CustomInterp::C(func)) => {
+                let prev = segment.prev.as_ref().map_or(std::ptr::null(), |key| key as *const Key);
+                let next = segment.next.as_ref().map_or(std::ptr::null(), |key| key as *const Key);
+                func(prev, &segment.from, &segment.to, next, t)
+            }
+            None => interp_none(segment, t)
+        }
     }
 
-    pub fn nodes(&self) -> slice::Iter<Node> { self.nodes.iter() }
+    pub fn nodes(&self) -> slice::Iter<'_, Node> { self.nodes.iter() }
+    pub fn node_count(&self) -> usize { self.nodes.len() }
+
+    // Nodes are indexed in time order, index 0 is always the node at time 0.
+    pub fn get_node_by_index(&self, index: usize) -> Option<&Node> { self.nodes.get(index) }
     
-    pub fn del_node_at(&mut self, time: u32) -> Option<&'static str> {
+    pub fn del_node_at(&mut self, time: f64) -> Result<(), Error> {
         match self.internal_get_node_index_at(time) {
-            Some(index) => { self.nodes.remove(index); None }
-            None => Some("Could not find node at the given time.")
+            Some(0) => Err(Error::ZeroTimeDelete),
+            Some(index) => { self.nodes.remove(index); Ok(()) }
+            None => Err(Error::NodeNotFound(time))
         }
     }
 
-    pub fn update_node_at(&mut self, time: u32, node: &Node) -> Option<&'static str> {
+    pub fn update_node_at(&mut self, time: f64, node: &Node) -> Result<(), Error> {
+        self.internal_check_node(node)?;
+        self.internal_check_interp(node)?;
+
         match self.internal_get_node_index_at(time) {
             Some(index) => { 
-                if (index + 1 == self.nodes.len()) 
-                    || (self.nodes[index].get_time() == node.get_time()) {
+                if self.nodes[index].get_time() == node.get_time() {
                     self.nodes[index] = *node; 
-                    return None 
+                    return Ok(())
+                }
+
+                if index == 0 {
+                    return Err(Error::ZeroTimeDelete);
                 }
 
-                self.del_node_at(time);
-                self.add_node(node);
-                None
+                let old_node = self.nodes.remove(index);
+                if let Err(err) = self.add_node(node) {
+                    self.internal_add_node(index, &old_node);
+                    return Err(err);
+                }
+                Ok(())
             }
-            None => Some("Could not find node at the given time.")
+            None => Err(Error::NodeNotFound(time))
+        }
+    }
+
+    // Range edits work on the nodes at start <= time < end and never move the node at time 0. They
+    // fail without changing the track if a node would land on or pass another one. Timeline's
+    // edit_shift_range and the like record them for undo.
+    pub fn shift_range(&mut self, start: f64, end: f64, offset: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Shift { start, end, offset })
+    }
+
+    // Stretches the range by `factor` around `pivot`, tangent handles included.
+    pub fn scale_range(&mut self, start: f64, end: f64, pivot: f64, factor: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Scale { start, end, pivot, factor })
+    }
+
+    // Moves every node at or after `at` later by `duration`.
+    pub fn insert_time(&mut self, at: f64, duration: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Insert { at, duration })
+    }
+
+    // Deletes the nodes in the range and moves the ones after it back by its length.
+    pub fn remove_time(&mut self, start: f64, end: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Remove { start, end })
+    }
+
+    fn internal_retime(&mut self, retime: Retime) -> Result<(), Error> {
+        retime.internal_check()?;
+        if let Some(nodes) = retime.internal_nodes(&self.nodes)? {
+            self.nodes = nodes;
         }
+        Ok(())
     }
 
+    // Besides the kind, refuses non-finite values and tangents, which couldn't be saved and loaded
+    // again.
+    fn internal_check_node(&self, node: &Node) -> Result<(), Error> {
+        if node.value.get_kind() != self.kind {
+            return Err(Error::ValueKindMismatch { expected: self.kind, found: node.value.get_kind() });
+        }
+
+        let tangents = [node.in_tangent.time, node.in_tangent.value, node.out_tangent.time, node.out_tangent.value];
+        match node.value.components().iter().chain(tangents.iter()).find(|value| !value.is_finite()) {
+            Some(&value) => Err(Error::InvalidValue(value)),
+            None => Ok(())
+        }
+    }
 
-    fn internal_get_nodes_between(&self, time: u32) -> (&Node, Option<&Node>) {
-        let mut prev_node = &self.nodes[0];
 
-        for node in self.nodes.iter().skip(1) {
-            if time >= prev_node.get_time() && node.get_time() >= time {
-                return (prev_node, Some(node))
-            }
+    // Custom nodes need an interpolator that is registered, not just named by a loaded file.
+    fn internal_check_interp(&self, node: &Node) -> Result<(), Error> {
+        let id = match (node.interp, node.custom_interp) {
+            (InterpType::Custom, None) => return Err(Error::MissingCustomInterp),
+            (_, Some(id)) => id,
+            (_, None) => return Ok(())
+        };
 
-            prev_node = node;
+        let registry = self.custom_interps.read().unwrap();
+        match registry.get(id as usize) {
+            None => Err(Error::UnknownInterp(id)),
+            Some(&(ref name, None)) => Err(Error::UnregisteredInterp { name: name.clone(), track: self.name.clone() }),
+            Some(&(_, Some(_))) => Ok(())
+        }
+    }
+
+    // The nodes at either end of the segment prev.time < time <= next.time, or the single node
+    // the value holds at before the first or after the last node.
+    fn internal_get_nodes_between(&self, time: f64) -> (usize, Option<usize>) {
+        if time.is_nan() || time <= self.nodes[0].get_time() { return (0, None) }
+
+        let right = self.nodes.partition_point(|node| node.get_time() < time);
+        if right == self.nodes.len() {
+            return (right - 1, None)
         }
 
-        (prev_node, None)
+        (right - 1, Some(right))
     }
 
-    fn internal_get_node_at(&self, time: u32) -> (usize, Option<&Node>) {
-        for (i, node) in self.nodes.iter().enumerate() {
-            if node.get_time() == time {
-                return (i, Some(node))
+    // Gallops away from the cursor's last segment before binary searching, so the cost grows with
+    // the log of the distance travelled since the previous lookup rather than with the track size.
+    fn internal_get_nodes_between_from(&self, time: f64, cursor: &mut TrackCursor) -> (usize, Option<usize>) {
+        if time.is_nan() || time <= self.nodes[0].get_time() {
+            cursor.right = 0;
+            return (0, None)
+        }
+
+        let len = self.nodes.len();
+        let hint = cursor.right.min(len - 1);
+        let mut step = 1;
+
+        let (low, high) = if self.nodes[hint].get_time() < time {
+            let mut low = hint + 1;
+            loop {
+                let probe = hint + step;
+                if probe >= len { break (low, len) }
+                if self.nodes[probe].get_time() >= time { break (low, probe) }
+                low = probe + 1;
+                step *= 2;
             }
+        } else {
+            let mut high = hint;
+            loop {
+                if step > hint { break (0, high) }
+                let probe = hint - step;
+                if self.nodes[probe].get_time() < time { break (probe + 1, high) }
+                high = probe;
+                step *= 2;
+            }
+        };
+
+        let right = low + self.nodes[low..high].partition_point(|node| node.get_time() < time);
+        if right == len {
+            cursor.right = len - 1;
+            return (len - 1, None)
         }
 
-        (0, None)
+        cursor.right = right;
+        (right - 1, Some(right))
     }
 
-    fn internal_get_node_index_at(&self, time: u32) -> Option<usize> {
-        let (index, opt_node) = self.internal_get_node_at(time);
-        match opt_node {
-            Some(_node) => Some(index),
-            None => None
+    fn internal_get_node_at(&self, time: f64) -> (usize, Option<&Node>) {
+        let index = self.nodes.partition_point(|node| node.get_time() < time);
+        match self.nodes.get(index) {
+            Some(node) if node.get_time() == time => (index, Some(node)),
+            _ => (0, None)
         }
     }
+
+    fn internal_get_node_index_at(&self, time: f64) -> Option<usize> {
+        let (index, opt_node) = self.internal_get_node_at(time);
+        opt_node.map(|_node| index)
+    }
+}
+
+// Remembers where the last lookup on a track ended. A cursor holds no reference to the track, it
+// stays usable across edits and only loses its speed-up until the next lookup.
+#[derive(Copy, Clone, Default)]
+pub struct TrackCursor {
+    right: usize,
+}
+
+impl TrackCursor {
+    pub fn new() -> Self { TrackCursor { right: 0 } }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Timeline {
-    tracks: HashMap<String, Track>, 
+    // Tracks and event tracks are ordered by name, so saving the same timeline twice gives the same file.
+    tracks: BTreeMap<String, Track>,
+    // Names of the registered custom interpolators, indexed by InterpId. Only the names are saved,
+    // the functions have to be registered again to load the file, see load_with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    interps: Vec<String>,
+    #[serde(skip)]
+    custom_interps: InterpRegistry,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    events: BTreeMap<String, EventTrack>,
+    #[serde(default)]
+    tempo: TempoMap,
+    #[serde(skip)]
+    handles: ffi::CAPIHandles,
+    #[serde(skip)]
+    history: history::History,
 }
 
 pub struct TimelineTrackIter<'timeline> {
-    iter: hash_map::Iter<'timeline, String, Track>,
+    iter: btree_map::Iter<'timeline, String, Track>,
 }
 
 impl<'timeline >Iterator for TimelineTrackIter<'timeline> {
@@ -188,386 +524,2077 @@ impl<'timeline >Iterator for TimelineTrackIter<'timeline> {
     }
 }
 
+impl Default for Timeline {
+    fn default() -> Self { Timeline::new() }
+}
+
 impl Timeline {
     pub fn new() -> Self {
         Timeline {
-            tracks: HashMap::new()
+            tracks: BTreeMap::new(),
+            interps: vec![],
+            custom_interps: InterpRegistry::default(),
+            events: BTreeMap::new(),
+            tempo: TempoMap::new(),
+            handles: ffi::CAPIHandles::default(),
+            history: history::History::default(),
         }
     }
 
-    pub fn save(&self) -> Result<String, &'static str> {
-        match serde_json::to_string(self) {
-            Ok(result) => Ok(result),
-            Err(_err) => Err("Failed to save timeline.")
-        }
+    // Writes the current FORMAT_VERSION.
+    pub fn save(&self) -> Result<String, Error> {
+        format::internal_save(self, false)
     }
 
-    pub fn load(buffer: &str) -> Result<Timeline, &'static str> {
-        match serde_json::from_str(&buffer) {
-            Ok(val) => Ok(val),
-            Err(_err) => Err("Failed to load timeline.")
-        }
+    // Same as save, but indented with one node, event or tempo change per line so that changes to
+    // files in version control stay readable.
+    pub fn save_pretty(&self) -> Result<String, Error> {
+        format::internal_save(self, true)
     }
 
-    pub fn get_track(&mut self, name: &str) -> &Track { 
-        self.try_add_track(name);
-        self.tracks.get(name).unwrap()
+    // Reads files of any version up to FORMAT_VERSION, migrating older ones. Fails with every broken
+    // node ordering or value in the file rather than only the first. A new timeline has no custom
+    // interpolators, so files using them fail to load, see load_with.
+    pub fn load(buffer: &str) -> Result<Timeline, Error> {
+        Timeline::load_with(buffer, &Timeline::new())
     }
 
-    pub fn get_track_mut(&mut self, name: &str) -> &mut Track { 
-        self.try_add_track(name);
-        self.tracks.get_mut(name).unwrap()
+    // Loads the file with the custom interpolators registered on `registered`, which it names them
+    // by. Fails if a node uses one that isn't registered there.
+    pub fn load_with(buffer: &str, registered: &Timeline) -> Result<Timeline, Error> {
+        let mut tl = format::internal_load(buffer)?;
+        tl.internal_finish_load(registered, false)?;
+        Ok(tl)
     }
 
-    pub fn del_track(&mut self, name: &str) -> bool {
-        match self.tracks.remove(name) {
-            Some(_) => true,
-            None => false
-        }
+    // Like load, but fixes broken tracks instead of failing and returns what was fixed. Nodes with
+    // invalid times or values are dropped, duplicate times keep the first node and a missing time 0
+    // node is added back. Unregistered custom interpolators still fail, see load_repaired_with.
+    pub fn load_repaired(buffer: &str) -> Result<(Timeline, Vec<Violation>), Error> {
+        Timeline::load_repaired_with(buffer, &Timeline::new())
     }
 
-    fn try_add_track(&mut self, name: &str) {
-        if self.tracks.contains_key(name) {
-            return
-        }
+    pub fn load_repaired_with(buffer: &str, registered: &Timeline) -> Result<(Timeline, Vec<Violation>), Error> {
+        let mut tl = format::internal_load(buffer)?;
+        let violations = tl.internal_finish_load(registered, true)?;
+        Ok((tl, violations))
+    }
 
-        let track = Track::new(name);
-        // TODO : we dupe the string here twice, can we get that down to one dupe?
-        let result = self.tracks.insert(String::from(name), track); 
-        
-        assert_eq!(result.is_none(), true, "key: {}", name);
+    // A compact form of save for size-constrained releases, see binary.rs for the layout. With
+    // Precision::F32 node values and tangents are rounded to f32.
+    pub fn save_binary(&self, precision: Precision) -> Vec<u8> {
+        binary::internal_save(self, precision)
     }
 
+    // Like load, files using custom interpolators need load_binary_with.
+    pub fn load_binary(buffer: &[u8]) -> Result<Timeline, Error> {
+        Timeline::load_binary_with(buffer, &Timeline::new())
+    }
 
-    pub fn tracks(&mut self) -> TimelineTrackIter { TimelineTrackIter { iter: self.tracks.iter() }}
-}
+    pub fn load_binary_with(buffer: &[u8], registered: &Timeline) -> Result<Timeline, Error> {
+        let mut tl = binary::internal_load(buffer)?;
+        tl.internal_finish_load(registered, false)?;
+        Ok(tl)
+    }
 
-pub type Interpolator = fn(from: &Node, to: &Node, t: f64) -> f64;
+    // Replaces the timeline with the one in `buffer` as a single step, failing leaves it untouched.
+    // Registered interpolators stay registered, the file may only use those, and C API handles stay
+    // valid as long as the track they name is still there. Handles to tracks the file lacks go stale
+    // as if the track was deleted, even if a later reload brings it back. The undo history is cleared.
+    pub fn reload(&mut self, buffer: &str) -> Result<(), Error> {
+        let mut loaded = Timeline::load_with(buffer, self)?;
 
-pub fn interp_none(from: &Node, _to: &Node, _t: f64) -> f64 { from.get_value() }
-pub fn interp_linear(from: &Node, to: &Node, t: f64) -> f64 {
-    from.get_value() * (1_f64 - t) + (t * to.get_value())
-}
+        let mut handles = std::mem::take(&mut self.handles);
+        handles.tracks.retain(|name| loaded.tracks.contains_key(name));
+        handles.event_tracks.retain(|name| loaded.events.contains_key(name));
+        loaded.handles = handles;
 
-#[derive(Copy, Clone, Serialize, Deserialize)]
-pub struct Node {
-    time: u32,
-    value: f64,
-    interp: InterpType,
-}
+        *self = loaded;
+        Ok(())
+    }
 
-impl Node {
-    pub fn new(time: u32, value: f64, interp: InterpType) -> Self {
-        Node { time, value, interp }
+    // Converts a GNU Rocket .rocket file, see rocket.rs. Smooth keys come back as SineInOut nodes and
+    // are listed in the returned Inexact entries.
+    pub fn import_rocket(xml: &str) -> Result<(Timeline, Vec<Inexact>), Error> {
+        rocket::internal_import(xml)
     }
 
-    pub fn get_time(&self) -> u32 { self.time }
-    pub fn set_time(&mut self, time: u32) { self.time = time }
+    // Writes the scalar tracks as a .rocket file. Everything Rocket can't represent exactly, from
+    // interpolators it lacks to nodes between rows and tempo changes, is listed in the returned
+    // Inexact entries.
+    pub fn export_rocket(&self) -> (String, Vec<Inexact>) {
+        rocket::internal_export(self)
+    }
 
-    pub fn get_value(&self) -> f64 { self.value }
-    pub fn set_value(&mut self, value: f64) { self.value = value }
+    // Every named interpolator gets a slot, bound to the function `registered` has under that name
+    // if any. Without `repair` any violation fails the load, with it the violations are fixed and
+    // returned. Either way nodes may only use registered interpolators.
+    fn internal_finish_load(&mut self, registered: &Timeline, repair: bool) -> Result<Vec<Violation>, Error> {
+        self.tempo.internal_validate()?;
 
-    pub fn get_interpolator(&self) -> InterpType{ self.interp }
-    pub fn set_interpolator(&mut self, interp: InterpType) { self.interp = interp }
-}
+        let violations = validate::internal_validate(self, repair);
+        if !repair && !violations.is_empty() {
+            return Err(Error::InvalidTimeline(violations));
+        }
 
-pub mod ffi {
+        *self.custom_interps.write().unwrap() = self.interps.iter().map(|name| (name.clone(), None)).collect();
+        for track in self.tracks.values_mut() {
+            track.custom_interps = Arc::clone(&self.custom_interps);
+        }
 
-    use super::*;
-    use std::boxed::Box;
-    use std::ffi::CStr;
-    use std::os::raw::*;
-    use std::ptr;
+        let functions: Vec<(String, CustomInterp)> = registered.custom_interps.read().unwrap().iter()
+            .filter_map(|&(ref name, custom)| custom.map(|custom| (name.clone(), custom)))
+            .collect();
+        for (name, custom) in functions {
+            self.internal_register_interp(&name, custom);
+        }
 
+        self.check_interps()?;
+        Ok(violations)
+    }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tl_new() -> *mut Timeline {
-        let tl = Box::new(Timeline::new());
-        Box::into_raw(tl)
+    // Registers `func` under `name`, or rebinds the name if it is already known (e.g. it came from a
+    // loaded file). The returned id is stable across save and load.
+    pub fn register_interp(&mut self, name: &str, func: Interpolator) -> InterpId {
+        self.internal_register_interp(name, CustomInterp::Rust(func))
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tl_free(tl: *mut Timeline) {
-        if tl.is_null() { return }
-        Box::from_raw(tl);
+    pub fn get_interp_id(&self, name: &str) -> Option<InterpId> {
+        self.interps.iter().position(|interp| interp == name).map(|index| index as InterpId)
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tl_track_get(tl: *mut Timeline, name: *const c_char) -> *mut Track {
-        let name = CStr::from_ptr(name).to_str().unwrap();
-        (*tl).get_track_mut(name)
+    // Fails with the name of the first custom interpolator that nodes use but that was never
+    // registered in this session. Loading and adding nodes already check this.
+    pub fn check_interps(&self) -> Result<(), Error> {
+        for track in self.tracks.values() {
+            for id in track.nodes.iter().filter_map(|node| node.custom_interp) {
+                if internal_registered(&self.custom_interps, id).is_none() {
+                    let name = self.interps.get(id as usize).map_or("<unnamed>", |name| name.as_str());
+                    return Err(Error::UnregisteredInterp { name: String::from(name), track: String::from(track.get_name()) });
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tl_track_del(tl: *mut Timeline, name: *const c_char) -> bool {
-        let name = CStr::from_ptr(name).to_str().unwrap();
-        (*tl).del_track(name)
+    fn internal_register_interp(&mut self, name: &str, custom: CustomInterp) -> InterpId {
+        let id = match self.get_interp_id(name) {
+            Some(id) => id,
+            None => {
+                self.interps.push(String::from(name));
+                (self.interps.len() - 1) as InterpId
+            }
+        };
+
+        let mut registry = self.custom_interps.write().unwrap();
+        let names = &self.interps;
+        let len = registry.len();
+        registry.extend(names[len..].iter().map(|name| (name.clone(), None)));
+        registry[id as usize].1 = Some(custom);
+
+        id
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tr_add_node(tr: *mut Track, time: c_uint, value: c_double, interp: InterpType) -> bool {
-        let node = Node::new(time, value, interp);
-        match (*tr).add_node(&node) {
-            Some(_err) => false, // TODO : expose error string to C
-            None => true
+    // The edit_* methods change the timeline like the methods they are named after, and record the
+    // change for undo. Every other way of changing it, from get_track_mut to the tempo map and
+    // get_track creating a track, clears the history instead: undo can't step back over changes it
+    // doesn't know about. Should an undo still fail, it leaves the timeline and the history as they
+    // were.
+    pub fn edit_add_node(&mut self, track: &str, node: &Node) -> Result<(), Error> {
+        let created = self.try_add_track(track, ValueKind::Scalar);
+        if let Err(err) = self.tracks.get_mut(track).unwrap().add_node(node) {
+            if created { self.internal_del_track(track); }
+            return Err(err)
         }
-    }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tr_del_node(tr: *mut Track, time: c_uint) -> bool {
-        match (*tr).del_node_at(time) {
-            Some(_err) => false, // TODO : expose error string to C
-            None => true
+        self.history.begin();
+        if created {
+            self.history.record(history::Change::TrackAdded { name: String::from(track) });
         }
+        self.history.record(history::Change::NodeAdded { track: String::from(track), node: *node });
+        self.history.commit();
+        Ok(())
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tr_get_node(tr: *const Track, time: c_uint) -> *const Node {
-        match (*tr).get_node_at(time) {
-            Some(node) => node,
-            None => ptr::null()
-        }
+    pub fn edit_del_node_at(&mut self, track: &str, time: f64) -> Result<(), Error> {
+        let edited = self.tracks.get_mut(track).ok_or_else(|| Error::TrackNotFound(String::from(track)))?;
+        let node = *edited.get_node_at(time).ok_or(Error::NodeNotFound(time))?;
+        edited.del_node_at(time)?;
+
+        self.internal_record(history::Change::NodeDeleted { track: String::from(track), node });
+        Ok(())
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tr_iter_begin(tr: *const Track) -> *mut CAPINodeIterator {
-        let data = Box::new(CAPINodeIterator { 
-            track: tr as *const Track, 
-            index: 0,
-        });
+    pub fn edit_update_node_at(&mut self, track: &str, time: f64, node: &Node) -> Result<(), Error> {
+        let edited = self.tracks.get_mut(track).ok_or_else(|| Error::TrackNotFound(String::from(track)))?;
+        let old = *edited.get_node_at(time).ok_or(Error::NodeNotFound(time))?;
+        edited.update_node_at(time, node)?;
 
-        Box::into_raw(data)
+        self.internal_record(history::Change::NodeUpdated { track: String::from(track), old, new: *node });
+        Ok(())
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tr_iter_end(tr: *const Track) -> *mut CAPINodeIterator {
-        let data = Box::new(CAPINodeIterator { 
-            track: tr as *const Track, 
-            index: (*tr).nodes.len(),
-        });
+    // Undo brings the track back with all of its nodes, but C API handles to it stay stale.
+    pub fn edit_del_track(&mut self, name: &str) -> bool {
+        let track = match self.tracks.get(name) {
+            Some(track) => track.clone(),
+            None => return false
+        };
 
-        Box::into_raw(data)
+        self.internal_del_track(name);
+        self.internal_record(history::Change::TrackDeleted { track });
+        true
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tr_iter_next(ptr_to_iter: *mut *mut CAPINodeIterator) {
-        if ptr_to_iter.is_null() { return }
-        let iter = *ptr_to_iter;
+    // Groups the edits up to the matching commit_transaction into a single undo step. Transactions
+    // nest, only the outermost commit ends the step.
+    pub fn begin_transaction(&mut self) { self.history.begin() }
+    pub fn commit_transaction(&mut self) { self.history.commit() }
 
-        if (*iter).index >= (*(*iter).track).nodes.len() {
-            (*ptr_to_iter) = ptr::null_mut()
-        }
-        
-        (*iter).index += 1
+    // Reverts the latest undo step, returning false if there is none. Undo and redo close any open
+    // transaction first.
+    pub fn undo(&mut self) -> Result<bool, Error> { history::internal_undo(self) }
+    pub fn redo(&mut self) -> Result<bool, Error> { history::internal_redo(self) }
+
+    pub fn can_undo(&self) -> bool { self.history.can_undo() }
+    pub fn can_redo(&self) -> bool { self.history.can_redo() }
+    pub fn clear_history(&mut self) { self.history.clear() }
+
+    fn internal_record(&mut self, change: history::Change) {
+        self.history.begin();
+        self.history.record(change);
+        self.history.commit();
     }
-    
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tr_iter_free(iter: *mut CAPINodeIterator) {
-        if iter.is_null() { return }
-        Box::from_raw(iter);
+
+    // Creating the track isn't recorded, so it clears the undo history like get_track_mut.
+    pub fn get_track(&mut self, name: &str) -> &Track { 
+        if self.try_add_track(name, ValueKind::Scalar) {
+            self.history.clear();
+        }
+        self.tracks.get(name).unwrap()
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tr_iter_are_eq(a: *const CAPINodeIterator, b: *const CAPINodeIterator) -> bool {
-        if a.is_null() || b.is_null() { return false; }
-        return (*a).index == (*b).index;
+    // The track can be changed through the returned reference without undo knowing, so this clears
+    // the undo history. Use the edit_* methods to keep it.
+    pub fn get_track_mut(&mut self, name: &str) -> &mut Track { 
+        self.history.clear();
+        self.try_add_track(name, ValueKind::Scalar);
+        self.tracks.get_mut(name).unwrap()
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tr_iter_get(iter: *const CAPINodeIterator) -> *const Node {
-        &(*(*iter).track).nodes[(*iter).index]
+    // Like get_track_mut, but new tracks hold values of `kind`. Fails if the track already exists
+    // with a different value type.
+    pub fn get_typed_track_mut(&mut self, name: &str, kind: ValueKind) -> Result<&mut Track, Error> {
+        self.internal_typed_track(name, kind)?;
+        self.history.clear();
+        Ok(self.tracks.get_mut(name).unwrap())
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_node_update_at(tr: *mut Track, time: c_uint, node: *const Node) -> bool{
-        match (*tr).update_node_at(time, &*node) {
-            Some(_err) => false, // TODO : expose errors to C
-            None => true
+    // Clears the undo history if it creates the track, like get_track.
+    fn internal_typed_track(&mut self, name: &str, kind: ValueKind) -> Result<&mut Track, Error> {
+        if self.try_add_track(name, kind) {
+            self.history.clear();
+        }
+        let track = self.tracks.get_mut(name).unwrap();
+
+        if track.kind != kind {
+            return Err(Error::ValueKindMismatch { expected: kind, found: track.kind });
         }
+        Ok(track)
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_node_clone(node: *const Node) -> *mut Node {
-        let new_node = Box::new((*node).clone());
-        Box::into_raw(new_node)
+    // Not recorded, clears the undo history like get_track_mut. See edit_del_track.
+    pub fn del_track(&mut self, name: &str) -> bool {
+        self.history.clear();
+        self.internal_del_track(name)
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_node_new(time: c_uint, value: c_double, interp: InterpType) -> *mut Node{
-        let new_node = Box::new(Node::new(time, value, interp));
-        Box::into_raw(new_node)
+    fn internal_del_track(&mut self, name: &str) -> bool {
+        self.handles.tracks.remove(name);
+        self.tracks.remove(name).is_some()
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_node_free(node: *mut Node) {
-        if node.is_null() { return }
-        Box::from_raw(node);
+    // Returns whether the track was created.
+    fn try_add_track(&mut self, name: &str, kind: ValueKind) -> bool {
+        if self.tracks.contains_key(name) {
+            return false
+        }
+
+        let mut track = Track::new(name, kind);
+        track.custom_interps = Arc::clone(&self.custom_interps);
+        // TODO : we dupe the string here twice, can we get that down to one dupe?
+        let result = self.tracks.insert(String::from(name), track); 
+        
+        assert!(result.is_none(), "key: {}", name);
+        true
     }
 
-    use std::fs;
-    use std::io::Read;
-    use std::io::Write;
-    use std::error::Error;
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tl_save(tl: *const Timeline, path: *const c_char) -> bool {
-        if tl.is_null() { return false; }
+    pub fn tracks(&mut self) -> TimelineTrackIter<'_> { TimelineTrackIter { iter: self.tracks.iter() }}
 
-        let path = match CStr::from_ptr(path).to_str() {
-            Ok(path) => path,
-            Err(e) => { 
-                println!("{}", e.description());
-                return false;
-            }
-        };
+    // Clears the undo history if it creates the event track, like get_track.
+    pub fn get_event_track(&mut self, name: &str) -> &EventTrack {
+        if !self.events.contains_key(name) {
+            self.history.clear();
+        }
+        self.events.entry(String::from(name)).or_insert_with(|| EventTrack::new(name))
+    }
 
-        let mut fd = match fs::File::create(path) {
-            Ok(fd) => fd,
-            Err(e) => {
-                println!("{}", e.description());
-                return false;
-            }
-        };
+    // Event tracks and the tempo map aren't recorded, changing them clears the undo history like
+    // get_track_mut.
+    pub fn get_event_track_mut(&mut self, name: &str) -> &mut EventTrack {
+        self.history.clear();
+        self.events.entry(String::from(name)).or_insert_with(|| EventTrack::new(name))
+    }
 
-        let data = match (*tl).save() {
-            Ok(data) => data,
-            Err(e) => {
-                println!("{}", e);
-                return false;
-            }
-        };
+    pub fn del_event_track(&mut self, name: &str) -> bool {
+        self.history.clear();
+        self.handles.event_tracks.remove(name);
+        self.events.remove(name).is_some()
+    }
 
-        match fd.write_all(&data.into_bytes()) {
-            Ok(_result) => true,
-            Err(e) => {
-                println!("{}", e.description());
-                false
-            }
-        }
+    pub fn event_tracks(&self) -> btree_map::Values<'_, String, EventTrack> { self.events.values() }
+
+    pub fn get_tempo(&self) -> &TempoMap { &self.tempo }
+    pub fn get_tempo_mut(&mut self) -> &mut TempoMap {
+        self.history.clear();
+        &mut self.tempo
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_tl_load(path: *const c_char) -> *mut Timeline {
-        if path.is_null() { return ptr::null_mut(); }
+    // The range edits of Track, applied to every track and event track. If any of them would collide
+    // none are edited. Tempo changes stay where they are. These clear the undo history, the edit_*
+    // variants record the whole edit as a single undo step.
+    pub fn shift_range(&mut self, start: f64, end: f64, offset: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Shift { start, end, offset }, false)
+    }
 
-        let path_cstr = CStr::from_ptr(path).to_str();
-        let path = match  path_cstr {
-            Ok(p) => p,
-            Err(e) =>  {
-                println!("{}", e.description());
-                return ptr::null_mut();
-            }
-        };
+    pub fn scale_range(&mut self, start: f64, end: f64, pivot: f64, factor: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Scale { start, end, pivot, factor }, false)
+    }
 
-        let mut fd = match fs::File::open(path) {
-            Ok(fd) => fd,
-            Err(e) => { 
-                println!("{}", e.description());
-                return ptr::null_mut()
-            }
-        };
+    pub fn insert_time(&mut self, at: f64, duration: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Insert { at, duration }, false)
+    }
 
-        let mut contents = String::new();
-        match fd.read_to_string(&mut contents) {
-            Ok (_num) => (),
-            Err(e) => { 
-                println!("{}", e.description());
-                return ptr::null_mut()
-            }
-        };
+    pub fn remove_time(&mut self, start: f64, end: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Remove { start, end }, false)
+    }
 
-        match Timeline::load(&contents) {
-            Ok(tl) => Box::into_raw(Box::new(tl)),
-            Err(e) => { 
-                println!("{}", e);
-                return ptr::null_mut()
-            }
-        }
+    pub fn edit_shift_range(&mut self, start: f64, end: f64, offset: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Shift { start, end, offset }, true)
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_node_set_interp(node: *mut Node, interp: InterpType) {
-        if node.is_null() { return }
-        (*node).interp = interp;
+    pub fn edit_scale_range(&mut self, start: f64, end: f64, pivot: f64, factor: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Scale { start, end, pivot, factor }, true)
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_node_get_interp(node: *mut Node) -> InterpType {
-        if node.is_null() { return InterpType::None }
-        (*node).interp
+    pub fn edit_insert_time(&mut self, at: f64, duration: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Insert { at, duration }, true)
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_node_set_value(node: *mut Node, value: c_double) {
-        if node.is_null() { return }
-        (*node).value = value;
+    pub fn edit_remove_time(&mut self, start: f64, end: f64) -> Result<(), Error> {
+        self.internal_retime(Retime::Remove { start, end }, true)
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_node_get_value(node: *mut Node) -> c_double {
-        if node.is_null() { return 0_f64 }
-        (*node).value
+    fn internal_retime(&mut self, retime: Retime, record: bool) -> Result<(), Error> {
+        retime.internal_check()?;
+        let nodes = self.tracks.values()
+            .map(|track| retime.internal_nodes(&track.nodes))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let events = self.events.values()
+            .map(|track| retime.internal_events(track.events().as_slice()))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        // only the tracks the edit changes are kept for undo
+        let old_nodes = self.tracks.iter_mut().zip(nodes)
+            .filter_map(|((name, track), nodes)| nodes.map(|nodes| (name.clone(), std::mem::replace(&mut track.nodes, nodes))))
+            .collect();
+        let old_events = self.events.iter_mut().zip(events)
+            .filter_map(|((name, track), events)| events.map(|events| (name.clone(), std::mem::replace(track.internal_parts_mut().1, events))))
+            .collect();
+
+        if record {
+            self.internal_record(history::Change::Retimed { nodes: old_nodes, events: old_events });
+        } else {
+            self.history.clear();
+        }
+        Ok(())
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_node_set_time(node: *mut Node, time: c_uint) {
-        if node.is_null() { return }
-        (*node).time = time;
+    // Adds `node` to the track at `beat`, converted to ticks through the tempo map.
+    pub fn add_node_at_beat(&mut self, name: &str, beat: f64, node: &Node) -> Result<(), Error> {
+        let mut node = *node;
+        node.set_time(self.tempo.beats_to_ticks(beat));
+        self.get_track_mut(name).add_node(&node)
     }
 
-    #[no_mangle]
-    pub unsafe extern "C" fn demy_node_get_time(node: *mut Node) -> c_uint {
-        if node.is_null() { return 0 }
-        (*node).time
+    pub fn get_value_at_beat(&mut self, name: &str, beat: f64) -> f64 {
+        let time = self.tempo.beats_to_ticks(beat);
+        self.get_track(name).get_value_at(time)
     }
 }
 
+// A node as seen by an interpolator: a single scalar value, one component of a typed node.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Key {
+    time: f64,
+    value: f64,
+    in_tangent: Tangent,
+    out_tangent: Tangent,
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Key {
+    // Tangent value offsets only carry over from scalar nodes, the components of other value types
+    // get flat handles.
+    fn from_node(node: &Node, value: f64, scalar_tangents: bool) -> Self {
+        let tangent = |tangent: Tangent| {
+            Tangent::new(tangent.time, if scalar_tangents { tangent.value } else { 0_f64 })
+        };
 
-    #[test]
-    fn update_node() {
-        let mut tl = Timeline::new();
-        let track = tl.get_track_mut("camera");
+        Key {
+            time: node.get_time(),
+            value,
+            in_tangent: tangent(node.in_tangent),
+            out_tangent: tangent(node.out_tangent),
+        }
+    }
 
-        track.add_node(&Node::new(10, 1_f64, InterpType::None));
+    pub fn get_time(&self) -> f64 { self.time }
+    pub fn get_value(&self) -> f64 { self.value }
+    pub fn get_in_tangent(&self) -> Tangent { self.in_tangent }
+    pub fn get_out_tangent(&self) -> Tangent { self.out_tangent }
+}
 
-        track.update_node_at(10, &Node::new(20, 2_f64, InterpType::None));
-        assert!(track.nodes().count() == 2);
+// The keys surrounding the sampled time. `prev` and `next` are the neighbours of `from` and `to`
+// on the track, if any, so that splines can look past the segment being interpolated.
+pub struct Segment {
+    pub prev: Option<Key>,
+    pub from: Key,
+    pub to: Key,
+    pub next: Option<Key>,
+}
 
-        assert!(track.get_node_at(20).unwrap().get_time() == 20);
-        assert!(track.get_node_at(20).unwrap().get_value() == 2_f64);
+impl Segment {
+    pub fn duration(&self) -> f64 { self.to.get_time() - self.from.get_time() }
 
-        assert!(track.get_node_at(10).is_none());
+    // Slope of the line between two keys, in value per tick.
+    fn slope(a: &Key, b: &Key) -> f64 {
+        (b.get_value() - a.get_value()) / (b.get_time() - a.get_time())
+    }
+}
 
-        track.add_node(&Node::new(10, 1_f64, InterpType::None));
-        assert!(track.nodes().count() == 3);
+pub type Interpolator = fn(segment: &Segment, t: f64) -> f64;
 
-        assert!(track.get_node_at(20).unwrap().get_time() == 20);
-        assert!(track.get_node_at(20).unwrap().get_value() == 2_f64);
+pub type InterpId = u32;
 
-        assert!(track.get_node_at(10).unwrap().get_time() == 10);
-        assert!(track.get_node_at(10).unwrap().get_value() == 1_f64);
+// A timeline's custom interpolators by InterpId, shared with its tracks. Names a loaded file uses
+// have no function until they are registered.
+type InterpRegistry = Arc<RwLock<Vec<(String, Option<CustomInterp>)>>>;
 
-        track.update_node_at(10, &Node::new(30, 3_f64, InterpType::None));
+fn internal_registered(registry: &InterpRegistry, id: InterpId) -> Option<CustomInterp> {
+    registry.read().unwrap().get(id as usize).and_then(|&(_, custom)| custom)
+}
 
-        assert!(track.get_node_at(10).is_none());
+#[derive(Copy, Clone)]
+pub enum CustomInterp {
+    Rust(Interpolator),
+    C(ffi::CInterpolator),
+}
 
-        assert!(track.get_node_at(20).unwrap().get_time() == 20);
-        assert!(track.get_node_at(20).unwrap().get_value() == 2_f64);
+pub fn interp_none(segment: &Segment, _t: f64) -> f64 { segment.from.get_value() }
+pub fn interp_linear(segment: &Segment, t: f64) -> f64 {
+    segment.from.get_value() * (1_f64 - t) + (t * segment.to.get_value())
+}
 
-        assert!(track.get_node_at(30).unwrap().get_time() == 30);
-        assert!(track.get_node_at(30).unwrap().get_value() == 3_f64);
-    }
+macro_rules! eased_interpolators {
+    ($($interp:ident => $curve:path,)*) => {$(
+        pub fn $interp(segment: &Segment, t: f64) -> f64 { interp_linear(segment, $curve(t)) }
+    )*}
+}
 
-    #[test]
-    fn track_deletion() {
-        let mut tl = Timeline::new();
-        assert!(tl.tracks().count() == 0);
+eased_interpolators! {
+    interp_quad_in => easing::quad_in,
+    interp_quad_out => easing::quad_out,
+    interp_quad_in_out => easing::quad_in_out,
+    interp_cubic_in => easing::cubic_in,
+    interp_cubic_out => easing::cubic_out,
+    interp_cubic_in_out => easing::cubic_in_out,
+    interp_quart_in => easing::quart_in,
+    interp_quart_out => easing::quart_out,
+    interp_quart_in_out => easing::quart_in_out,
+    interp_expo_in => easing::expo_in,
+    interp_expo_out => easing::expo_out,
+    interp_expo_in_out => easing::expo_in_out,
+    interp_sine_in => easing::sine_in,
+    interp_sine_out => easing::sine_out,
+    interp_sine_in_out => easing::sine_in_out,
+    interp_back_in => easing::back_in,
+    interp_back_out => easing::back_out,
+    interp_back_in_out => easing::back_in_out,
+    interp_elastic_in => easing::elastic_in,
+    interp_elastic_out => easing::elastic_out,
+    interp_elastic_in_out => easing::elastic_in_out,
+    interp_bounce_in => easing::bounce_in,
+    interp_bounce_out => easing::bounce_out,
+    interp_bounce_in_out => easing::bounce_in_out,
+}
 
-        tl.get_track("camera");
+// The curve runs from `from` to `to` with control points at `from`'s out handle and `to`'s in
+// handle. Handle times are clamped into the segment so that the curve stays a function of time.
+pub fn interp_bezier(segment: &Segment, t: f64) -> f64 {
+    let (from, to) = (&segment.from, &segment.to);
+    let duration = segment.duration();
+
+    let x1 = clamp_unit(from.out_tangent.time / duration);
+    let x2 = clamp_unit(1_f64 + to.in_tangent.time / duration);
+
+    let y0 = from.get_value();
+    let y1 = y0 + from.out_tangent.value;
+    let y3 = to.get_value();
+    let y2 = y3 + to.in_tangent.value;
+
+    let s = bezier_solve(x1, x2, t);
+    bezier_eval(y0, y1, y2, y3, s)
+}
+
+fn clamp_unit(x: f64) -> f64 {
+    if x.is_nan() { return 0_f64 }
+    x.clamp(0_f64, 1_f64)
+}
+
+fn bezier_eval(p0: f64, p1: f64, p2: f64, p3: f64, s: f64) -> f64 {
+    let inv = 1_f64 - s;
+    inv * inv * inv * p0 + 3_f64 * inv * inv * s * p1 + 3_f64 * inv * s * s * p2 + s * s * s * p3
+}
+
+fn bezier_derivative(p0: f64, p1: f64, p2: f64, p3: f64, s: f64) -> f64 {
+    let inv = 1_f64 - s;
+    3_f64 * inv * inv * (p1 - p0) + 6_f64 * inv * s * (p2 - p1) + 3_f64 * s * s * (p3 - p2)
+}
+
+// Finds the curve parameter whose time component equals `x`, for a curve with time control points
+// (0, x1, x2, 1). Newton's method converges quickly on well-behaved handles, bisection catches the rest.
+fn bezier_solve(x1: f64, x2: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-9;
+
+    let mut s = x;
+    for _ in 0..8 {
+        let err = bezier_eval(0_f64, x1, x2, 1_f64, s) - x;
+        if err.abs() < EPSILON { return s }
+
+        let slope = bezier_derivative(0_f64, x1, x2, 1_f64, s);
+        if slope.abs() < EPSILON { break }
+
+        s -= err / slope;
+        if !(0_f64..=1_f64).contains(&s) { break }
+    }
+
+    let mut low = 0_f64;
+    let mut high = 1_f64;
+    s = x;
+    while high - low > EPSILON {
+        if bezier_eval(0_f64, x1, x2, 1_f64, s) < x { low = s } else { high = s }
+        s = (low + high) * 0.5_f64;
+    }
+
+    s
+}
+
+// Uniform Catmull-Rom: treats the neighbouring nodes as evenly spaced, regardless of their times.
+// A missing neighbour is replaced by the segment end next to it.
+pub fn interp_catmull_rom(segment: &Segment, t: f64) -> f64 {
+    let p1 = segment.from.get_value();
+    let p2 = segment.to.get_value();
+    let p0 = segment.prev.map_or(p1, |node| node.get_value());
+    let p3 = segment.next.map_or(p2, |node| node.get_value());
+
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    0.5_f64 * ((2_f64 * p1)
+        + (p2 - p0) * t
+        + (2_f64 * p0 - 5_f64 * p1 + 4_f64 * p2 - p3) * t2
+        + (3_f64 * p1 - p0 - 3_f64 * p2 + p3) * t3)
+}
+
+// Cubic Hermite with tangents taken from the average of the slopes on either side of each node,
+// which accounts for uneven spacing between nodes.
+pub fn interp_hermite(segment: &Segment, t: f64) -> f64 {
+    let slope = Segment::slope(&segment.from, &segment.to);
+
+    let m0 = match segment.prev {
+        Some(prev) => (Segment::slope(&prev, &segment.from) + slope) * 0.5_f64,
+        None => slope
+    };
+
+    let m1 = match segment.next {
+        Some(next) => (slope + Segment::slope(&segment.to, &next)) * 0.5_f64,
+        None => slope
+    };
+
+    hermite(segment.from.get_value(), m0, segment.to.get_value(), m1, segment.duration(), t)
+}
+
+// Fritsch-Butland monotone cubic: tangents are flattened wherever the data changes direction, so
+// the curve never overshoots the values of the nodes it passes through.
+pub fn interp_monotone_cubic(segment: &Segment, t: f64) -> f64 {
+    let slope = Segment::slope(&segment.from, &segment.to);
+    let duration = segment.duration();
+
+    let m0 = match segment.prev {
+        Some(prev) => {
+            let prev_duration = segment.from.get_time() - prev.get_time();
+            monotone_tangent(Segment::slope(&prev, &segment.from), prev_duration, slope, duration)
+        }
+        None => slope
+    };
+
+    let m1 = match segment.next {
+        Some(next) => {
+            let next_duration = next.get_time() - segment.to.get_time();
+            monotone_tangent(slope, duration, Segment::slope(&segment.to, &next), next_duration)
+        }
+        None => slope
+    };
+
+    hermite(segment.from.get_value(), m0, segment.to.get_value(), m1, duration, t)
+}
+
+fn monotone_tangent(left_slope: f64, left_duration: f64, right_slope: f64, right_duration: f64) -> f64 {
+    if left_slope * right_slope <= 0_f64 { return 0_f64 }
+
+    let left_weight = 2_f64 * right_duration + left_duration;
+    let right_weight = right_duration + 2_f64 * left_duration;
+    (left_weight + right_weight) / (left_weight / left_slope + right_weight / right_slope)
+}
+
+// Tangents are in value per tick and get scaled by the segment duration.
+fn hermite(p0: f64, m0: f64, p1: f64, m1: f64, duration: f64, t: f64) -> f64 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    (2_f64 * t3 - 3_f64 * t2 + 1_f64) * p0
+        + (t3 - 2_f64 * t2 + t) * m0 * duration
+        + (3_f64 * t2 - 2_f64 * t3) * p1
+        + (t3 - t2) * m1 * duration
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+pub struct Tangent {
+    pub time: f64,
+    pub value: f64,
+}
+
+impl Tangent {
+    pub fn new(time: f64, value: f64) -> Self {
+        Tangent { time, value }
+    }
+
+    pub fn is_zero(&self) -> bool { self.time == 0_f64 && self.value == 0_f64 }
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Node {
+    time: f64,
+    value: Value,
+    interp: InterpType,
+    #[serde(default, skip_serializing_if = "Tangent::is_zero")]
+    in_tangent: Tangent,
+    #[serde(default, skip_serializing_if = "Tangent::is_zero")]
+    out_tangent: Tangent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    custom_interp: Option<InterpId>,
+}
+
+impl Node {
+    pub fn new(time: f64, value: f64, interp: InterpType) -> Self {
+        Node::new_typed(time, Value::scalar(value), interp)
+    }
+
+    pub fn new_typed(time: f64, value: Value, interp: InterpType) -> Self {
+        Node { time, value, interp, in_tangent: Tangent::default(), out_tangent: Tangent::default(), custom_interp: None }
+    }
+
+    pub fn new_custom(time: f64, value: f64, interp: InterpId) -> Self {
+        let mut node = Node::new(time, value, InterpType::Custom);
+        node.custom_interp = Some(interp);
+        node
+    }
+
+    pub fn get_time(&self) -> f64 { self.time }
+    pub fn set_time(&mut self, time: f64) { self.time = time }
+
+    // Scalar access, reads the first component of typed values and writes a scalar value.
+    pub fn get_value(&self) -> f64 { self.value.get(0) }
+    pub fn set_value(&mut self, value: f64) { self.value = Value::scalar(value) }
+
+    pub fn get_typed_value(&self) -> Value { self.value }
+    pub fn set_typed_value(&mut self, value: Value) { self.value = value }
+
+    pub fn get_interpolator(&self) -> InterpType{ self.interp }
+    pub fn set_interpolator(&mut self, interp: InterpType) {
+        self.interp = interp;
+        if interp != InterpType::Custom { self.custom_interp = None }
+    }
+
+    pub fn get_custom_interpolator(&self) -> Option<InterpId> { self.custom_interp }
+    pub fn set_custom_interpolator(&mut self, interp: InterpId) {
+        self.interp = InterpType::Custom;
+        self.custom_interp = Some(interp);
+    }
+
+    // Handle offsets are relative to the node. The in handle shapes the segment ending at this node,
+    // the out handle the segment starting at it.
+    pub fn get_in_tangent(&self) -> Tangent { self.in_tangent }
+    pub fn set_in_tangent(&mut self, tangent: Tangent) { self.in_tangent = tangent }
+
+    pub fn get_out_tangent(&self) -> Tangent { self.out_tangent }
+    pub fn set_out_tangent(&mut self, tangent: Tangent) { self.out_tangent = tangent }
+}
+
+#[allow(clippy::missing_safety_doc)]
+pub mod ffi {
+
+    use super::*;
+    use handle::{HandleTable, NameHandles};
+    use std::any::Any;
+    use std::boxed::Box;
+    use std::cell::RefCell;
+    use std::ffi::{CStr, CString};
+    use std::os::raw::*;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::ptr;
+
+    pub type CInterpolator = extern "C" fn(prev: *const Key, from: *const Key, to: *const Key, next: *const Key, t: c_double) -> c_double;
+
+    // Enums are taken as plain integers, since a value C passes that is not one of the variants
+    // can't be held in the Rust enum. The same goes for the kind of a Value, which C sees as this
+    // struct under the name Value (see cbindgen.toml).
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    pub struct CValue {
+        pub kind: c_uint,
+        pub components: [c_double; 4],
+    }
+
+    impl TryFrom<CValue> for Value {
+        type Error = Error;
+
+        fn try_from(value: CValue) -> Result<Self, Error> {
+            let mut result = Value::zero(ValueKind::try_from(value.kind)?);
+            for (i, &component) in value.components.iter().enumerate().take(result.get_kind().component_count()) {
+                result.set(i, component);
+            }
+            Ok(result)
+        }
+    }
+
+    impl From<Value> for CValue {
+        fn from(value: Value) -> Self {
+            let mut components = [0_f64; 4];
+            components[..value.components().len()].copy_from_slice(value.components());
+            CValue { kind: value.get_kind() as c_uint, components }
+        }
+    }
+
+    // Like errno, the last error is per thread and is only overwritten by the next failing call.
+    thread_local! {
+        static LAST_ERROR: RefCell<(ErrorCode, CString)> = RefCell::new((ErrorCode::None, CString::default()));
+    }
+
+    fn internal_set_error(err: &Error) {
+        // the message is handed to C as a NUL-terminated string, drop any NULs inside it
+        let msg = err.to_string().replace('\0', "");
+        let msg = CString::new(msg).unwrap_or_default();
+        LAST_ERROR.with(|last| *last.borrow_mut() = (err.code(), msg));
+    }
+
+    // Every entry point runs its body through here. Errors and panics are recorded as the last error
+    // and turn into `fallback`, so neither unwinds into C.
+    fn internal_guard<T, F: FnOnce() -> Result<T, Error>>(fallback: T, body: F) -> T {
+        match panic::catch_unwind(AssertUnwindSafe(body)) {
+            Ok(Ok(value)) => value,
+            Ok(Err(err)) => { internal_set_error(&err); fallback }
+            Err(payload) => { internal_set_error(&Error::Panic(internal_panic_msg(payload))); fallback }
+        }
+    }
+
+    fn internal_panic_msg(payload: Box<dyn Any + Send>) -> String {
+        match payload.downcast::<String>() {
+            Ok(msg) => *msg,
+            Err(payload) => match payload.downcast_ref::<&'static str>() {
+                Some(msg) => String::from(*msg),
+                None => String::from("unknown panic")
+            }
+        }
+    }
+
+    unsafe fn internal_ref<'a, T>(arg: &'static str, ptr: *const T) -> Result<&'a T, Error> {
+        ptr.as_ref().ok_or(Error::NullPointer(arg))
+    }
+
+    unsafe fn internal_mut<'a, T>(arg: &'static str, ptr: *mut T) -> Result<&'a mut T, Error> {
+        ptr.as_mut().ok_or(Error::NullPointer(arg))
+    }
+
+    // Track handles name a track, so they survive the map rehashing when other tracks are added.
+    pub type TrackHandle = Handle;
+    pub type EventTrackHandle = Handle;
+    pub type NodeIterHandle = Handle;
+    pub type TrackIterHandle = Handle;
+    pub type EventIterHandle = Handle;
+
+    #[derive(Default)]
+    pub(crate) struct CAPIHandles {
+        pub(crate) tracks: NameHandles,
+        pub(crate) event_tracks: NameHandles,
+        node_iters: HandleTable<CAPINodeIterator>,
+        track_iters: HandleTable<CAPITrackIterator>,
+        event_iters: HandleTable<CAPIEventIterator>,
+        // Set by the first demy_tl_poll_reload.
+        reloader: Option<Reloader>,
+    }
+
+    fn internal_track_handle(tl: &mut Timeline, name: &str) -> TrackHandle {
+        tl.handles.tracks.get_or_insert(name)
+    }
+
+    fn internal_track(tl: &Timeline, tr: TrackHandle) -> Result<&Track, Error> {
+        tl.handles.tracks.get(tr).and_then(|name| tl.tracks.get(name)).ok_or(Error::StaleHandle)
+    }
+
+    // For the unrecorded edits, which clear the undo history like Timeline::get_track_mut.
+    fn internal_track_mut(tl: &mut Timeline, tr: TrackHandle) -> Result<&mut Track, Error> {
+        let Timeline { ref handles, ref mut tracks, ref mut history, .. } = *tl;
+        let track = handles.tracks.get(tr).and_then(move |name| tracks.get_mut(name)).ok_or(Error::StaleHandle)?;
+        history.clear();
+        Ok(track)
+    }
+
+    fn internal_event_track(tl: &Timeline, ev: EventTrackHandle) -> Result<&EventTrack, Error> {
+        tl.handles.event_tracks.get(ev).and_then(|name| tl.events.get(name)).ok_or(Error::StaleHandle)
+    }
+
+    fn internal_event_track_mut(tl: &mut Timeline, ev: EventTrackHandle) -> Result<&mut EventTrack, Error> {
+        let Timeline { ref handles, ref mut events, ref mut history, .. } = *tl;
+        let track = handles.event_tracks.get(ev).and_then(move |name| events.get_mut(name)).ok_or(Error::StaleHandle)?;
+        history.clear();
+        Ok(track)
+    }
+
+    unsafe fn internal_str<'a>(arg: &'static str, ptr: *const c_char) -> Result<&'a str, Error> {
+        if ptr.is_null() { return Err(Error::NullPointer(arg)) }
+        Ok(CStr::from_ptr(ptr).to_str()?)
+    }
+
+    #[no_mangle]
+    pub extern "C" fn demy_last_error_code() -> ErrorCode {
+        LAST_ERROR.with(|last| last.borrow().0)
+    }
+
+    // The returned string stays valid until the next failing call on this thread, it is empty if
+    // nothing failed yet.
+    #[no_mangle]
+    pub extern "C" fn demy_last_error_message() -> *const c_char {
+        LAST_ERROR.with(|last| last.borrow().1.as_ptr())
+    }
+
+    #[no_mangle]
+    pub extern "C" fn demy_clear_last_error() {
+        LAST_ERROR.with(|last| *last.borrow_mut() = (ErrorCode::None, CString::default()));
+    }
+
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_new() -> *mut Timeline {
+        internal_guard(ptr::null_mut(), || {
+            let tl = Box::new(Timeline::new());
+            Ok(Box::into_raw(tl))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_free(tl: *mut Timeline) {
+        if tl.is_null() { return }
+        internal_guard((), || { drop(Box::from_raw(tl)); Ok(()) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_track_get(tl: *mut Timeline, name: *const c_char) -> TrackHandle {
+        internal_guard(Handle::null(), || {
+            let name = internal_str("name", name)?;
+            let tl = internal_mut("tl", tl)?;
+            tl.get_track(name);
+            Ok(internal_track_handle(tl, name))
+        })
+    }
+
+    // Returns the null handle if the track already exists with a different value type.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_typed_track_get(tl: *mut Timeline, name: *const c_char, kind: c_uint) -> TrackHandle {
+        internal_guard(Handle::null(), || {
+            let name = internal_str("name", name)?;
+            let kind = ValueKind::try_from(kind)?;
+            let tl = internal_mut("tl", tl)?;
+            tl.internal_typed_track(name, kind)?;
+            Ok(internal_track_handle(tl, name))
+        })
+    }
+
+    // Handles to the deleted track go stale, even if a track with the same name is added later.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_track_del(tl: *mut Timeline, name: *const c_char) -> bool {
+        internal_guard(false, || {
+            let name = internal_str("name", name)?;
+            Ok(internal_mut("tl", tl)?.del_track(name))
+        })
+    }
+
+    // Checks a handle without touching the last error.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_is_valid(tl: *const Timeline, tr: TrackHandle) -> bool {
+        match tl.as_ref() {
+            Some(tl) => internal_track(tl, tr).is_ok(),
+            None => false
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_add_node(tl: *mut Timeline, tr: TrackHandle, time: c_double, value: c_double, interp: c_uint) -> bool {
+        internal_guard(false, || {
+            let node = Node::new(time, value, InterpType::try_from(interp)?);
+            internal_track_mut(internal_mut("tl", tl)?, tr)?.add_node(&node)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_add_typed_node(tl: *mut Timeline, tr: TrackHandle, time: c_double, value: CValue, interp: c_uint) -> bool {
+        internal_guard(false, || {
+            let node = Node::new_typed(time, Value::try_from(value)?, InterpType::try_from(interp)?);
+            internal_track_mut(internal_mut("tl", tl)?, tr)?.add_node(&node)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_get_kind(tl: *const Timeline, tr: TrackHandle) -> ValueKind {
+        internal_guard(ValueKind::Scalar, || Ok(internal_track(internal_ref("tl", tl)?, tr)?.get_kind()))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_del_node(tl: *mut Timeline, tr: TrackHandle, time: c_double) -> bool {
+        internal_guard(false, || {
+            internal_track_mut(internal_mut("tl", tl)?, tr)?.del_node_at(time)?;
+            Ok(true)
+        })
+    }
+
+    // Node pointers stay valid until the track is next modified. Returns null without setting an
+    // error if there is no node at the given time.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_get_node(tl: *const Timeline, tr: TrackHandle, time: c_double) -> *const Node {
+        internal_guard(ptr::null(), || {
+            match internal_track(internal_ref("tl", tl)?, tr)?.get_node_at(time) {
+                Some(node) => Ok(node),
+                None => Ok(ptr::null())
+            }
+        })
+    }
+
+    pub struct CAPINodeIterator {
+        track: TrackHandle,
+        index: usize,
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_iter_begin(tl: *mut Timeline, tr: TrackHandle) -> NodeIterHandle {
+        internal_guard(Handle::null(), || {
+            let tl = internal_mut("tl", tl)?;
+            internal_track(tl, tr)?;
+            Ok(tl.handles.node_iters.insert(CAPINodeIterator { track: tr, index: 0 }))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_iter_end(tl: *mut Timeline, tr: TrackHandle) -> NodeIterHandle {
+        internal_guard(Handle::null(), || {
+            let tl = internal_mut("tl", tl)?;
+            let index = internal_track(tl, tr)?.node_count();
+            Ok(tl.handles.node_iters.insert(CAPINodeIterator { track: tr, index }))
+        })
+    }
+
+    // Advancing an iterator that is already past the end frees it and sets it to the null handle.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_iter_next(tl: *mut Timeline, iter: *mut NodeIterHandle) {
+        internal_guard((), || {
+            let tl = internal_mut("tl", tl)?;
+            let handle = internal_mut("iter", iter)?;
+            let (track, index) = match tl.handles.node_iters.get(*handle) {
+                Some(it) => (it.track, it.index),
+                None => return Err(Error::StaleHandle)
+            };
+
+            if index >= internal_track(tl, track)?.node_count() {
+                tl.handles.node_iters.remove(*handle);
+                *handle = Handle::null();
+                return Ok(())
+            }
+
+            if let Some(it) = tl.handles.node_iters.get_mut(*handle) { it.index += 1 }
+            Ok(())
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_iter_free(tl: *mut Timeline, iter: NodeIterHandle) {
+        if iter.is_null() { return }
+        internal_guard((), || {
+            internal_mut("tl", tl)?.handles.node_iters.remove(iter).ok_or(Error::StaleHandle)?;
+            Ok(())
+        })
+    }
+
+    // False if either iterator is stale.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_iter_are_eq(tl: *const Timeline, a: NodeIterHandle, b: NodeIterHandle) -> bool {
+        let iters = match tl.as_ref() {
+            Some(tl) => &tl.handles.node_iters,
+            None => return false
+        };
+
+        match (iters.get(a), iters.get(b)) {
+            (Some(a), Some(b)) => a.track == b.track && a.index == b.index,
+            _ => false
+        }
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_iter_get(tl: *const Timeline, iter: NodeIterHandle) -> *const Node {
+        internal_guard(ptr::null(), || {
+            let tl = internal_ref("tl", tl)?;
+            let iter = tl.handles.node_iters.get(iter).ok_or(Error::StaleHandle)?;
+            let track = internal_track(tl, iter.track)?;
+            match track.get_node_by_index(iter.index) {
+                Some(node) => Ok(node),
+                None => Err(Error::IndexOutOfRange { index: iter.index, len: track.node_count() })
+            }
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_get_value_at(tl: *const Timeline, tr: TrackHandle, time: c_double) -> c_double {
+        internal_guard(0_f64, || Ok(internal_track(internal_ref("tl", tl)?, tr)?.get_value_at(time)))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_get_typed_value_at(tl: *const Timeline, tr: TrackHandle, time: c_double) -> CValue {
+        internal_guard(CValue::from(Value::scalar(0_f64)), || {
+            Ok(CValue::from(internal_track(internal_ref("tl", tl)?, tr)?.get_typed_value_at(time)))
+        })
+    }
+
+    // Copies the name into `buffer` the same way demy_event_get_text does, returning its full length.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_get_name(tl: *const Timeline, tr: TrackHandle, buffer: *mut c_char, size: usize) -> usize {
+        internal_guard(0, || Ok(internal_copy_str(internal_track(internal_ref("tl", tl)?, tr)?.get_name(), buffer, size)))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_node_count(tl: *const Timeline, tr: TrackHandle) -> usize {
+        internal_guard(0, || Ok(internal_track(internal_ref("tl", tl)?, tr)?.node_count()))
+    }
+
+    // Returns null if the index is past the last node.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_get_node_by_index(tl: *const Timeline, tr: TrackHandle, index: usize) -> *const Node {
+        internal_guard(ptr::null(), || {
+            let track = internal_track(internal_ref("tl", tl)?, tr)?;
+            match track.get_node_by_index(index) {
+                Some(node) => Ok(node),
+                None => Err(Error::IndexOutOfRange { index, len: track.node_count() })
+            }
+        })
+    }
+
+    // A snapshot of the timeline's tracks, ordered by name. Tracks deleted after the snapshot
+    // come back as stale handles.
+    pub struct CAPITrackIterator {
+        tracks: Vec<TrackHandle>,
+        index: usize,
+    }
+
+    // Iterates every track of the timeline. Call demy_tl_track_iter_next until it returns the null handle.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_track_iter(tl: *mut Timeline) -> TrackIterHandle {
+        internal_guard(Handle::null(), || {
+            let tl = internal_mut("tl", tl)?;
+            let names = tl.tracks.keys().cloned().collect::<Vec<String>>();
+            let tracks = names.iter().map(|name| internal_track_handle(tl, name)).collect();
+            Ok(tl.handles.track_iters.insert(CAPITrackIterator { tracks, index: 0 }))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_track_iter_next(tl: *mut Timeline, iter: TrackIterHandle) -> TrackHandle {
+        internal_guard(Handle::null(), || {
+            let iter = internal_mut("tl", tl)?.handles.track_iters.get_mut(iter).ok_or(Error::StaleHandle)?;
+            match iter.tracks.get(iter.index) {
+                Some(&track) => { iter.index += 1; Ok(track) }
+                None => Ok(Handle::null())
+            }
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_track_iter_free(tl: *mut Timeline, iter: TrackIterHandle) {
+        if iter.is_null() { return }
+        internal_guard((), || {
+            internal_mut("tl", tl)?.handles.track_iters.remove(iter).ok_or(Error::StaleHandle)?;
+            Ok(())
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_set_bpm(tl: *mut Timeline, time: c_double, bpm: c_double) -> bool {
+        internal_guard(false, || {
+            internal_mut("tl", tl)?.get_tempo_mut().set_bpm_at(time, bpm)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_get_bpm(tl: *const Timeline, time: c_double) -> c_double {
+        internal_guard(0_f64, || Ok(internal_ref("tl", tl)?.get_tempo().get_bpm_at(time)))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_set_ticks_per_second(tl: *mut Timeline, ticks_per_second: c_double) -> bool {
+        internal_guard(false, || {
+            internal_mut("tl", tl)?.get_tempo_mut().set_ticks_per_second(ticks_per_second)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_set_rows_per_beat(tl: *mut Timeline, rows_per_beat: c_uint) -> bool {
+        internal_guard(false, || {
+            internal_mut("tl", tl)?.get_tempo_mut().set_rows_per_beat(rows_per_beat)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_set_time_signature(tl: *mut Timeline, signature: TimeSignature) -> bool {
+        internal_guard(false, || {
+            internal_mut("tl", tl)?.get_tempo_mut().set_time_signature(signature)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_ticks_to_seconds(tl: *const Timeline, ticks: c_double) -> c_double {
+        internal_guard(0_f64, || Ok(internal_ref("tl", tl)?.get_tempo().ticks_to_seconds(ticks)))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_seconds_to_ticks(tl: *const Timeline, seconds: c_double) -> c_double {
+        internal_guard(0_f64, || Ok(internal_ref("tl", tl)?.get_tempo().seconds_to_ticks(seconds)))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_ticks_to_beats(tl: *const Timeline, ticks: c_double) -> c_double {
+        internal_guard(0_f64, || Ok(internal_ref("tl", tl)?.get_tempo().ticks_to_beats(ticks)))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_beats_to_ticks(tl: *const Timeline, beats: c_double) -> c_double {
+        internal_guard(0_f64, || Ok(internal_ref("tl", tl)?.get_tempo().beats_to_ticks(beats)))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_ticks_to_rows(tl: *const Timeline, ticks: c_double) -> c_double {
+        internal_guard(0_f64, || Ok(internal_ref("tl", tl)?.get_tempo().ticks_to_rows(ticks)))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_rows_to_ticks(tl: *const Timeline, rows: c_double) -> c_double {
+        internal_guard(0_f64, || Ok(internal_ref("tl", tl)?.get_tempo().rows_to_ticks(rows)))
+    }
+
+    pub struct CAPIEventIterator {
+        track: EventTrackHandle,
+        index: usize,
+        end: usize,
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_event_track_get(tl: *mut Timeline, name: *const c_char) -> EventTrackHandle {
+        internal_guard(Handle::null(), || {
+            let name = internal_str("name", name)?;
+            let tl = internal_mut("tl", tl)?;
+            tl.get_event_track(name);
+
+            Ok(tl.handles.event_tracks.get_or_insert(name))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_event_track_del(tl: *mut Timeline, name: *const c_char) -> bool {
+        internal_guard(false, || {
+            let name = internal_str("name", name)?;
+            Ok(internal_mut("tl", tl)?.del_event_track(name))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_ev_add_int(tl: *mut Timeline, ev: EventTrackHandle, time: c_double, value: i64) -> bool {
+        internal_guard(false, || {
+            internal_event_track_mut(internal_mut("tl", tl)?, ev)?.add_event(Event::new(time, Payload::Int(value)))?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_ev_add_text(tl: *mut Timeline, ev: EventTrackHandle, time: c_double, text: *const c_char) -> bool {
+        internal_guard(false, || {
+            let text = String::from(internal_str("text", text)?);
+            internal_event_track_mut(internal_mut("tl", tl)?, ev)?.add_event(Event::new(time, Payload::Text(text)))?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_ev_del(tl: *mut Timeline, ev: EventTrackHandle, time: c_double) -> bool {
+        internal_guard(false, || {
+            internal_event_track_mut(internal_mut("tl", tl)?, ev)?.del_event_at(time)?;
+            Ok(true)
+        })
+    }
+
+    // Iterates the events with prev_time < time <= now. Call demy_ev_iter_next until it returns null.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_ev_fired(tl: *mut Timeline, ev: EventTrackHandle, prev_time: c_double, now: c_double) -> EventIterHandle {
+        internal_guard(Handle::null(), || {
+            let tl = internal_mut("tl", tl)?;
+            let (index, end) = internal_event_track(tl, ev)?.internal_fired_range(prev_time, now);
+            Ok(tl.handles.event_iters.insert(CAPIEventIterator { track: ev, index, end }))
+        })
+    }
+
+    // Event pointers stay valid until the event track is next modified.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_ev_iter_next(tl: *mut Timeline, iter: EventIterHandle) -> *const Event {
+        internal_guard(ptr::null(), || {
+            let tl = internal_mut("tl", tl)?;
+            let (track, index, end) = match tl.handles.event_iters.get(iter) {
+                Some(it) => (it.track, it.index, it.end),
+                None => return Err(Error::StaleHandle)
+            };
+            if index >= end { return Ok(ptr::null()) }
+
+            let event = match internal_event_track(tl, track)?.internal_get(index) {
+                Some(event) => event as *const Event,
+                None => return Ok(ptr::null())
+            };
+
+            if let Some(it) = tl.handles.event_iters.get_mut(iter) { it.index += 1 }
+            Ok(event)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_ev_iter_free(tl: *mut Timeline, iter: EventIterHandle) {
+        if iter.is_null() { return }
+        internal_guard((), || {
+            internal_mut("tl", tl)?.handles.event_iters.remove(iter).ok_or(Error::StaleHandle)?;
+            Ok(())
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_event_get_time(event: *const Event) -> c_double {
+        internal_guard(0_f64, || Ok(internal_ref("event", event)?.get_time()))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_event_get_kind(event: *const Event) -> PayloadKind {
+        internal_guard(PayloadKind::Int, || Ok(internal_ref("event", event)?.get_payload().get_kind()))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_event_get_int(event: *const Event) -> i64 {
+        internal_guard(0, || {
+            match *internal_ref("event", event)?.get_payload() {
+                Payload::Int(value) => Ok(value),
+                Payload::Text(_) => Ok(0)
+            }
+        })
+    }
+
+    // Copies the text payload into `buffer`, truncated and NUL-terminated to fit `size` bytes. Returns
+    // the full length of the text, so a return value >= size means the copy was truncated.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_event_get_text(event: *const Event, buffer: *mut c_char, size: usize) -> usize {
+        internal_guard(0, || {
+            match *internal_ref("event", event)?.get_payload() {
+                Payload::Text(ref text) => Ok(internal_copy_str(text, buffer, size)),
+                Payload::Int(_) => Ok(0)
+            }
+        })
+    }
+
+    unsafe fn internal_copy_str(text: &str, buffer: *mut c_char, size: usize) -> usize {
+        if !buffer.is_null() && size > 0 {
+            let count = text.len().min(size - 1);
+            ptr::copy_nonoverlapping(text.as_ptr() as *const c_char, buffer, count);
+            *buffer.add(count) = 0;
+        }
+
+        text.len()
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_update_at(tl: *mut Timeline, tr: TrackHandle, time: c_double, node: *const Node) -> bool {
+        internal_guard(false, || {
+            let node = internal_ref("node", node)?;
+            internal_track_mut(internal_mut("tl", tl)?, tr)?.update_node_at(time, node)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_clone(node: *const Node) -> *mut Node {
+        internal_guard(ptr::null_mut(), || {
+            let new_node = Box::new(*internal_ref("node", node)?);
+            Ok(Box::into_raw(new_node))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_new(time: c_double, value: c_double, interp: c_uint) -> *mut Node {
+        internal_guard(ptr::null_mut(), || {
+            let new_node = Box::new(Node::new(time, value, InterpType::try_from(interp)?));
+            Ok(Box::into_raw(new_node))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_new_typed(time: c_double, value: CValue, interp: c_uint) -> *mut Node {
+        internal_guard(ptr::null_mut(), || {
+            let new_node = Box::new(Node::new_typed(time, Value::try_from(value)?, InterpType::try_from(interp)?));
+            Ok(Box::into_raw(new_node))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_free(node: *mut Node) {
+        if node.is_null() { return }
+        internal_guard((), || { drop(Box::from_raw(node)); Ok(()) })
+    }
+
+    use std::fs;
+    use std::io::Read;
+    use std::io::Write;
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_save(tl: *const Timeline, path: *const c_char) -> bool {
+        internal_guard(false, || {
+            let tl = internal_ref("tl", tl)?;
+            let path = internal_str("path", path)?;
+
+            let data = tl.save()?;
+            let mut fd = fs::File::create(path)?;
+            fd.write_all(data.as_bytes())?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_save_pretty(tl: *const Timeline, path: *const c_char) -> bool {
+        internal_guard(false, || {
+            let tl = internal_ref("tl", tl)?;
+            let path = internal_str("path", path)?;
+
+            let data = tl.save_pretty()?;
+            let mut fd = fs::File::create(path)?;
+            fd.write_all(data.as_bytes())?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_load(path: *const c_char) -> *mut Timeline {
+        demy_tl_load_with(ptr::null(), path)
+    }
+
+    // Loads the file with the custom interpolators registered on `registered`, see
+    // Timeline::load_with. A null `registered` loads like demy_tl_load.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_load_with(registered: *const Timeline, path: *const c_char) -> *mut Timeline {
+        internal_guard(ptr::null_mut(), || {
+            let path = internal_str("path", path)?;
+
+            let mut contents = String::new();
+            fs::File::open(path)?.read_to_string(&mut contents)?;
+            let tl = match registered.as_ref() {
+                Some(registered) => Timeline::load_with(&contents, registered)?,
+                None => Timeline::load(&contents)?
+            };
+            Ok(Box::into_raw(Box::new(tl)))
+        })
+    }
+
+    // Same as demy_tl_load, but fixes broken tracks the way Timeline::load_repaired does. The number
+    // of fixed problems is written to `repaired` unless it is null.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_load_repaired(path: *const c_char, repaired: *mut usize) -> *mut Timeline {
+        demy_tl_load_repaired_with(ptr::null(), path, repaired)
+    }
+
+    // See demy_tl_load_with.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_load_repaired_with(registered: *const Timeline, path: *const c_char, repaired: *mut usize) -> *mut Timeline {
+        internal_guard(ptr::null_mut(), || {
+            let path = internal_str("path", path)?;
+
+            let mut contents = String::new();
+            fs::File::open(path)?.read_to_string(&mut contents)?;
+            let (tl, violations) = match registered.as_ref() {
+                Some(registered) => Timeline::load_repaired_with(&contents, registered)?,
+                None => Timeline::load_repaired(&contents)?
+            };
+            if !repaired.is_null() {
+                *repaired = violations.len();
+            }
+            Ok(Box::into_raw(Box::new(tl)))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_save_binary(tl: *const Timeline, path: *const c_char, precision: c_uint) -> bool {
+        internal_guard(false, || {
+            let tl = internal_ref("tl", tl)?;
+            let path = internal_str("path", path)?;
+            let precision = Precision::try_from(precision)?;
+
+            let mut fd = fs::File::create(path)?;
+            fd.write_all(&tl.save_binary(precision))?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_load_binary(path: *const c_char) -> *mut Timeline {
+        demy_tl_load_binary_with(ptr::null(), path)
+    }
+
+    // See demy_tl_load_with.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_load_binary_with(registered: *const Timeline, path: *const c_char) -> *mut Timeline {
+        internal_guard(ptr::null_mut(), || {
+            let path = internal_str("path", path)?;
+
+            let mut contents = vec![];
+            fs::File::open(path)?.read_to_end(&mut contents)?;
+            let tl = match registered.as_ref() {
+                Some(registered) => Timeline::load_binary_with(&contents, registered)?,
+                None => Timeline::load_binary(&contents)?
+            };
+            Ok(Box::into_raw(Box::new(tl)))
+        })
+    }
+
+    // Bytes owned by the library, release them with demy_buffer_free. `data` is null if the call failed.
+    #[repr(C)]
+    pub struct CBuffer {
+        pub data: *mut u8,
+        pub len: usize,
+    }
+
+    fn internal_buffer(bytes: Vec<u8>) -> CBuffer {
+        let len = bytes.len();
+        CBuffer { data: Box::into_raw(bytes.into_boxed_slice()) as *mut u8, len }
+    }
+
+    unsafe fn internal_bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], Error> {
+        if data.is_null() {
+            return Err(Error::NullPointer("data"))
+        }
+        Ok(slice::from_raw_parts(data, len))
+    }
+
+    // Same as demy_tl_load, reading the JSON from `len` bytes at `data` instead of a file.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_load_from_memory(data: *const u8, len: usize) -> *mut Timeline {
+        demy_tl_load_from_memory_with(ptr::null(), data, len)
+    }
+
+    // See demy_tl_load_with.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_load_from_memory_with(registered: *const Timeline, data: *const u8, len: usize) -> *mut Timeline {
+        internal_guard(ptr::null_mut(), || {
+            let contents = str::from_utf8(internal_bytes(data, len)?)?;
+            let tl = match registered.as_ref() {
+                Some(registered) => Timeline::load_with(contents, registered)?,
+                None => Timeline::load(contents)?
+            };
+            Ok(Box::into_raw(Box::new(tl)))
+        })
+    }
+
+    // The JSON is not null terminated.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_save_to_buffer(tl: *const Timeline) -> CBuffer {
+        internal_guard(CBuffer { data: ptr::null_mut(), len: 0 }, || {
+            Ok(internal_buffer(internal_ref("tl", tl)?.save()?.into_bytes()))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_load_binary_from_memory(data: *const u8, len: usize) -> *mut Timeline {
+        demy_tl_load_binary_from_memory_with(ptr::null(), data, len)
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_load_binary_from_memory_with(registered: *const Timeline, data: *const u8, len: usize) -> *mut Timeline {
+        internal_guard(ptr::null_mut(), || {
+            let contents = internal_bytes(data, len)?;
+            let tl = match registered.as_ref() {
+                Some(registered) => Timeline::load_binary_with(contents, registered)?,
+                None => Timeline::load_binary(contents)?
+            };
+            Ok(Box::into_raw(Box::new(tl)))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_save_binary_to_buffer(tl: *const Timeline, precision: c_uint) -> CBuffer {
+        internal_guard(CBuffer { data: ptr::null_mut(), len: 0 }, || {
+            let tl = internal_ref("tl", tl)?;
+            Ok(internal_buffer(tl.save_binary(Precision::try_from(precision)?)))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_buffer_free(buffer: CBuffer) {
+        if buffer.data.is_null() { return }
+        internal_guard((), || {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+            Ok(())
+        })
+    }
+
+    // Recorded counterparts of demy_tr_add_node, demy_node_update_at, demy_tr_del_node and
+    // demy_tl_track_del, see Timeline::edit_add_node. The added node is copied. Those and every other
+    // function that changes the timeline clear the undo history.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_edit_add_node(tl: *mut Timeline, tr: TrackHandle, node: *const Node) -> bool {
+        internal_guard(false, || {
+            let tl = internal_mut("tl", tl)?;
+            let node = internal_ref("node", node)?;
+            let name = tl.handles.tracks.get(tr).cloned().ok_or(Error::StaleHandle)?;
+            tl.edit_add_node(&name, node)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_edit_update_node(tl: *mut Timeline, tr: TrackHandle, time: c_double, node: *const Node) -> bool {
+        internal_guard(false, || {
+            let tl = internal_mut("tl", tl)?;
+            let node = internal_ref("node", node)?;
+            let name = tl.handles.tracks.get(tr).cloned().ok_or(Error::StaleHandle)?;
+            tl.edit_update_node_at(&name, time, node)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_edit_del_node(tl: *mut Timeline, tr: TrackHandle, time: c_double) -> bool {
+        internal_guard(false, || {
+            let tl = internal_mut("tl", tl)?;
+            let name = tl.handles.tracks.get(tr).cloned().ok_or(Error::StaleHandle)?;
+            tl.edit_del_node_at(&name, time)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_edit_del_track(tl: *mut Timeline, name: *const c_char) -> bool {
+        internal_guard(false, || {
+            let name = internal_str("name", name)?;
+            Ok(internal_mut("tl", tl)?.edit_del_track(name))
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_begin_transaction(tl: *mut Timeline) -> bool {
+        internal_guard(false, || { internal_mut("tl", tl)?.begin_transaction(); Ok(true) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_commit_transaction(tl: *mut Timeline) -> bool {
+        internal_guard(false, || { internal_mut("tl", tl)?.commit_transaction(); Ok(true) })
+    }
+
+    // Return 1 if a step was undone or redone, 0 if there was none and -1 if it failed, in which case
+    // the timeline is left as it was.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_undo(tl: *mut Timeline) -> c_int {
+        internal_guard(-1, || Ok(internal_mut("tl", tl)?.undo()? as c_int))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_redo(tl: *mut Timeline) -> c_int {
+        internal_guard(-1, || Ok(internal_mut("tl", tl)?.redo()? as c_int))
+    }
+
+    // Reloads the timeline if the file at `path` changed since the previous call, see Reloader::poll.
+    // The first call for a path only starts watching it. Returns 1 if the timeline was reloaded, 0 if
+    // the file is unchanged and -1 if it failed to load, in which case the timeline is left as it was.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_poll_reload(tl: *mut Timeline, path: *const c_char) -> c_int {
+        internal_guard(-1, || {
+            let tl = internal_mut("tl", tl)?;
+            let path = internal_str("path", path)?;
+
+            let mut reloader = match tl.handles.reloader.take() {
+                Some(reloader) if reloader.get_path() == std::path::Path::new(path) => reloader,
+                _ => {
+                    tl.handles.reloader = Some(Reloader::new(path));
+                    return Ok(0)
+                }
+            };
+
+            let result = reloader.poll(tl);
+            tl.handles.reloader = Some(reloader);
+            Ok(result? as c_int)
+        })
+    }
+
+    // Range edits of a single track, see Track::shift_range. A failed edit leaves the track as it was.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_shift_range(tl: *mut Timeline, tr: TrackHandle, start: c_double, end: c_double, offset: c_double) -> bool {
+        internal_guard(false, || {
+            internal_track_mut(internal_mut("tl", tl)?, tr)?.shift_range(start, end, offset)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_scale_range(tl: *mut Timeline, tr: TrackHandle, start: c_double, end: c_double,
+                                                 pivot: c_double, factor: c_double) -> bool {
+        internal_guard(false, || {
+            internal_track_mut(internal_mut("tl", tl)?, tr)?.scale_range(start, end, pivot, factor)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_insert_time(tl: *mut Timeline, tr: TrackHandle, at: c_double, duration: c_double) -> bool {
+        internal_guard(false, || {
+            internal_track_mut(internal_mut("tl", tl)?, tr)?.insert_time(at, duration)?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tr_remove_time(tl: *mut Timeline, tr: TrackHandle, start: c_double, end: c_double) -> bool {
+        internal_guard(false, || {
+            internal_track_mut(internal_mut("tl", tl)?, tr)?.remove_time(start, end)?;
+            Ok(true)
+        })
+    }
+
+    // The same edits over every track and event track, see Timeline::shift_range. The edit variants
+    // are recorded for undo as a single step.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_shift_range(tl: *mut Timeline, start: c_double, end: c_double, offset: c_double) -> bool {
+        internal_guard(false, || { internal_mut("tl", tl)?.shift_range(start, end, offset)?; Ok(true) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_scale_range(tl: *mut Timeline, start: c_double, end: c_double, pivot: c_double, factor: c_double) -> bool {
+        internal_guard(false, || { internal_mut("tl", tl)?.scale_range(start, end, pivot, factor)?; Ok(true) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_insert_time(tl: *mut Timeline, at: c_double, duration: c_double) -> bool {
+        internal_guard(false, || { internal_mut("tl", tl)?.insert_time(at, duration)?; Ok(true) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_remove_time(tl: *mut Timeline, start: c_double, end: c_double) -> bool {
+        internal_guard(false, || { internal_mut("tl", tl)?.remove_time(start, end)?; Ok(true) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_edit_shift_range(tl: *mut Timeline, start: c_double, end: c_double, offset: c_double) -> bool {
+        internal_guard(false, || { internal_mut("tl", tl)?.edit_shift_range(start, end, offset)?; Ok(true) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_edit_scale_range(tl: *mut Timeline, start: c_double, end: c_double, pivot: c_double, factor: c_double) -> bool {
+        internal_guard(false, || { internal_mut("tl", tl)?.edit_scale_range(start, end, pivot, factor)?; Ok(true) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_edit_insert_time(tl: *mut Timeline, at: c_double, duration: c_double) -> bool {
+        internal_guard(false, || { internal_mut("tl", tl)?.edit_insert_time(at, duration)?; Ok(true) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_edit_remove_time(tl: *mut Timeline, start: c_double, end: c_double) -> bool {
+        internal_guard(false, || { internal_mut("tl", tl)?.edit_remove_time(start, end)?; Ok(true) })
+    }
+
+    // Returns the id of the interpolator, or -1 if the arguments are invalid. The callback type is
+    // spelled out so the header generator sees a nullable CInterpolator rather than an opaque Option.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_register_interp(tl: *mut Timeline, name: *const c_char,
+        func: Option<extern "C" fn(*const Key, *const Key, *const Key, *const Key, c_double) -> c_double>) -> c_int {
+        internal_guard(-1, || {
+            let tl = internal_mut("tl", tl)?;
+            let func = func.ok_or(Error::NullPointer("func"))?;
+            let name = internal_str("name", name)?;
+            Ok(tl.internal_register_interp(name, CustomInterp::C(func)) as c_int)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_tl_check_interps(tl: *const Timeline) -> bool {
+        internal_guard(false, || {
+            internal_ref("tl", tl)?.check_interps()?;
+            Ok(true)
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_set_interp(node: *mut Node, interp: c_uint) {
+        internal_guard((), || {
+            let interp = InterpType::try_from(interp)?;
+            internal_mut("node", node)?.set_interpolator(interp);
+            Ok(())
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_set_custom_interp(node: *mut Node, interp: c_uint) {
+        internal_guard((), || { internal_mut("node", node)?.set_custom_interpolator(interp); Ok(()) })
+    }
+
+    // Returns -1 if the node does not use a custom interpolator.
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_get_custom_interp(node: *const Node) -> c_int {
+        internal_guard(-1, || {
+            match internal_ref("node", node)?.custom_interp {
+                Some(id) => Ok(id as c_int),
+                None => Ok(-1)
+            }
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_get_interp(node: *const Node) -> InterpType {
+        internal_guard(InterpType::None, || Ok(internal_ref("node", node)?.interp))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_set_in_tangent(node: *mut Node, tangent: Tangent) {
+        internal_guard((), || { internal_mut("node", node)?.in_tangent = tangent; Ok(()) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_get_in_tangent(node: *const Node) -> Tangent {
+        internal_guard(Tangent::default(), || Ok(internal_ref("node", node)?.in_tangent))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_set_out_tangent(node: *mut Node, tangent: Tangent) {
+        internal_guard((), || { internal_mut("node", node)?.out_tangent = tangent; Ok(()) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_get_out_tangent(node: *const Node) -> Tangent {
+        internal_guard(Tangent::default(), || Ok(internal_ref("node", node)?.out_tangent))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_set_value(node: *mut Node, value: c_double) {
+        internal_guard((), || { internal_mut("node", node)?.set_value(value); Ok(()) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_get_value(node: *const Node) -> c_double {
+        internal_guard(0_f64, || Ok(internal_ref("node", node)?.get_value()))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_set_typed_value(node: *mut Node, value: CValue) {
+        internal_guard((), || {
+            let value = Value::try_from(value)?;
+            internal_mut("node", node)?.set_typed_value(value);
+            Ok(())
+        })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_get_typed_value(node: *const Node) -> CValue {
+        internal_guard(CValue::from(Value::scalar(0_f64)), || Ok(CValue::from(internal_ref("node", node)?.get_typed_value())))
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_set_time(node: *mut Node, time: c_double) {
+        internal_guard((), || { internal_mut("node", node)?.time = time; Ok(()) })
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn demy_node_get_time(node: *const Node) -> c_double {
+        internal_guard(0_f64, || Ok(internal_ref("node", node)?.time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_node() {
+        let mut tl = Timeline::new();
+        let track = tl.get_track_mut("camera");
+
+        track.add_node(&Node::new(10_f64, 1_f64, InterpType::None)).unwrap();
+
+        track.update_node_at(10_f64, &Node::new(20_f64, 2_f64, InterpType::None)).unwrap();
+        assert!(track.nodes().count() == 2);
+
+        assert!(track.get_node_at(20_f64).unwrap().get_time() == 20_f64);
+        assert!(track.get_node_at(20_f64).unwrap().get_value() == 2_f64);
+
+        assert!(track.get_node_at(10_f64).is_none());
+
+        track.add_node(&Node::new(10_f64, 1_f64, InterpType::None)).unwrap();
+        assert!(track.nodes().count() == 3);
+
+        assert!(track.get_node_at(20_f64).unwrap().get_time() == 20_f64);
+        assert!(track.get_node_at(20_f64).unwrap().get_value() == 2_f64);
+
+        assert!(track.get_node_at(10_f64).unwrap().get_time() == 10_f64);
+        assert!(track.get_node_at(10_f64).unwrap().get_value() == 1_f64);
+
+        track.update_node_at(10_f64, &Node::new(30_f64, 3_f64, InterpType::None)).unwrap();
+
+        assert!(track.get_node_at(10_f64).is_none());
+
+        assert!(track.get_node_at(20_f64).unwrap().get_time() == 20_f64);
+        assert!(track.get_node_at(20_f64).unwrap().get_value() == 2_f64);
+
+        assert!(track.get_node_at(30_f64).unwrap().get_time() == 30_f64);
+        assert!(track.get_node_at(30_f64).unwrap().get_value() == 3_f64);
+    }
+
+    #[test]
+    fn errors() {
+        let mut tl = Timeline::new();
+
+        {
+            let track = tl.get_track_mut("camera");
+            track.add_node(&Node::new(10_f64, 1_f64, InterpType::None)).unwrap();
+            track.add_node(&Node::new(20_f64, 2_f64, InterpType::None)).unwrap();
+
+            match track.add_node(&Node::new(0_f64, 1_f64, InterpType::None)) {
+                Err(Error::ZeroTimeInsert) => (),
+                other => panic!("unexpected result: {:?}", other)
+            }
+
+            match track.add_node(&Node::new(10_f64, 1_f64, InterpType::None)) {
+                Err(Error::DuplicateTime(time)) => assert_eq!(time, 10_f64),
+                other => panic!("unexpected result: {:?}", other)
+            }
+
+            match track.del_node_at(15_f64) {
+                Err(Error::NodeNotFound(time)) => assert_eq!(time, 15_f64),
+                other => panic!("unexpected result: {:?}", other)
+            }
+
+            // a failed move leaves the node where it was
+            assert!(track.update_node_at(10_f64, &Node::new(20_f64, 5_f64, InterpType::None)).is_err());
+            assert_eq!(track.get_node_at(10_f64).unwrap().get_value(), 1_f64);
+            assert_eq!(track.get_node_at(20_f64).unwrap().get_value(), 2_f64);
+            assert_eq!(track.nodes().count(), 3);
+
+            // nodes that couldn't be saved and loaded again are refused
+            match track.add_node(&Node::new(30_f64, f64::NAN, InterpType::None)) {
+                Err(Error::InvalidValue(value)) => assert!(value.is_nan()),
+                other => panic!("unexpected result: {:?}", other)
+            }
+
+            match track.update_node_at(20_f64, &Node::new_custom(20_f64, 2_f64, 4)) {
+                Err(Error::UnknownInterp(id)) => assert_eq!(id, 4),
+                other => panic!("unexpected result: {:?}", other)
+            }
+        }
+
+        match tl.get_typed_track_mut("camera", ValueKind::Vec3) {
+            Err(Error::ValueKindMismatch { expected, found }) => {
+                assert_eq!(expected, ValueKind::Vec3);
+                assert_eq!(found, ValueKind::Scalar);
+            }
+            _ => panic!("expected a value kind mismatch")
+        }
+
+        match Timeline::load("{\"tracks\": {}\n  \"interps\": []}") {
+            Err(Error::Parse { line, column, msg }) => {
+                assert_eq!(line, 2);
+                assert_eq!(column, 3);
+                assert!(!msg.contains("at line"));
+            }
+            _ => panic!("expected a parse error")
+        }
+
+        match tl.get_tempo_mut().set_bpm_at(0_f64, -1_f64) {
+            Err(err) => assert_eq!(err.to_string(), "BPM -1 is not a finite, positive number."),
+            Ok(()) => panic!("negative bpm was accepted")
+        }
+    }
+
+    #[test]
+    fn ffi_last_error() {
+        use std::ffi::{CStr, CString};
+        use std::thread;
+
+        unsafe {
+            let tl = ffi::demy_tl_new();
+            let name = CString::new("camera").unwrap();
+            let track = ffi::demy_tl_track_get(tl, name.as_ptr());
+
+            assert!(ffi::demy_tr_add_node(tl, track, 10_f64, 1_f64, InterpType::Linear as u32));
+            assert!(!ffi::demy_tr_add_node(tl, track, 10_f64, 2_f64, InterpType::Linear as u32));
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::DuplicateTime);
+            let msg = CStr::from_ptr(ffi::demy_last_error_message()).to_str().unwrap();
+            assert_eq!(msg, "Something already exists at time 10.");
+
+            // successful calls leave the last error alone
+            assert!(ffi::demy_tr_del_node(tl, track, 10_f64));
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::DuplicateTime);
+
+            assert!(!ffi::demy_tr_del_node(tl, track, 10_f64));
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::NodeNotFound);
+
+            let missing = std::env::temp_dir().join("demy_ffi_last_error_missing.json");
+            let missing = CString::new(missing.to_str().unwrap()).unwrap();
+            assert!(ffi::demy_tl_load(missing.as_ptr()).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::Io);
+
+            assert!(ffi::demy_tl_load(std::ptr::null()).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::NullPointer);
+
+            assert!(!ffi::demy_tr_add_node(std::ptr::null_mut(), track, 10_f64, 1_f64, InterpType::Linear as u32));
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::NullPointer);
+
+            let invalid = [0xff_u8, 0xfe, 0];
+            assert!(ffi::demy_tl_track_get(tl, invalid.as_ptr() as *const _).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::InvalidUtf8);
+
+            // enums from C are checked before they become Rust enums
+            assert!(!ffi::demy_tr_add_node(tl, track, 10_f64, 1_f64, 31));
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::InvalidEnum);
+            assert!(ffi::demy_tl_typed_track_get(tl, name.as_ptr(), u32::MAX).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::InvalidEnum);
+            let value = ffi::CValue { kind: 6, components: [0_f64; 4] };
+            assert!(ffi::demy_node_new_typed(10_f64, value, InterpType::Linear as u32).is_null());
+            let msg = CStr::from_ptr(ffi::demy_last_error_message()).to_str().unwrap();
+            assert_eq!(msg, "6 is not a valid ValueKind.");
+            assert_eq!(ffi::demy_tr_node_count(tl, track), 1);
+
+            // the end iterator points past the last node
+            let end = ffi::demy_tr_iter_end(tl, track);
+            assert!(ffi::demy_tr_iter_get(tl, end).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::IndexOutOfRange);
+            ffi::demy_tr_iter_free(tl, end);
+
+            assert!(!ffi::demy_tr_del_node(tl, track, 0_f64));
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::ZeroTimeDelete);
+            assert_eq!(ffi::demy_tr_node_count(tl, track), 1);
+
+            // errors are per thread
+            thread::spawn(|| assert_eq!(ffi::demy_last_error_code(), ErrorCode::None)).join().unwrap();
+
+            ffi::demy_clear_last_error();
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::None);
+            assert_eq!(CStr::from_ptr(ffi::demy_last_error_message()).to_bytes().len(), 0);
+
+            ffi::demy_tl_free(tl);
+        }
+    }
+
+    #[test]
+    fn ffi_buffers() {
+        unsafe {
+            let tl = ffi::demy_tl_new();
+            let name = std::ffi::CString::new("camera").unwrap();
+            let track = ffi::demy_tl_track_get(tl, name.as_ptr());
+            assert!(ffi::demy_tr_add_node(tl, track, 10_f64, 1_f64, InterpType::Linear as u32));
+
+            let json = ffi::demy_tl_save_to_buffer(tl);
+            let loaded = ffi::demy_tl_load_from_memory(json.data, json.len);
+            assert_eq!((*loaded).get_track("camera").get_value_at(5_f64), 0.5_f64);
+            ffi::demy_tl_free(loaded);
+
+            assert!(ffi::demy_tl_load_binary_from_memory(json.data, json.len).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::InvalidBinary);
+            ffi::demy_buffer_free(json);
+
+            let packed = ffi::demy_tl_save_binary_to_buffer(tl, Precision::F32 as u32);
+            let loaded = ffi::demy_tl_load_binary_from_memory(packed.data, packed.len);
+            assert_eq!((*loaded).get_track("camera").get_value_at(5_f64), 0.5_f64);
+            ffi::demy_tl_free(loaded);
+            ffi::demy_buffer_free(packed);
+
+            assert!(ffi::demy_tl_save_binary_to_buffer(tl, 2).data.is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::InvalidEnum);
+
+            // custom interpolators are registered up front, as with files
+            (*tl).register_interp("step", interp_none);
+            (*tl).get_track_mut("camera").add_node(&Node::new_custom(20_f64, 2_f64, 0)).unwrap();
+            let json = ffi::demy_tl_save_to_buffer(tl);
+            assert!(ffi::demy_tl_load_from_memory(json.data, json.len).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::UnregisteredInterp);
+            let loaded = ffi::demy_tl_load_from_memory_with(tl, json.data, json.len);
+            assert_eq!((*loaded).get_track("camera").get_value_at(15_f64), 1_f64);
+            ffi::demy_tl_free(loaded);
+            ffi::demy_buffer_free(json);
+
+            let packed = ffi::demy_tl_save_binary_to_buffer(tl, Precision::F64 as u32);
+            assert!(ffi::demy_tl_load_binary_from_memory(packed.data, packed.len).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::UnregisteredInterp);
+            let loaded = ffi::demy_tl_load_binary_from_memory_with(tl, packed.data, packed.len);
+            assert_eq!((*loaded).get_track("camera").get_value_at(15_f64), 1_f64);
+            ffi::demy_tl_free(loaded);
+            ffi::demy_buffer_free(packed);
+
+            let invalid = [b'{', 0xff];
+            assert!(ffi::demy_tl_load_from_memory(invalid.as_ptr(), invalid.len()).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::InvalidUtf8);
+            assert!(ffi::demy_tl_load_from_memory(std::ptr::null(), 0).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::NullPointer);
+
+            assert!(ffi::demy_tl_save_to_buffer(std::ptr::null()).data.is_null());
+            ffi::demy_tl_free(tl);
+        }
+    }
+
+    #[test]
+    fn track_deletion() {
+        let mut tl = Timeline::new();
+        assert!(tl.tracks().count() == 0);
+
+        tl.get_track("camera");
         assert!(tl.tracks().count() == 1);
 
         tl.del_track("not_the_camera");
@@ -582,19 +2609,939 @@ mod tests {
         let mut tl = Timeline::new();
         {
             let track = tl.get_track_mut("camera");
-            assert!(track.add_node(&Node::new(10, 1_f64, InterpType::Linear)).is_none());
-            assert!(track.add_node(&Node::new(20, 2_f64, InterpType::Linear)).is_none());
+            assert!(track.add_node(&Node::new(10_f64, 1_f64, InterpType::Linear)).is_ok());
+            assert!(track.add_node(&Node::new(20_f64, 2_f64, InterpType::Linear)).is_ok());
 
             assert!(track.nodes().len() == 3);
         }
 
         let track = tl.get_track("camera");
 
-        let val = track.get_value_at(5);
+        let val = track.get_value_at(5_f64);
         assert!(0.001 > (0.5_f64 - val).abs(), "val: {}", val);
         
-        let val = track.get_value_at(15);
+        let val = track.get_value_at(15_f64);
+        assert!(0.001 > (1.5_f64 - val).abs(), "val: {}", val);
+    }
+
+    #[test]
+    fn bezier_interpolation() {
+        let mut tl = Timeline::new();
+        let track = tl.get_track_mut("camera");
+
+        // handles on the chord at thirds reproduce a straight line
+        let mut first = Node::new(0_f64, 0_f64, InterpType::None);
+        first.set_out_tangent(Tangent::new(10_f64, 1_f64));
+        assert!(track.update_node_at(0_f64, &first).is_ok());
+
+        let mut node = Node::new(30_f64, 3_f64, InterpType::Bezier);
+        node.set_in_tangent(Tangent::new(-10_f64, -1_f64));
+        assert!(track.add_node(&node).is_ok());
+
+        let val = track.get_value_at(15_f64);
         assert!(0.001 > (1.5_f64 - val).abs(), "val: {}", val);
+
+        // flat handles ease in and out around the midpoint
+        let mut node = Node::new(60_f64, 6_f64, InterpType::Bezier);
+        node.set_in_tangent(Tangent::new(-15_f64, 0_f64));
+        let mut prev = *track.get_node_at(30_f64).unwrap();
+        prev.set_out_tangent(Tangent::new(15_f64, 0_f64));
+        assert!(track.update_node_at(30_f64, &prev).is_ok());
+        assert!(track.add_node(&node).is_ok());
+
+        let val = track.get_value_at(45_f64);
+        assert!(0.001 > (4.5_f64 - val).abs(), "val: {}", val);
+        let val = track.get_value_at(35_f64);
+        assert!(val > 3_f64 && val < 3.5_f64, "val: {}", val);
+        let val = track.get_value_at(55_f64);
+        assert!(val > 5.5_f64 && val < 6_f64, "val: {}", val);
+    }
+
+    #[test]
+    fn spline_interpolation() {
+        let keys = [(10_f64, 1_f64), (20_f64, 2_f64), (30_f64, 3_f64), (40_f64, 3_f64), (50_f64, 0_f64)];
+        let mut tl = Timeline::new();
+
+        for &(name, interp) in &[("catmull_rom", InterpType::CatmullRom),
+                                 ("hermite", InterpType::Hermite),
+                                 ("monotone", InterpType::MonotoneCubic)] {
+            let track = tl.get_track_mut(name);
+            for &(time, value) in &keys {
+                assert!(track.add_node(&Node::new(time, value, interp)).is_ok());
+            }
+
+            // passes through every node
+            for &(time, value) in &keys {
+                let val = track.get_value_at(time);
+                assert!(0.001 > (value - val).abs(), "{} at {}: {}", name, time, val);
+            }
+
+            // evenly spaced collinear nodes stay on the line
+            let val = track.get_value_at(15_f64);
+            assert!(0.001 > (1.5_f64 - val).abs(), "{}: {}", name, val);
+        }
+
+        // catmull-rom overshoots the plateau, monotone cubic does not
+        let track = tl.get_track("catmull_rom");
+        assert!(track.get_value_at(35_f64) > 3_f64);
+
+        let track = tl.get_track("monotone");
+        for time in 30..41 {
+            let val = track.get_value_at(time as f64);
+            assert!(0.001 > (3_f64 - val).abs(), "at {}: {}", time, val);
+        }
+        for time in 40..51 {
+            let val = track.get_value_at(time as f64);
+            assert!((0_f64..=3_f64).contains(&val), "at {}: {}", time, val);
+        }
+    }
+
+    #[test]
+    fn eased_interpolation() {
+        let eased = [InterpType::QuadIn, InterpType::QuadOut, InterpType::QuadInOut,
+                     InterpType::CubicIn, InterpType::CubicOut, InterpType::CubicInOut,
+                     InterpType::QuartIn, InterpType::QuartOut, InterpType::QuartInOut,
+                     InterpType::ExpoIn, InterpType::ExpoOut, InterpType::ExpoInOut,
+                     InterpType::SineIn, InterpType::SineOut, InterpType::SineInOut,
+                     InterpType::BackIn, InterpType::BackOut, InterpType::BackInOut,
+                     InterpType::ElasticIn, InterpType::ElasticOut, InterpType::ElasticInOut,
+                     InterpType::BounceIn, InterpType::BounceOut, InterpType::BounceInOut];
+
+        let mut tl = Timeline::new();
+        for (i, &interp) in eased.iter().enumerate() {
+            let track = tl.get_track_mut(&i.to_string());
+            track.add_node(&Node::new(100_f64, 10_f64, interp)).unwrap();
+
+            for &(time, expected) in &[(0_f64, 0_f64), (100_f64, 10_f64)] {
+                let val = track.get_value_at(time);
+                assert!(0.001 > (expected - val).abs(), "{} at {}: {}", i, time, val);
+            }
+        }
+
+        let val = tl.get_track("0").get_value_at(50_f64);
+        assert!(0.001 > (2.5_f64 - val).abs(), "val: {}", val);
+        let val = tl.get_track("1").get_value_at(50_f64);
+        assert!(0.001 > (7.5_f64 - val).abs(), "val: {}", val);
+        let val = tl.get_track("2").get_value_at(50_f64);
+        assert!(0.001 > (5_f64 - val).abs(), "val: {}", val);
+
+        let loaded = Timeline::load(&tl.save().unwrap()).unwrap();
+        for (i, &interp) in eased.iter().enumerate() {
+            let node = loaded.tracks.get(&i.to_string()).unwrap().get_node_at(100_f64).unwrap();
+            assert_eq!(node.get_interpolator() as u32, interp as u32);
+        }
+    }
+
+    fn interp_half(segment: &Segment, t: f64) -> f64 {
+        interp_linear(segment, t * 0.5_f64)
+    }
+
+    extern "C" fn c_interp_step(_prev: *const Key, from: *const Key, to: *const Key, _next: *const Key, t: f64) -> f64 {
+        unsafe { if t < 0.5_f64 { (*from).get_value() } else { (*to).get_value() } }
+    }
+
+    #[test]
+    fn custom_interpolation() {
+        let serialized: String;
+        {
+            let mut tl = Timeline::new();
+            let half = tl.register_interp("half", interp_half);
+            let step = unsafe {
+                ffi::demy_tl_register_interp(&mut tl, b"step\0".as_ptr() as *const _, Some(c_interp_step))
+            };
+            assert_eq!(step, 1);
+
+            let track = tl.get_track_mut("camera");
+            assert!(track.add_node(&Node::new_custom(10_f64, 10_f64, half)).is_ok());
+            assert!(track.add_node(&Node::new_custom(20_f64, 20_f64, step as InterpId)).is_ok());
+
+            let val = track.get_value_at(5_f64);
+            assert!(0.001 > (2.5_f64 - val).abs(), "val: {}", val);
+            assert_eq!(track.get_value_at(14_f64), 10_f64);
+            assert_eq!(track.get_value_at(16_f64), 20_f64);
+
+            assert!(tl.check_interps().is_ok());
+            serialized = tl.save().unwrap();
+        }
+
+        // the functions have to be registered before loading, the file's ids win over the order
+        // they were registered in
+        match Timeline::load(&serialized) {
+            Err(err @ Error::UnregisteredInterp { .. }) => assert!(err.to_string().contains("'half'")),
+            other => panic!("unexpected result: {:?}", other.map(|_| ()))
+        }
+        let mut registered = Timeline::new();
+        assert_eq!(registered.register_interp("step", interp_none), 0);
+        assert!(Timeline::load_with(&serialized, &registered).is_err());
+        assert_eq!(registered.register_interp("half", interp_half), 1);
+        let mut tl = Timeline::load_with(&serialized, &registered).unwrap();
+        assert_eq!(tl.get_interp_id("half"), Some(0));
+        assert!(tl.check_interps().is_ok());
+
+        let val = tl.get_track("camera").get_value_at(5_f64);
+        assert!(0.001 > (2.5_f64 - val).abs(), "val: {}", val);
+
+        // nodes can't use a name without a function, or the custom type without a name
+        let mut unused = Timeline::new();
+        unused.register_interp("unused", interp_half);
+        let mut tl = Timeline::load(&unused.save().unwrap()).unwrap();
+        match tl.get_track_mut("camera").add_node(&Node::new_custom(10_f64, 1_f64, 0)) {
+            Err(Error::UnregisteredInterp { ref name, .. }) => assert_eq!(name, "unused"),
+            other => panic!("unexpected result: {:?}", other)
+        }
+        match tl.get_track_mut("camera").add_node(&Node::new(10_f64, 1_f64, InterpType::Custom)) {
+            Err(err @ Error::MissingCustomInterp) => assert_eq!(err.code(), ErrorCode::MissingCustomInterp),
+            other => panic!("unexpected result: {:?}", other)
+        }
+        tl.register_interp("unused", interp_half);
+        assert!(tl.get_track_mut("camera").add_node(&Node::new_custom(10_f64, 1_f64, 0)).is_ok());
+
+        let corrupt = serialized.replace(r#""custom_interp":1"#, r#""custom_interp":7"#);
+        assert!(Timeline::load_with(&corrupt, &registered).is_err());
+    }
+
+    #[test]
+    fn typed_values() {
+        let mut tl = Timeline::new();
+        {
+            let track = tl.get_typed_track_mut("camera.pos", ValueKind::Vec3).unwrap();
+            assert!(track.add_node(&Node::new_typed(10_f64, Value::vec3(10_f64, -10_f64, 2_f64), InterpType::Linear)).is_ok());
+            assert!(track.add_node(&Node::new(20_f64, 1_f64, InterpType::Linear)).is_err());
+
+            let val = track.get_typed_value_at(5_f64);
+            assert_eq!(val, Value::vec3(5_f64, -5_f64, 1_f64));
+        }
+
+        assert!(tl.get_typed_track_mut("camera.pos", ValueKind::Color).is_err());
+
+        {
+            let track = tl.get_typed_track_mut("camera.rot", ValueKind::Quat).unwrap();
+            let half = std::f64::consts::FRAC_1_SQRT_2;
+            assert!(track.add_node(&Node::new_typed(10_f64, Value::quat(0_f64, 0_f64, half, half), InterpType::Linear)).is_ok());
+
+            // a quarter turn about z, halfway is an eighth turn
+            let val = track.get_typed_value_at(5_f64);
+            let eighth = (std::f64::consts::PI / 8_f64).sin();
+            assert!(0.001 > (eighth - val.get(2)).abs(), "val: {:?}", val);
+            assert!(0.001 > (1_f64 - val.components().iter().map(|c| c * c).sum::<f64>()).abs());
+        }
+
+        {
+            let track = tl.get_typed_track_mut("fog", ValueKind::Color).unwrap();
+            assert!(track.add_node(&Node::new_typed(10_f64, Value::color(1_f64, 0.5_f64, 0_f64, 1_f64), InterpType::None)).is_ok());
+        }
+
+        let mut loaded = Timeline::load(&tl.save().unwrap()).unwrap();
+        assert_eq!(loaded.get_track("camera.pos").get_kind(), ValueKind::Vec3);
+        assert_eq!(loaded.get_track("camera.pos").get_typed_value_at(5_f64), Value::vec3(5_f64, -5_f64, 1_f64));
+        assert_eq!(loaded.get_track("fog").get_node_at(10_f64).unwrap().get_typed_value(), Value::color(1_f64, 0.5_f64, 0_f64, 1_f64));
+        assert_eq!(loaded.get_track("camera.rot").get_node_at(0_f64).unwrap().get_typed_value(), Value::zero(ValueKind::Quat));
+
+        let mismatched = r#"{"tracks":{"fog":{"nodes":[{"time":0,"value":0.0,"interp":"None"}],"name":"fog","kind":"Color"}}}"#;
+        assert!(Timeline::load(mismatched).is_err());
+    }
+
+    #[test]
+    fn event_tracks() {
+        let mut tl = Timeline::new();
+        {
+            let ev = tl.get_event_track_mut("scene");
+            assert!(ev.add_event(Event::new(20_f64, Payload::Text(String::from("tunnel")))).is_ok());
+            assert!(ev.add_event(Event::new(10_f64, Payload::Int(1))).is_ok());
+            assert!(ev.add_event(Event::new(30_f64, Payload::Int(3))).is_ok());
+            assert!(ev.add_event(Event::new(10_f64, Payload::Int(2))).is_err());
+        }
+
+        let fired_times = |ev: &EventTrack, prev_time, now| ev.fired(prev_time, now).map(|e| e.get_time()).collect::<Vec<_>>();
+        {
+            let ev = tl.get_event_track("scene");
+            assert_eq!(fired_times(ev, 0_f64, 10_f64), vec![10_f64]);
+            assert_eq!(fired_times(ev, 10_f64, 25_f64), vec![20_f64]);
+            assert_eq!(fired_times(ev, 5_f64, 30_f64), vec![10_f64, 20_f64, 30_f64]);
+            assert_eq!(fired_times(ev, 9.5_f64, 10.5_f64), vec![10_f64]);
+            assert_eq!(fired_times(ev, 30_f64, 100_f64), Vec::<f64>::new());
+            assert_eq!(fired_times(ev, 25_f64, 5_f64), Vec::<f64>::new());
+        }
+
+        let mut loaded = Timeline::load(&tl.save().unwrap()).unwrap();
+        assert_eq!(loaded.event_tracks().count(), 1);
+
+        unsafe {
+            let tl: *mut Timeline = &mut loaded;
+            let name = std::ffi::CString::new("scene").unwrap();
+            let ev = ffi::demy_tl_event_track_get(tl, name.as_ptr());
+            let iter = ffi::demy_ev_fired(tl, ev, 0_f64, 20_f64);
+            let first = ffi::demy_ev_iter_next(tl, iter);
+            assert_eq!(ffi::demy_event_get_kind(first), PayloadKind::Int);
+            assert_eq!(ffi::demy_event_get_int(first), 1);
+
+            let second = ffi::demy_ev_iter_next(tl, iter);
+            let mut buffer = [0 as std::os::raw::c_char; 4];
+            assert_eq!(ffi::demy_event_get_text(second, buffer.as_mut_ptr(), buffer.len()), 6);
+            assert_eq!(std::ffi::CStr::from_ptr(buffer.as_ptr()).to_str().unwrap(), "tun");
+
+            assert!(ffi::demy_ev_iter_next(tl, iter).is_null());
+            ffi::demy_ev_iter_free(tl, iter);
+        }
+
+        let ev = loaded.get_event_track_mut("scene");
+        assert_eq!(ev.get_event_at(20_f64).unwrap().get_payload(), &Payload::Text(String::from("tunnel")));
+        assert_eq!(ev.get_event_at(30_f64).unwrap().get_payload(), &Payload::Int(3));
+
+        assert!(ev.del_event_at(20_f64).is_ok());
+        assert!(ev.del_event_at(20_f64).is_err());
+    }
+
+    #[test]
+    fn tangents_serialize() {
+        let serialized: String;
+        {
+            let mut tl = Timeline::new();
+            let mut node = Node::new(10_f64, 1_f64, InterpType::Bezier);
+            node.set_in_tangent(Tangent::new(-2_f64, 0.5_f64));
+            node.set_out_tangent(Tangent::new(3_f64, -0.25_f64));
+            tl.get_track_mut("camera").add_node(&node).unwrap();
+            serialized = tl.save().unwrap();
+        }
+
+        let mut tl = Timeline::load(&serialized).unwrap();
+        let node = tl.get_track("camera").get_node_at(10_f64).unwrap();
+        assert_eq!(node.get_in_tangent().time, -2_f64);
+        assert_eq!(node.get_in_tangent().value, 0.5_f64);
+        assert_eq!(node.get_out_tangent().time, 3_f64);
+        assert_eq!(node.get_out_tangent().value, -0.25_f64);
+
+        // files written before tangents existed still load
+        let old = r#"{"tracks":{"camera":{"nodes":[{"time":0,"value":0.0,"interp":"None"}],"name":"camera"}}}"#;
+        let mut tl = Timeline::load(old).unwrap();
+        assert_eq!(tl.get_track("camera").get_node_at(0_f64).unwrap().get_out_tangent().time, 0_f64);
+    }
+
+    // The fixtures use a custom interpolator named "step".
+    fn load_fixture(json: &str) -> Timeline {
+        let mut registered = Timeline::new();
+        registered.register_interp("step", interp_none);
+        Timeline::load_with(json, &registered).unwrap()
+    }
+
+    fn load_binary_fixture(binary: &[u8]) -> Timeline {
+        let mut registered = Timeline::new();
+        registered.register_interp("step", interp_none);
+        Timeline::load_binary_with(binary, &registered).unwrap()
+    }
+
+    fn check_fixture(tl: &mut Timeline) {
+        assert_eq!(tl.tracks().count(), 4);
+        assert_eq!(tl.get_interp_id("step"), Some(0));
+
+        let camera = tl.get_track("camera.x");
+        assert_eq!(camera.node_count(), 4);
+        let node = camera.get_node_at(20_f64).unwrap();
+        assert_eq!(node.get_interpolator(), InterpType::Bezier);
+        assert_eq!(node.get_out_tangent().value, 0.5_f64);
+        assert_eq!(camera.get_node_at(30.5_f64).unwrap().get_interpolator(), InterpType::QuadInOut);
+
+        assert_eq!(tl.get_track("fade").get_node_at(40_f64).unwrap().get_custom_interpolator(), Some(0));
+        assert_eq!(tl.get_track("position").get_node_at(10_f64).unwrap().get_typed_value(), Value::vec3(1_f64, 2_f64, 3_f64));
+        assert_eq!(tl.get_track("tint").get_kind(), ValueKind::Color);
+
+        let scenes = tl.get_event_track_mut("scenes");
+        assert_eq!(scenes.get_event_at(15_f64).unwrap().get_payload(), &Payload::Text(String::from("tunnel")));
+        assert_eq!(scenes.get_event_at(25_f64).unwrap().get_payload(), &Payload::Int(3));
+
+        assert_eq!(tl.get_tempo().get_bpm_at(0_f64), 140_f64);
+        assert_eq!(tl.get_tempo().get_bpm_at(9000_f64), 70_f64);
+        assert_eq!(tl.get_tempo().get_rows_per_beat(), 4);
+    }
+
+    #[test]
+    fn file_versions() {
+        let v0 = include_str!("../tests/fixtures/v0.json");
+        let v1 = include_str!("../tests/fixtures/v1.json");
+
+        // files from before the version field are migrated forward
+        check_fixture(&mut load_fixture(v0));
+        check_fixture(&mut load_fixture(v1));
+
+        let mut tl = Timeline::load(include_str!("../tests/fixtures/v0_integer_times.json")).unwrap();
+        assert_eq!(tl.get_track("camera").get_value_at(150_f64), 2.5_f64);
+
+        let saved = load_fixture(v0).save().unwrap();
+        assert!(saved.starts_with(&format!("{{\"version\":{},", FORMAT_VERSION)));
+        check_fixture(&mut load_fixture(&saved));
+
+        let newer = v1.replacen("\"version\":1", &format!("\"version\":{}", FORMAT_VERSION + 1), 1);
+        match Timeline::load(&newer) {
+            Err(err @ Error::UnsupportedVersion { .. }) => {
+                assert_eq!(err.code(), ErrorCode::UnsupportedVersion);
+                assert_eq!(err.to_string(), format!("The file is format version {}, but only versions up to {} can be read.", FORMAT_VERSION + 1, FORMAT_VERSION));
+            }
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("a file from a newer version was accepted")
+        }
+    }
+
+    #[test]
+    fn load_validation() {
+        let broken = r#"{"version":1,"tracks":{
+            "a":{"nodes":[{"time":5,"value":1.0,"interp":"Linear"},{"time":2,"value":2.0,"interp":"Linear"},{"time":5,"value":3.0,"interp":"Linear"}],"name":"a","kind":"Scalar"},
+            "b":{"nodes":[],"name":"b","kind":"Scalar"},
+            "c":{"nodes":[{"time":0,"value":1.0,"interp":"Linear"},{"time":-1,"value":1.0,"interp":"Linear"},{"time":10,"value":4.0,"interp":"Linear","custom_interp":3},{"time":20,"value":6.0,"interp":"Custom"},{"time":30,"value":8.0,"interp":"Custom","custom_interp":5}],"name":"x","kind":"Scalar"}
+        },"events":{"e":{"events":[{"time":4,"payload":{"Int":1}},{"time":1,"payload":{"Int":2}}],"name":"e"}}}"#;
+
+        let expected = vec![
+            Violation::OutOfOrder { track: String::from("a"), time: 2_f64, prev: 5_f64 },
+            Violation::DuplicateTime { track: String::from("a"), time: 5_f64 },
+            Violation::MissingZeroNode { track: String::from("a"), first: 2_f64 },
+            Violation::NoNodes { track: String::from("b") },
+            Violation::NameMismatch { track: String::from("c"), name: String::from("x") },
+            Violation::InvalidTime { track: String::from("c"), time: -1_f64 },
+            Violation::UnknownInterp { track: String::from("c"), time: 10_f64, id: 3 },
+            Violation::MissingCustomInterp { track: String::from("c"), time: 20_f64 },
+            Violation::UnknownInterp { track: String::from("c"), time: 30_f64, id: 5 },
+            Violation::OutOfOrder { track: String::from("e"), time: 1_f64, prev: 4_f64 },
+        ];
+
+        match Timeline::load(broken) {
+            Err(Error::InvalidTimeline(violations)) => assert_eq!(violations, expected),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("a broken timeline was accepted")
+        }
+
+        let err = Timeline::load(broken).err().unwrap();
+        assert_eq!(err.code(), ErrorCode::InvalidTimeline);
+        assert!(err.to_string().contains("\n  track 'b' has no nodes"));
+
+        let (mut tl, violations) = Timeline::load_repaired(broken).unwrap();
+        assert_eq!(violations, expected);
+
+        let times = |track: &Track| track.nodes.iter().map(|node| node.time).collect::<Vec<_>>();
+        assert_eq!(times(tl.get_track("a")), vec![0_f64, 2_f64, 5_f64]);
+        assert_eq!(tl.get_track("a").get_value_at(5_f64), 1_f64);
+        assert_eq!(times(tl.get_track("b")), vec![0_f64]);
+        assert_eq!(tl.get_track("b").get_value_at(3_f64), 0_f64);
+        assert_eq!(tl.get_track("c").get_name(), "c");
+        assert_eq!(times(tl.get_track("c")), vec![0_f64, 10_f64, 20_f64, 30_f64]);
+        assert_eq!(tl.get_track("c").get_value_at(5_f64), 2.5_f64);
+        // custom interpolation without a known interpolator falls back to linear
+        assert_eq!(tl.get_track("c").get_value_at(15_f64), 5_f64);
+        assert_eq!(tl.get_track("c").get_value_at(25_f64), 7_f64);
+
+        let events = tl.get_event_track("e").events().map(|event| event.get_time()).collect::<Vec<_>>();
+        assert_eq!(events, vec![1_f64, 4_f64]);
+
+        // a repaired timeline saves as a valid one
+        assert_eq!(Timeline::load_repaired(&tl.save().unwrap()).unwrap().1, vec![]);
+
+        // repairing doesn't drop nodes that use unregistered interpolators
+        let v1 = include_str!("../tests/fixtures/v1.json");
+        match Timeline::load_repaired(v1) {
+            Err(Error::UnregisteredInterp { name, .. }) => assert_eq!(name, "step"),
+            _ => panic!("an unregistered interpolator was accepted")
+        }
+        let mut registered = Timeline::new();
+        registered.register_interp("step", interp_none);
+        assert_eq!(Timeline::load_repaired_with(v1, &registered).unwrap().1, vec![]);
+    }
+
+    #[test]
+    fn sync_loopback() {
+        use sync::{Client, Command, KeyType, Server};
+
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let editor = std::thread::spawn(move || {
+            let mut conn = server.accept().unwrap();
+            assert_eq!(conn.recv().unwrap(), Command::GetTrack(String::from("camera")));
+
+            conn.send(&Command::SetKey { track: 0, row: 4, value: 3_f32, key_type: KeyType::Step }).unwrap();
+            conn.send(&Command::SetKey { track: 0, row: 8, value: 5_f32, key_type: KeyType::Linear }).unwrap();
+            conn.send(&Command::SetKey { track: 0, row: 2, value: 1_f32, key_type: KeyType::Linear }).unwrap();
+            conn.send(&Command::Pause(true)).unwrap();
+            assert_eq!(conn.recv().unwrap(), Command::SetRow(12));
+
+            conn.send(&Command::SetKey { track: 0, row: 1, value: f32::NAN, key_type: KeyType::Step }).unwrap();
+            conn.send(&Command::Pause(false)).unwrap();
+            conn.send(&Command::DeleteKey { track: 0, row: 4 }).unwrap();
+            conn.send(&Command::DeleteKey { track: 0, row: 4 }).unwrap();
+            conn.send(&Command::DeleteKey { track: 0, row: 6 }).unwrap();
+            conn.send(&Command::SetKey { track: 7, row: 1, value: 1_f32, key_type: KeyType::Step }).unwrap();
+            conn.send(&Command::SaveTracks).unwrap();
+            assert!(conn.recv().is_err());
+        });
+
+        let mut tl = Timeline::new();
+        tl.get_track_mut("camera").add_node(&Node::new(100_f64, 9_f64, InterpType::Linear)).unwrap();
+
+        let mut client = Client::connect(addr).unwrap();
+        assert_eq!(client.get_track(&mut tl, "camera").unwrap(), 0);
+        // the track is only replaced once the editor's keys arrive
+        assert_eq!(tl.get_track("camera").node_count(), 2);
+
+        fn wait_for(client: &mut Client, tl: &mut Timeline, last: Command) -> Vec<Command> {
+            let mut commands = vec![];
+            while commands.last() != Some(&last) {
+                commands.extend(client.update(tl).unwrap());
+            }
+            commands
+        }
+
+        assert_eq!(wait_for(&mut client, &mut tl, Command::Pause(true)), vec![Command::Pause(true)]);
+        let row = |tl: &mut Timeline, row: f64| {
+            let time = tl.get_tempo().rows_to_ticks(row);
+            tl.get_track("camera").get_value_at(time)
+        };
+
+        // Rocket holds the first key's value before it, and keys pick the segment after them
+        assert_eq!(row(&mut tl, 1_f64), 1_f64);
+        assert_eq!(row(&mut tl, 3_f64), 2_f64);
+        assert_eq!(row(&mut tl, 6_f64), 3_f64);
+        assert_eq!(row(&mut tl, 9_f64), 5_f64);
+
+        assert_eq!(tl.get_track("camera").node_count(), 4);
+
+        client.set_row(12).unwrap();
+        client.set_row(12).unwrap();
+
+        // deleting keys that aren't there does nothing, and keys that can't be applied fail the
+        // update without losing the commands that came before them or being kept
+        let mut commands = vec![];
+        let mut errors = vec![];
+        while errors.len() < 2 {
+            match client.update(&mut tl) {
+                Ok(received) => commands.extend(received),
+                Err(err) => errors.push(err.code())
+            }
+        }
+        assert_eq!(errors, vec![ErrorCode::InvalidValue, ErrorCode::Protocol]);
+        commands.extend(wait_for(&mut client, &mut tl, Command::SaveTracks));
+        assert_eq!(commands, vec![Command::Pause(false), Command::SaveTracks]);
+        assert_eq!(row(&mut tl, 5_f64), 3_f64);
+        assert_eq!(tl.get_track("camera").node_count(), 3);
+
+        drop(client);
+        editor.join().unwrap();
+    }
+
+    #[test]
+    fn rocket_files() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<sync rows="128">
+  <!-- written by the original editor -->
+  <tracks>
+    <track name="fx:fade &amp; blur">
+      <key row="16" value="2.5" interpolation="1"/>
+      <key row="4" value="1.0" interpolation="2"/>
+      <key row="8" value="0.5" interpolation="0"/>
+    </track>
+    <track name="unused"/>
+  </tracks>
+</sync>"#;
+
+        let (mut tl, inexact) = Timeline::import_rocket(xml).unwrap();
+        assert_eq!(inexact, vec![Inexact::SmoothKey { track: String::from("fx:fade & blur"), row: 4 }]);
+        assert_eq!(tl.get_track("unused").node_count(), 1);
+
+        let row = |tl: &mut Timeline, row: f64| {
+            let time = tl.get_tempo().rows_to_ticks(row);
+            tl.get_track("fx:fade & blur").get_value_at(time)
+        };
+        assert_eq!(row(&mut tl, 2_f64), 1_f64);
+        assert_eq!(row(&mut tl, 6_f64), 0.75_f64);
+        assert_eq!(row(&mut tl, 12_f64), 0.5_f64);
+        assert_eq!(row(&mut tl, 17_f64), 2.5_f64);
+        assert_eq!(tl.get_track("fx:fade & blur").get_node_by_index(2).unwrap().get_interpolator(), InterpType::SineInOut);
+
+        // exporting and importing again keeps every value, apart from the smooth key's approximation
+        let (exported, inexact) = tl.export_rocket();
+        assert!(exported.contains(r#"<track name="fx:fade &amp; blur">"#), "{}", exported);
+        assert_eq!(inexact, vec![Inexact::Interp { track: String::from("fx:fade & blur"), time: tl.get_tempo().rows_to_ticks(8_f64), interp: InterpType::SineInOut, exported: sync::KeyType::Smooth }]);
+        let (mut reimported, _) = Timeline::import_rocket(&exported).unwrap();
+        for r in 0..20 {
+            assert_eq!(row(&mut reimported, r as f64), row(&mut tl, r as f64));
+        }
+
+        let mut tl = Timeline::new();
+        tl.get_tempo_mut().set_rows_per_beat(4).unwrap();
+        let half_row = tl.get_tempo().rows_to_ticks(2.5_f64);
+        tl.get_track_mut("camera").add_node(&Node::new(half_row, 1_f64, InterpType::Bezier)).unwrap();
+        tl.get_typed_track_mut("color", ValueKind::Color).unwrap();
+
+        let (exported, inexact) = tl.export_rocket();
+        assert!(exported.contains(r#"rowsPerBeat="4" beatsPerMin="120""#));
+        assert!(exported.contains(r#"<key row="0" value="0" interpolation="1" />"#), "{}", exported);
+        assert!(exported.contains(r#"<key row="3" value="1" interpolation="0" />"#), "{}", exported);
+        assert_eq!(inexact, vec![
+            Inexact::Interp { track: String::from("camera"), time: half_row, interp: InterpType::Bezier, exported: sync::KeyType::Linear },
+            Inexact::Row { track: String::from("camera"), time: half_row, row: 3 },
+            Inexact::Kind { track: String::from("color"), kind: ValueKind::Color },
+        ]);
+
+        // only the first tempo makes it into the file
+        tl.get_tempo_mut().set_bpm_at(half_row, 90_f64).unwrap();
+        let (exported, inexact) = tl.export_rocket();
+        assert!(exported.contains(r#"beatsPerMin="120""#));
+        assert_eq!(inexact[0], Inexact::Tempo { time: half_row, bpm: 90_f64 });
+        assert_eq!(inexact[0].to_string(), format!("the tempo change to 90 BPM at {} was left out", half_row));
+
+        match Timeline::import_rocket("<tracks>\n  <track name=\"a\">\n    <key row=\"x\" value=\"1\"/>") {
+            Err(Error::Parse { line, msg, .. }) => { assert_eq!(line, 3); assert!(msg.contains("'row'")) }
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("a broken file was imported")
+        }
+    }
+
+    #[test]
+    fn hot_reload() {
+        let path = std::env::temp_dir().join(format!("demy_hot_reload_{}.json", std::process::id()));
+        // every version has a different length, so changes show even within the file system's
+        // timestamp resolution
+        let write = |value: f64| {
+            let mut tl = Timeline::new();
+            let ease = tl.register_interp("ease", interp_linear);
+            tl.get_track_mut("camera").add_node(&Node::new_custom(10_f64, value, ease)).unwrap();
+            std::fs::write(&path, tl.save().unwrap()).unwrap();
+        };
+
+        write(1_f64);
+        let mut registered = Timeline::new();
+        registered.register_interp("ease", interp_linear);
+        let mut tl = Timeline::load_with(&std::fs::read_to_string(&path).unwrap(), &registered).unwrap();
+        let camera = std::ffi::CString::new("camera").unwrap();
+        let track = unsafe { ffi::demy_tl_track_get(&mut tl, camera.as_ptr()) };
+
+        let mut reloader = Reloader::new(&path);
+        assert!(!reloader.poll(&mut tl).unwrap());
+
+        write(2.25_f64);
+        assert!(reloader.poll(&mut tl).unwrap());
+        assert!(!reloader.poll(&mut tl).unwrap());
+        assert_eq!(tl.get_track("camera").get_value_at(5_f64), 1.125_f64);
+        assert!(tl.check_interps().is_ok());
+        assert_eq!(unsafe { ffi::demy_tr_get_value_at(&tl, track, 10_f64) }, 2.25_f64);
+
+        // a broken save keeps the old timeline and is only reported once
+        std::fs::write(&path, "{\"version\":1,\"tracks\":").unwrap();
+        assert!(reloader.poll(&mut tl).is_err());
+        assert!(!reloader.poll(&mut tl).unwrap());
+        assert_eq!(tl.get_track("camera").get_value_at(10_f64), 2.25_f64);
+
+        unsafe {
+            let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+            assert_eq!(ffi::demy_tl_poll_reload(&mut tl, c_path.as_ptr()), 0);
+            write(3.125_f64);
+            assert_eq!(ffi::demy_tl_poll_reload(&mut tl, c_path.as_ptr()), 1);
+            assert_eq!(ffi::demy_tl_poll_reload(&mut tl, c_path.as_ptr()), 0);
+            assert_eq!(tl.get_track("camera").get_value_at(10_f64), 3.125_f64);
+
+            std::fs::write(&path, "{}}").unwrap();
+            assert_eq!(ffi::demy_tl_poll_reload(&mut tl, c_path.as_ptr()), -1);
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::Parse);
+            assert_eq!(tl.get_track("camera").get_value_at(10_f64), 3.125_f64);
+        }
+
+        // a track missing from one reload takes its handles with it for good
+        let saved = tl.save().unwrap();
+        tl.reload(&Timeline::new().save().unwrap()).unwrap();
+        assert!(tl.handles.tracks.get(track).is_none());
+        tl.reload(&saved).unwrap();
+        assert!(tl.handles.tracks.get(track).is_none());
+        let track = unsafe { ffi::demy_tl_track_get(&mut tl, camera.as_ptr()) };
+        assert_eq!(unsafe { ffi::demy_tr_get_value_at(&tl, track, 10_f64) }, 3.125_f64);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(!reloader.poll(&mut tl).unwrap());
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut tl = Timeline::new();
+        let times = |tl: &mut Timeline, name: &str| tl.tracks.get(name).map(|track| track.nodes.iter().map(|node| node.time).collect::<Vec<_>>());
+
+        // adding to a new track creates it, undo removes both
+        tl.edit_add_node("camera", &Node::new(10_f64, 1_f64, InterpType::Linear)).unwrap();
+        assert!(tl.undo().unwrap());
+        assert_eq!(times(&mut tl, "camera"), None);
+        assert!(!tl.undo().unwrap());
+        assert!(tl.redo().unwrap());
+        assert_eq!(times(&mut tl, "camera"), Some(vec![0_f64, 10_f64]));
+        assert!(!tl.can_redo());
+
+        tl.begin_transaction();
+        tl.edit_add_node("camera", &Node::new(20_f64, 2_f64, InterpType::Linear)).unwrap();
+        tl.begin_transaction();
+        tl.edit_update_node_at("camera", 10_f64, &Node::new(15_f64, 3_f64, InterpType::Linear)).unwrap();
+        tl.commit_transaction();
+        tl.edit_del_node_at("camera", 20_f64).unwrap();
+        assert!(tl.edit_add_node("camera", &Node::new(15_f64, 0_f64, InterpType::Linear)).is_err());
+        tl.commit_transaction();
+        assert_eq!(times(&mut tl, "camera"), Some(vec![0_f64, 15_f64]));
+
+        assert!(tl.undo().unwrap());
+        assert_eq!(times(&mut tl, "camera"), Some(vec![0_f64, 10_f64]));
+        assert_eq!(tl.get_track("camera").get_value_at(10_f64), 1_f64);
+        assert!(tl.redo().unwrap());
+        assert_eq!(times(&mut tl, "camera"), Some(vec![0_f64, 15_f64]));
+        assert_eq!(tl.get_track("camera").get_value_at(15_f64), 3_f64);
+
+        // a deleted track comes back with its nodes, but under a new handle
+        let name = std::ffi::CString::new("camera").unwrap();
+        unsafe {
+            let old = ffi::demy_tl_track_get(&mut tl, name.as_ptr());
+            assert!(ffi::demy_tl_edit_del_track(&mut tl, name.as_ptr()));
+            assert_eq!(ffi::demy_tl_undo(&mut tl), 1);
+            assert!(!ffi::demy_tr_is_valid(&tl, old));
+
+            let track = ffi::demy_tl_track_get(&mut tl, name.as_ptr());
+            assert_eq!(ffi::demy_tr_node_count(&tl, track), 2);
+            let node = Node::new(30_f64, 4_f64, InterpType::None);
+            assert!(ffi::demy_tr_edit_add_node(&mut tl, track, &node));
+            assert!(!ffi::demy_tr_edit_add_node(&mut tl, old, &node));
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::StaleHandle);
+            assert_eq!(ffi::demy_tl_undo(&mut tl), 1);
+            assert_eq!(ffi::demy_tr_node_count(&tl, track), 2);
+            assert_eq!(ffi::demy_tl_redo(&mut tl), 1);
+            assert_eq!(ffi::demy_tl_redo(&mut tl), 0);
+        }
+
+        // changes made around the history clear it, undo never steps back over them
+        tl.begin_transaction();
+        tl.edit_update_node_at("camera", 0_f64, &Node::new(0_f64, 5_f64, InterpType::None)).unwrap();
+        tl.edit_add_node("camera", &Node::new(40_f64, 5_f64, InterpType::Linear)).unwrap();
+        tl.commit_transaction();
+        tl.get_track_mut("camera").del_node_at(40_f64).unwrap();
+        assert!(!tl.can_undo());
+        assert!(!tl.undo().unwrap());
+        assert_eq!(tl.get_track("camera").get_value_at(0_f64), 5_f64);
+        assert_eq!(times(&mut tl, "camera"), Some(vec![0_f64, 15_f64, 30_f64]));
+
+        tl.edit_add_node("camera", &Node::new(40_f64, 5_f64, InterpType::Linear)).unwrap();
+        tl.get_tempo_mut().set_bpm_at(0_f64, 140_f64).unwrap();
+        assert!(!tl.can_undo());
+        tl.edit_del_node_at("camera", 40_f64).unwrap();
+        tl.get_event_track_mut("cues").add_event(Event::new(1_f64, Payload::Int(1))).unwrap();
+        assert!(!tl.can_undo());
+        unsafe {
+            let track = ffi::demy_tl_track_get(&mut tl, name.as_ptr());
+            let node = Node::new(40_f64, 4_f64, InterpType::None);
+            assert!(ffi::demy_tr_edit_add_node(&mut tl, track, &node));
+            assert!(ffi::demy_tr_del_node(&mut tl, track, 40_f64));
+            assert_eq!(ffi::demy_tl_undo(&mut tl), 0);
+            assert_eq!(ffi::demy_tr_node_count(&tl, track), 3);
+        }
+
+        // getting a track that exists keeps the history, creating one clears it so a deleted track
+        // can't be undone into its place
+        tl.edit_add_node("light", &Node::new(5_f64, 1_f64, InterpType::Linear)).unwrap();
+        tl.get_track("light");
+        assert!(tl.can_undo());
+        assert!(tl.edit_del_track("light"));
+        tl.get_track("light");
+        assert!(!tl.can_undo());
+        assert!(!tl.undo().unwrap());
+        assert_eq!(tl.get_track("light").node_count(), 1);
+
+        let fog = std::ffi::CString::new("fog").unwrap();
+        tl.edit_add_node("light", &Node::new(5_f64, 1_f64, InterpType::Linear)).unwrap();
+        unsafe { ffi::demy_tl_track_get(&mut tl, fog.as_ptr()); }
+        assert!(!tl.can_undo());
+        tl.edit_del_node_at("light", 5_f64).unwrap();
+        unsafe { ffi::demy_tl_typed_track_get(&mut tl, name.as_ptr(), ValueKind::Scalar as u32); }
+        assert!(tl.can_undo());
+        let tint = std::ffi::CString::new("tint").unwrap();
+        unsafe { ffi::demy_tl_typed_track_get(&mut tl, tint.as_ptr(), ValueKind::Vec3 as u32); }
+        assert!(!tl.can_undo());
+        tl.edit_add_node("light", &Node::new(5_f64, 1_f64, InterpType::Linear)).unwrap();
+        tl.get_event_track("cues");
+        assert!(tl.can_undo());
+        tl.get_event_track("flashes");
+        assert!(!tl.can_undo());
+
+        // new edits drop what could have been redone
+        tl.clear_history();
+        tl.edit_del_node_at("camera", 30_f64).unwrap();
+        tl.undo().unwrap();
+        tl.edit_del_node_at("camera", 15_f64).unwrap();
+        assert!(!tl.can_redo());
+    }
+
+    #[test]
+    fn stable_output() {
+        let build = |names: &[&str]| {
+            let mut tl = Timeline::new();
+            for name in names {
+                tl.get_track_mut(name).add_node(&Node::new(10_f64, name.len() as f64, InterpType::Linear)).unwrap();
+                tl.get_event_track_mut(name).add_event(Event::new(5_f64, Payload::Int(1))).unwrap();
+            }
+            tl.get_track_mut("camera.x").add_node(&Node::new(20_f64, 1_f64, InterpType::Linear)).unwrap();
+            tl
+        };
+
+        // the same tracks give the same file whatever order they were added in
+        let a = build(&["camera.x", "camera.y", "fade"]).save().unwrap();
+        let b = build(&["fade", "camera.x", "camera.y"]);
+        let b_names = b.event_tracks().map(|track| track.get_name()).collect::<Vec<&str>>();
+        assert_eq!(b_names, ["camera.x", "camera.y", "fade"]);
+
+        assert_eq!(a, b.save().unwrap());
+
+        // one line per node, event and tempo change
+        let pretty = b.save_pretty().unwrap();
+        let lines = pretty.lines().filter(|line| line.trim_start().starts_with("{\"time\":")).count();
+        assert_eq!(lines, 3 * 2 + 1 + 3 + 1);
+        assert!(pretty.contains("\n        {\"time\":20.0,\"value\":1.0,\"interp\":\"Linear\"}\n      ],"));
+        assert!(!pretty.contains("tangent"));
+
+        let reloaded = Timeline::load(&pretty).unwrap();
+        assert_eq!(reloaded.save().unwrap(), a);
+        assert_eq!(reloaded.save_pretty().unwrap(), pretty);
+    }
+
+    #[test]
+    fn binary_format() {
+        let json = include_str!("../tests/fixtures/v1.json");
+        let tl = load_fixture(json);
+
+        let as_json = |tl: &Timeline| serde_json::from_str::<serde_json::Value>(&tl.save().unwrap()).unwrap();
+
+        // full precision reproduces the JSON form exactly
+        let binary = tl.save_binary(Precision::F64);
+        let loaded = load_binary_fixture(&binary);
+        assert_eq!(as_json(&loaded), as_json(&tl));
+        assert_eq!(loaded.save_binary(Precision::F64), binary);
+        assert!(binary.len() * 3 < tl.save().unwrap().len(), "binary: {} bytes", binary.len());
+
+        let binary = tl.save_binary(Precision::F32);
+        check_fixture(&mut load_binary_fixture(&binary));
+
+        // like JSON files, custom interpolators have to be registered up front
+        match Timeline::load_binary(&binary) {
+            Err(Error::UnregisteredInterp { name, .. }) => assert_eq!(name, "step"),
+            _ => panic!("an unregistered interpolator was accepted")
+        }
+
+        // f32 only rounds values, times stay exact
+        let mut small = Timeline::new();
+        small.get_track_mut("camera").add_node(&Node::new(1e15_f64 + 0.5_f64, 0.1_f64, InterpType::Linear)).unwrap();
+        small.get_event_track_mut("scenes").add_event(Event::new(0.25_f64, Payload::Int(-40000))).unwrap();
+        let mut loaded = Timeline::load_binary(&small.save_binary(Precision::F32)).unwrap();
+        assert_eq!(loaded.get_track("camera").get_node_at(1e15_f64 + 0.5_f64).unwrap().get_value(), 0.1_f32 as f64);
+        assert_eq!(loaded.get_event_track("scenes").get_event_at(0.25_f64).unwrap().get_payload(), &Payload::Int(-40000));
+
+        let mut newer = binary.clone();
+        newer[4] = 2;
+        match Timeline::load_binary(&newer) {
+            Err(Error::UnsupportedVersion { found, supported }) => assert_eq!((found, supported), (2, 1)),
+            _ => panic!("a newer binary version was accepted")
+        }
+
+        match Timeline::load_binary(json.as_bytes()) {
+            Err(err @ Error::InvalidBinary { .. }) => assert_eq!(err.to_string(), "Invalid binary timeline at byte 0: not a binary timeline."),
+            _ => panic!("JSON was accepted as binary")
+        }
+
+        // corrupt input fails without panicking
+        for len in 0..binary.len() {
+            assert!(Timeline::load_binary(&binary[..len]).is_err());
+        }
+        for i in 0..binary.len() {
+            let mut corrupt = binary.clone();
+            corrupt[i] ^= 0xa5;
+            let _ = Timeline::load_binary(&corrupt);
+        }
+    }
+
+    #[test]
+    fn fractional_time() {
+        let mut tl = Timeline::new();
+        let track = tl.get_track_mut("camera");
+
+        assert!(track.add_node(&Node::new(0.5_f64, 1_f64, InterpType::Linear)).is_ok());
+        assert!(track.add_node(&Node::new(1.5_f64, 3_f64, InterpType::Linear)).is_ok());
+        assert!(track.add_node(&Node::new(f64::NAN, 3_f64, InterpType::Linear)).is_err());
+        assert!(track.add_node(&Node::new(-1_f64, 3_f64, InterpType::Linear)).is_err());
+
+        let val = track.get_value_at(0.25_f64);
+        assert!(0.001 > (0.5_f64 - val).abs(), "val: {}", val);
+        let val = track.get_value_at(1.25_f64);
+        assert!(0.001 > (2.5_f64 - val).abs(), "val: {}", val);
+        assert_eq!(track.get_value_at(-2_f64), 0_f64);
+        assert_eq!(track.get_node_at(0.5_f64).unwrap().get_value(), 1_f64);
+
+        // integer times from older files still load
+        let old = r#"{"tracks":{"camera":{"nodes":[{"time":0,"value":0.0,"interp":"None"},{"time":10,"value":1.0,"interp":"Linear"}],"name":"camera"}}}"#;
+        let mut tl = Timeline::load(old).unwrap();
+        let val = tl.get_track("camera").get_value_at(2.5_f64);
+        assert!(0.001 > (0.25_f64 - val).abs(), "val: {}", val);
+    }
+
+    #[test]
+    fn tempo_map() {
+        let mut tl = Timeline::new();
+        {
+            let tempo = tl.get_tempo_mut();
+            assert!(tempo.set_rows_per_beat(4).is_ok());
+            assert!(tempo.set_bpm_at(2000_f64, 60_f64).is_ok());
+            assert!(tempo.set_bpm_at(1000_f64, -1_f64).is_err());
+            assert!(tempo.set_time_signature(TimeSignature { beats_per_bar: 3, beat_unit: 4 }).is_ok());
+        }
+
+        {
+            // 120 BPM for the first two seconds, 60 BPM afterwards
+            let tempo = tl.get_tempo();
+            assert_eq!(tempo.ticks_to_seconds(1500_f64), 1.5_f64);
+            assert_eq!(tempo.ticks_to_beats(500_f64), 1_f64);
+            assert_eq!(tempo.ticks_to_beats(2000_f64), 4_f64);
+            assert_eq!(tempo.ticks_to_beats(3000_f64), 5_f64);
+            assert_eq!(tempo.ticks_to_rows(3000_f64), 20_f64);
+            assert_eq!(tempo.ticks_to_bars(3000_f64), 5_f64 / 3_f64);
+            assert_eq!(tempo.beats_to_ticks(5_f64), 3000_f64);
+            assert_eq!(tempo.beats_to_ticks(2_f64), 1000_f64);
+            assert_eq!(tempo.rows_to_ticks(18_f64), 2500_f64);
+            assert_eq!(tempo.get_bpm_at(1999_f64), 120_f64);
+            assert_eq!(tempo.get_bpm_at(2000_f64), 60_f64);
+        }
+
+        assert!(tl.add_node_at_beat("camera", 5_f64, &Node::new(0_f64, 10_f64, InterpType::Linear)).is_ok());
+        assert!(tl.get_track("camera").get_node_at(3000_f64).is_some());
+        let val = tl.get_value_at_beat("camera", 2_f64);
+        assert!(0.001 > (10_f64 / 3_f64 - val).abs(), "val: {}", val);
+
+        let loaded = Timeline::load(&tl.save().unwrap()).unwrap();
+        assert_eq!(loaded.get_tempo().get_rows_per_beat(), 4);
+        assert_eq!(loaded.get_tempo().changes().count(), 2);
+        assert_eq!(loaded.get_tempo().ticks_to_beats(3000_f64), 5_f64);
+        assert!(tl.get_tempo_mut().del_bpm_at(0_f64).is_err());
+    }
+
+    #[test]
+    fn cursor_sampling() {
+        let mut tl = Timeline::new();
+        let track = tl.get_track_mut("camera");
+
+        for i in 1..100 {
+            assert!(track.add_node(&Node::new(i as f64 * 10_f64, i as f64, InterpType::Linear)).is_ok());
+        }
+
+        let mut cursor = TrackCursor::new();
+        let mut times = (0..2000).map(|i| i as f64 * 0.5_f64).collect::<Vec<_>>();
+        // seeks backwards and past both ends
+        times.extend(&[-5_f64, 500_f64, 120_f64, 5000_f64, 3_f64]);
+
+        for &time in &times {
+            assert_eq!(track.get_value_at_cursor(time, &mut cursor), track.get_value_at(time), "at {}", time);
+        }
+
+        let val = track.get_value_at(995_f64);
+        assert!(0.001 > (99_f64 - val).abs(), "val: {}", val);
+        let val = track.get_value_at(255_f64);
+        assert!(0.001 > (25.5_f64 - val).abs(), "val: {}", val);
+        assert!(track.get_node_at(500_f64).is_some());
+        assert!(track.get_node_at(505_f64).is_none());
+
+        // the cursor survives edits to the track
+        assert!(track.del_node_at(500_f64).is_ok());
+        assert_eq!(track.get_value_at_cursor(505_f64, &mut cursor), track.get_value_at(505_f64));
     }
 
     #[test]
@@ -613,7 +3560,7 @@ mod tests {
         let mut tl = Timeline::new();
         let track = tl.get_track_mut("camera");
 
-        assert_eq!(track.nodes().next().unwrap().get_time(), 0);
+        assert_eq!(track.nodes().next().unwrap().get_time(), 0_f64);
     }
 
     #[test]
@@ -621,8 +3568,8 @@ mod tests {
         let mut tl = Timeline::new();
         let track = tl.get_track_mut("camera");
 
-        assert!(track.add_node(&Node::new(1, 0_f64, InterpType::None)).is_none());
-        assert!(track.add_node(&Node::new(1, 0_f64, InterpType::None)).is_some());
+        assert!(track.add_node(&Node::new(1_f64, 0_f64, InterpType::None)).is_ok());
+        assert!(track.add_node(&Node::new(1_f64, 0_f64, InterpType::None)).is_err());
 
         assert_eq!(track.nodes().count(), 2); // implcit 0
     }
@@ -633,7 +3580,7 @@ mod tests {
 
         {
             let track = tl.get_track_mut("camera.x");
-            track.add_node(&Node::new(10, 1_f64, InterpType::Linear));
+            track.add_node(&Node::new(10_f64, 1_f64, InterpType::Linear)).unwrap();
         }
 
         for i in 0..50 {
@@ -641,18 +3588,67 @@ mod tests {
         }
 
         let track = tl.get_track("camera.x");
-        let node = track.get_node_at(10).unwrap();
-        assert_eq!(node.get_time(), 10);
+        let node = track.get_node_at(10_f64).unwrap();
+        assert_eq!(node.get_time(), 10_f64);
         assert_eq!(node.get_value(), 1_f64);
     }
 
+    #[test]
+    fn ffi_handles() {
+        use std::ffi::CString;
+
+        unsafe {
+            let tl = ffi::demy_tl_new();
+            let name = CString::new("camera.x").unwrap();
+            let track = ffi::demy_tl_track_get(tl, name.as_ptr());
+            assert!(ffi::demy_tr_add_node(tl, track, 10_f64, 1_f64, InterpType::Linear as u32));
+            let iter = ffi::demy_tr_iter_begin(tl, track);
+
+            for i in 0..50 {
+                let other = CString::new(i.to_string()).unwrap();
+                ffi::demy_tl_track_get(tl, other.as_ptr());
+            }
+
+            assert!(ffi::demy_tr_is_valid(tl, track));
+            assert_eq!(ffi::demy_tl_track_get(tl, name.as_ptr()), track);
+            assert_eq!((*ffi::demy_tr_get_node_by_index(tl, track, 1)).get_value(), 1_f64);
+            assert_eq!((*ffi::demy_tr_iter_get(tl, iter)).get_time(), 0_f64);
+
+            assert!(ffi::demy_tl_track_del(tl, name.as_ptr()));
+            assert!(!ffi::demy_tr_is_valid(tl, track));
+            assert!(!ffi::demy_tr_add_node(tl, track, 20_f64, 1_f64, InterpType::Linear as u32));
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::StaleHandle);
+            assert!(ffi::demy_tr_iter_get(tl, iter).is_null());
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::StaleHandle);
+
+            // a new track with the same name gets a new handle, the old one stays stale
+            let recreated = ffi::demy_tl_track_get(tl, name.as_ptr());
+            assert!(recreated != track);
+            assert!(!ffi::demy_tr_is_valid(tl, track));
+            assert_eq!(ffi::demy_tr_node_count(tl, recreated), 1);
+
+            ffi::demy_tr_iter_free(tl, iter);
+            ffi::demy_tr_iter_free(tl, iter);
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::StaleHandle);
+
+            // handles only work with the timeline that issued them
+            let other = ffi::demy_tl_new();
+            assert_eq!(ffi::demy_tr_node_count(tl, ffi::demy_tl_track_get(other, name.as_ptr())), 0);
+            assert_eq!(ffi::demy_last_error_code(), ErrorCode::StaleHandle);
+            assert!(!ffi::demy_tr_is_valid(other, recreated));
+            ffi::demy_tl_free(other);
+
+            ffi::demy_tl_free(tl);
+        }
+    }
+
     #[test]
     fn node_vec_is_ordered() {
         let mut tl = Timeline::new();
         let track = tl.get_track_mut("camera.x");
 
-        track.add_node(&Node::new(5, 5_f64, InterpType::Linear));
-        track.add_node(&Node::new(2, 2_f64, InterpType::Linear));
+        track.add_node(&Node::new(5_f64, 5_f64, InterpType::Linear)).unwrap();
+        track.add_node(&Node::new(2_f64, 2_f64, InterpType::Linear)).unwrap();
 
         let mut prev_node = track.nodes().next().unwrap();
         for node in track.nodes().skip(1) {
@@ -670,22 +3666,22 @@ mod tests {
 
         {
             let mut tl = Timeline::new();
-            let t1_node1 = Node::new(10, 1_f64, InterpType::Linear);
-            let t1_node2 = Node::new(20, 2_f64, InterpType::Linear);
+            let t1_node1 = Node::new(10_f64, 1_f64, InterpType::Linear);
+            let t1_node2 = Node::new(20_f64, 2_f64, InterpType::Linear);
 
-            let t2_node1 = Node::new(10, 4_f64, InterpType::Linear);
-            let t2_node2 = Node::new(20, 8_f64, InterpType::Linear);
+            let t2_node1 = Node::new(10_f64, 4_f64, InterpType::Linear);
+            let t2_node2 = Node::new(20_f64, 8_f64, InterpType::Linear);
 
             {
                 let track = tl.get_track_mut(track1);
-                track.add_node(&t1_node1);
-                track.add_node(&t1_node2);
+                track.add_node(&t1_node1).unwrap();
+                track.add_node(&t1_node2).unwrap();
             }
 
             {
                 let track = tl.get_track_mut(track2);
-                track.add_node(&t2_node1);
-                track.add_node(&t2_node2);
+                track.add_node(&t2_node1).unwrap();
+                track.add_node(&t2_node2).unwrap();
             }
 
             serialized = tl.save().unwrap();
@@ -699,20 +3695,121 @@ mod tests {
             {
                 let track = tl.get_track(track1);
                 assert_eq!(track.nodes().count(), 3);
-                let val = track.get_value_at(5);
+                let val = track.get_value_at(5_f64);
                 assert!(0.001 > (0.5_f64 - val).abs(), "val: {}", val);
-                let val = track.get_value_at(15);
+                let val = track.get_value_at(15_f64);
                 assert!(0.001 > (1.5_f64 - val).abs(), "val: {}", val);
             }
 
             {
                 let track = tl.get_track(track2);
                 assert_eq!(track.nodes().count(), 3);
-                let val = track.get_value_at(5);
+                let val = track.get_value_at(5_f64);
                 assert!(0.001 > (2_f64 - val).abs(), "val: {}", val);
-                let val = track.get_value_at(15);
+                let val = track.get_value_at(15_f64);
                 assert!(0.001 > (6_f64 - val).abs(), "val: {}", val);
             }
         }
     }
+
+    #[test]
+    fn range_edits() {
+        let mut tl = Timeline::new();
+        let times = |tl: &mut Timeline, name: &str| tl.get_track(name).nodes().map(|node| node.get_time()).collect::<Vec<_>>();
+
+        {
+            let camera = tl.get_track_mut("camera");
+            camera.add_node(&Node::new(10_f64, 1_f64, InterpType::Linear)).unwrap();
+            let mut bezier = Node::new(20_f64, 2_f64, InterpType::Bezier);
+            bezier.set_out_tangent(Tangent::new(2_f64, 1_f64));
+            camera.add_node(&bezier).unwrap();
+            camera.add_node(&Node::new(30_f64, 3_f64, InterpType::Linear)).unwrap();
+
+            camera.shift_range(10_f64, 20_f64, 5_f64).unwrap();
+            assert_eq!(camera.get_value_at(15_f64), 1_f64);
+
+            // nodes may not pass or land on the ones outside the range, and the track stays as it was
+            match camera.shift_range(10_f64, 20_f64, 10_f64) {
+                Err(Error::RangeCollision(time)) => assert_eq!(time, 20_f64),
+                other => panic!("unexpected result: {:?}", other)
+            }
+            match camera.shift_range(15_f64, 16_f64, 5_f64) {
+                Err(err @ Error::RangeCollision(_)) => assert_eq!(err.code(), ErrorCode::RangeCollision),
+                other => panic!("unexpected result: {:?}", other)
+            }
+            assert!(camera.shift_range(20_f64, 10_f64, 1_f64).is_err());
+            assert!(camera.scale_range(0_f64, 40_f64, 0_f64, 0_f64).is_err());
+            assert!(camera.shift_range(30_f64, 40_f64, -40_f64).is_err());
+            assert!(camera.shift_range(0_f64, 1_f64, 5_f64).is_ok());
+        }
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 15_f64, 20_f64, 30_f64]);
+
+        // the time 0 node stays put, tangents stretch along
+        tl.get_track_mut("camera").scale_range(0_f64, 40_f64, 0_f64, 2_f64).unwrap();
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 30_f64, 40_f64, 60_f64]);
+        assert_eq!(tl.get_track("camera").get_node_at(40_f64).unwrap().get_out_tangent().time, 4_f64);
+
+        tl.get_track_mut("camera").insert_time(35_f64, 5_f64).unwrap();
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 30_f64, 45_f64, 65_f64]);
+        tl.get_track_mut("camera").remove_time(30_f64, 45_f64).unwrap();
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 30_f64, 50_f64]);
+        assert_eq!(tl.get_track("camera").get_value_at(30_f64), 2_f64);
+        match tl.get_track_mut("camera").remove_time(0_f64, 30_f64) {
+            Err(Error::RangeCollision(time)) => assert_eq!(time, 0_f64),
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        // timeline-wide edits cover event tracks, and change nothing if any track collides
+        tl.get_track_mut("fade").add_node(&Node::new(5_f64, 1_f64, InterpType::Linear)).unwrap();
+        tl.get_event_track_mut("cues").add_event(Event::new(12_f64, Payload::Int(1))).unwrap();
+        tl.insert_time(10_f64, 10_f64).unwrap();
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 40_f64, 60_f64]);
+        assert_eq!(times(&mut tl, "fade"), vec![0_f64, 5_f64]);
+        assert!(tl.get_event_track("cues").get_event_at(22_f64).is_some());
+
+        assert!(tl.shift_range(3_f64, 45_f64, 20_f64).is_err());
+        assert_eq!(times(&mut tl, "fade"), vec![0_f64, 5_f64]);
+        assert!(tl.get_event_track("cues").get_event_at(22_f64).is_some());
+
+        tl.remove_time(3_f64, 13_f64).unwrap();
+        assert_eq!(times(&mut tl, "fade"), vec![0_f64]);
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 30_f64, 50_f64]);
+        assert!(tl.get_event_track("cues").get_event_at(12_f64).is_some());
+
+        // the edit_* variants undo as one step, events included, the others clear the history
+        tl.edit_add_node("fade", &Node::new(5_f64, 1_f64, InterpType::Linear)).unwrap();
+        tl.edit_insert_time(4_f64, 20_f64).unwrap();
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 50_f64, 70_f64]);
+        assert_eq!(times(&mut tl, "fade"), vec![0_f64, 25_f64]);
+        assert!(tl.undo().unwrap());
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 30_f64, 50_f64]);
+        assert_eq!(times(&mut tl, "fade"), vec![0_f64, 5_f64]);
+        assert!(tl.get_event_track("cues").get_event_at(12_f64).is_some());
+        assert!(tl.redo().unwrap());
+        assert!(tl.get_event_track("cues").get_event_at(32_f64).is_some());
+
+        assert!(tl.edit_shift_range(20_f64, 60_f64, 40_f64).is_err());
+        assert!(tl.can_undo() && !tl.can_redo());
+        tl.edit_scale_range(0_f64, 100_f64, 0_f64, 0.5_f64).unwrap();
+        tl.edit_remove_time(10_f64, 20_f64).unwrap();
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 15_f64, 25_f64]);
+        assert!(tl.undo().unwrap() && tl.undo().unwrap());
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 50_f64, 70_f64]);
+
+        tl.shift_range(60_f64, 80_f64, 5_f64).unwrap();
+        assert!(!tl.can_undo() && !tl.can_redo());
+        assert_eq!(times(&mut tl, "camera"), vec![0_f64, 50_f64, 75_f64]);
+
+        // only the tracks an edit changes are kept for its undo, stretched tangents count as a change
+        let fade = tl.get_track("fade").nodes.clone();
+        let cues = tl.get_event_track("cues").events().as_slice().to_vec();
+        assert!(Retime::Shift { start: 40_f64, end: 60_f64, offset: 1_f64 }.internal_nodes(&fade).unwrap().is_none());
+        assert!(Retime::Insert { at: 40_f64, duration: 1_f64 }.internal_events(&cues).unwrap().is_none());
+        assert!(Retime::Insert { at: 20_f64, duration: 1_f64 }.internal_events(&cues).unwrap().is_some());
+        let mut pivot = Node::new(50_f64, 1_f64, InterpType::Linear);
+        pivot.set_out_tangent(Tangent { time: 2_f64, value: 0_f64 });
+        let nodes = [Node::new(0_f64, 0_f64, InterpType::Linear), pivot];
+        assert!(Retime::Scale { start: 40_f64, end: 60_f64, pivot: 50_f64, factor: 2_f64 }.internal_nodes(&nodes).unwrap().is_some());
+        assert!(Retime::Scale { start: 40_f64, end: 60_f64, pivot: 50_f64, factor: 2_f64 }.internal_nodes(&nodes[..1]).unwrap().is_none());
+    }
 }
@@ -0,0 +1,138 @@
+// Range editing: moving, stretching, inserting and removing stretches of time. Each operation maps
+// every node or event time to a new one and is refused as a whole if the result would reorder or
+// merge them, so a track is never left half edited. The node at time 0 stays where it is.
+
+use Error;
+use Event;
+use Node;
+
+#[derive(Copy, Clone)]
+pub(crate) enum Retime {
+    // Moves start <= time < end by `offset`.
+    Shift { start: f64, end: f64, offset: f64 },
+    // Stretches start <= time < end away from or towards `pivot`.
+    Scale { start: f64, end: f64, pivot: f64, factor: f64 },
+    // Moves at <= time later by `duration`.
+    Insert { at: f64, duration: f64 },
+    // Drops start <= time < end and moves what follows back to `start`.
+    Remove { start: f64, end: f64 },
+}
+
+fn internal_check_time(time: f64) -> Result<(), Error> {
+    if time >= 0_f64 && time.is_finite() { Ok(()) } else { Err(Error::InvalidTime(time)) }
+}
+
+impl Retime {
+    pub fn internal_check(&self) -> Result<(), Error> {
+        let check_range = |start: f64, end: f64| {
+            internal_check_time(start)?;
+            internal_check_time(end)?;
+            if start > end { Err(Error::InvalidRange("The range must not end before it starts.")) } else { Ok(()) }
+        };
+
+        match *self {
+            Retime::Shift { start, end, offset } => {
+                check_range(start, end)?;
+                if !offset.is_finite() { return Err(Error::InvalidRange("The offset must be a finite number.")) }
+            }
+            Retime::Scale { start, end, pivot, factor } => {
+                check_range(start, end)?;
+                if !pivot.is_finite() { return Err(Error::InvalidRange("The pivot must be a finite number.")) }
+                if !(factor > 0_f64 && factor.is_finite()) {
+                    return Err(Error::InvalidRange("The scale factor must be a finite, positive number."))
+                }
+            }
+            Retime::Insert { at, duration } => {
+                internal_check_time(at)?;
+                if !(duration >= 0_f64 && duration.is_finite()) {
+                    return Err(Error::InvalidRange("The duration must be a finite, non-negative number."))
+                }
+            }
+            Retime::Remove { start, end } => check_range(start, end)?
+        }
+        Ok(())
+    }
+
+    // The new time, or None if the time is removed.
+    fn internal_map(&self, time: f64) -> Option<f64> {
+        let within = |start: f64, end: f64| start <= time && time < end;
+        match *self {
+            Retime::Shift { start, end, offset } if within(start, end) => Some(time + offset),
+            Retime::Scale { start, end, pivot, factor } if within(start, end) => Some(pivot + (time - pivot) * factor),
+            Retime::Insert { at, duration } if time >= at => Some(time + duration),
+            Retime::Remove { start, end } if within(start, end) => None,
+            Retime::Remove { start, end } if time >= end => Some(time - (end - start)),
+            _ => Some(time)
+        }
+    }
+
+    // How much longer the time around `time` gets, which tangent handles are stretched by.
+    fn internal_stretch(&self, time: f64) -> f64 {
+        match *self {
+            Retime::Scale { start, end, factor, .. } if start <= time && time < end => factor,
+            _ => 1_f64
+        }
+    }
+
+    // New times for `times`, which are sorted. With `fixed_first` the first time is the node at
+    // time 0 and stays put.
+    fn internal_map_all(&self, times: &[f64], fixed_first: bool) -> Result<Vec<Option<f64>>, Error> {
+        let mut mapped = Vec::with_capacity(times.len());
+        // the original and new time of the last time that was kept
+        let mut prev: Option<(f64, f64)> = None;
+
+        for (i, &time) in times.iter().enumerate() {
+            let new_time = if fixed_first && i == 0 { Some(time) } else { self.internal_map(time) };
+
+            if let Some(new_time) = new_time {
+                if !new_time.is_finite() || new_time < 0_f64 {
+                    return Err(Error::InvalidTime(new_time))
+                }
+
+                // blame whichever of the two didn't move
+                if let Some((prev_time, prev_new)) = prev.filter(|&(_, prev_new)| new_time <= prev_new) {
+                    return Err(Error::RangeCollision(if prev_time == prev_new { prev_time } else { time }))
+                }
+                prev = Some((time, new_time));
+            }
+            mapped.push(new_time);
+        }
+
+        Ok(mapped)
+    }
+
+    // The edited nodes, or None if the edit leaves them as they are.
+    pub fn internal_nodes(&self, nodes: &[Node]) -> Result<Option<Vec<Node>>, Error> {
+        let times: Vec<f64> = nodes.iter().map(|node| node.time).collect();
+        let mapped = self.internal_map_all(&times, true)?;
+
+        let retimed: Vec<Node> = nodes.iter().zip(mapped).filter_map(|(node, new_time)| {
+            new_time.map(|new_time| {
+                let stretch = self.internal_stretch(node.time);
+                let mut node = *node;
+                node.time = new_time;
+                node.in_tangent.time *= stretch;
+                node.out_tangent.time *= stretch;
+                node
+            })
+        }).collect();
+
+        let changed = retimed.len() != nodes.len() || retimed.iter().zip(nodes).any(|(new, old)| {
+            new.time != old.time || new.in_tangent.time != old.in_tangent.time || new.out_tangent.time != old.out_tangent.time
+        });
+        Ok(if changed { Some(retimed) } else { None })
+    }
+
+    // The edited events, or None if the edit leaves them as they are.
+    pub fn internal_events(&self, events: &[Event]) -> Result<Option<Vec<Event>>, Error> {
+        let times: Vec<f64> = events.iter().map(|event| event.get_time()).collect();
+        let mapped = self.internal_map_all(&times, false)?;
+
+        if mapped.iter().zip(&times).all(|(new_time, &time)| *new_time == Some(time)) {
+            return Ok(None)
+        }
+        Ok(Some(events.iter().zip(mapped).filter_map(|(event, new_time)| {
+            new_time.map(|new_time| Event::new(new_time, event.get_payload().clone()))
+        }).collect()))
+    }
+}
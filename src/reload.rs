@@ -0,0 +1,56 @@
+// Hot reloading of a timeline file that is edited while the demo runs. The file is polled rather
+// than watched: each poll compares its modification time and size with the previous poll's and
+// only reads and parses it when either changed.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use Error;
+use Timeline;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+pub struct Reloader {
+    path: PathBuf,
+    stamp: Option<Stamp>,
+}
+
+impl Reloader {
+    // Takes what is currently in the file as already loaded, the first poll only reloads if it
+    // changes after this.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let mut reloader = Reloader { path: path.as_ref().to_path_buf(), stamp: None };
+        reloader.stamp = reloader.internal_stamp().ok().and_then(|stamp| stamp);
+        reloader
+    }
+
+    pub fn get_path(&self) -> &Path { &self.path }
+
+    // Reloads `tl` if the file changed since the last poll, returning whether it did. A file that
+    // fails to load leaves `tl` as it was and isn't retried until it changes again. A missing file
+    // counts as unchanged, editors that save by renaming briefly leave none behind.
+    pub fn poll(&mut self, tl: &mut Timeline) -> Result<bool, Error> {
+        let stamp = match self.internal_stamp()? {
+            Some(stamp) if Some(stamp) != self.stamp => stamp,
+            _ => return Ok(false)
+        };
+        self.stamp = Some(stamp);
+
+        tl.reload(&fs::read_to_string(&self.path)?)?;
+        Ok(true)
+    }
+
+    fn internal_stamp(&self) -> Result<Option<Stamp>, Error> {
+        match fs::metadata(&self.path) {
+            Ok(meta) => Ok(Some(Stamp { modified: meta.modified().ok(), len: meta.len() })),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::Io(err))
+        }
+    }
+}
@@ -0,0 +1,370 @@
+// Import and export of GNU Rocket's XML track files (.rocket). Both the original editor's
+// <sync><tracks> layout and the newer <rootElement><tracks> one are read, the newer one is written.
+// Rows map to time through the timeline's tempo map, which takes its rows per beat and BPM from the
+// file when it has them.
+//
+// Rocket is more limited than demy: one scalar per track, keys on whole rows and four interpolation
+// modes. Whatever doesn't carry over exactly is listed in the returned Inexact entries instead of
+// failing the conversion.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use sync::KeyType;
+use Error;
+use InterpType;
+use Node;
+use TempoMap;
+use Timeline;
+use Track;
+use ValueKind;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Inexact {
+    // Imported smooth keys are sampled with InterpType::SineInOut rather than a smoothstep.
+    SmoothKey { track: String, row: u32 },
+    // Exported as a key of type `exported`.
+    Interp { track: String, time: f64, interp: InterpType, exported: KeyType },
+    // A node between rows, exported on the nearest one. A node that lands on a row that's already
+    // taken is left out.
+    Row { track: String, time: f64, row: u32 },
+    // Tracks of vectors, colors and quaternions are left out.
+    Kind { track: String, kind: ValueKind },
+    // Files hold a single BPM, so every tempo change after the first is left out and Rocket plays
+    // the rows after it at the wrong speed.
+    Tempo { time: f64, bpm: f64 },
+}
+
+impl fmt::Display for Inexact {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Inexact::SmoothKey { ref track, row } =>
+                write!(f, "track '{}' has a smooth key at row {}, approximated with SineInOut", track, row),
+            Inexact::Interp { ref track, time, interp, exported } =>
+                write!(f, "track '{}' has a {:?} node at {}, exported as a {:?} key", track, interp, time, exported),
+            Inexact::Row { ref track, time, row } => write!(f, "track '{}' has a node at {}, exported on row {}", track, time, row),
+            Inexact::Kind { ref track, kind } => write!(f, "track '{}' holds {:?} values and was left out", track, kind),
+            Inexact::Tempo { time, bpm } => write!(f, "the tempo change to {} BPM at {} was left out", bpm, time),
+        }
+    }
+}
+
+// A track's keys by row, as read from a file.
+type Keys = BTreeMap<u32, (f64, KeyType)>;
+
+pub(crate) struct RowKey {
+    pub row: u32,
+    pub value: f64,
+    pub key_type: KeyType,
+}
+
+// Step, linear and ramp are the only modes both sides sample the same way.
+fn internal_exact_key_type(interp: InterpType) -> Option<KeyType> {
+    match interp {
+        InterpType::None | InterpType::Linear | InterpType::QuadIn => KeyType::from_interp(interp),
+        _ => None
+    }
+}
+
+// The keys Rocket needs to replay a scalar track. A key's type shapes the segment after it, which
+// demy keeps on the node that ends the segment.
+pub(crate) fn internal_track_keys(track: &Track, tempo: &TempoMap, inexact: &mut Vec<Inexact>) -> Vec<RowKey> {
+    let nodes: Vec<&Node> = track.nodes().collect();
+    let mut keys: Vec<RowKey> = vec![];
+
+    for (i, node) in nodes.iter().enumerate() {
+        let exact_row = tempo.ticks_to_rows(node.get_time());
+        let row = exact_row.round().max(0_f64).min(u32::MAX as f64) as u32;
+        let taken = keys.last().is_some_and(|key| key.row == row);
+        if taken || (exact_row - row as f64).abs() > 1e-6 {
+            inexact.push(Inexact::Row { track: String::from(track.get_name()), time: node.get_time(), row });
+        }
+        if taken {
+            continue
+        }
+
+        let key_type = match nodes.get(i + 1) {
+            None => KeyType::Step,
+            Some(next) => {
+                let interp = next.get_interpolator();
+                internal_exact_key_type(interp).unwrap_or_else(|| {
+                    let exported = KeyType::from_interp(interp).unwrap_or(KeyType::Linear);
+                    inexact.push(Inexact::Interp { track: String::from(track.get_name()), time: next.get_time(), interp, exported });
+                    exported
+                })
+            }
+        };
+
+        keys.push(RowKey { row, value: node.get_value(), key_type });
+    }
+
+    keys
+}
+
+pub(crate) fn internal_export(tl: &Timeline) -> (String, Vec<Inexact>) {
+    let tempo = tl.get_tempo();
+    let mut inexact = vec![];
+    let mut body = String::new();
+    let mut last_row = 0;
+
+    for change in tempo.changes().skip(1) {
+        inexact.push(Inexact::Tempo { time: change.get_time(), bpm: change.get_bpm() });
+    }
+
+    for track in tl.tracks.values() {
+        if !track.get_kind().is_scalar() {
+            inexact.push(Inexact::Kind { track: String::from(track.get_name()), kind: track.get_kind() });
+            continue
+        }
+
+        body.push_str(&format!("\t\t<track name=\"{}\">\n", internal_escape(track.get_name())));
+        for key in internal_track_keys(track, tempo, &mut inexact) {
+            body.push_str(&format!("\t\t\t<key row=\"{}\" value=\"{}\" interpolation=\"{}\" />\n", key.row, key.value, key.key_type as u8));
+            last_row = last_row.max(key.row);
+        }
+        body.push_str("\t\t</track>\n");
+    }
+
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<rootElement>\n\t<tracks rows=\"{}\" rowsPerBeat=\"{}\" beatsPerMin=\"{}\">\n{}\t</tracks>\n</rootElement>\n",
+        last_row + 1, tempo.get_rows_per_beat(), tempo.get_bpm_at(0_f64), body);
+
+    (xml, inexact)
+}
+
+pub(crate) fn internal_import(xml: &str) -> Result<(Timeline, Vec<Inexact>), Error> {
+    let mut tl = Timeline::new();
+    let mut inexact = vec![];
+    let mut scanner = Scanner { text: xml, pos: 0 };
+    let mut track: Option<(String, Keys)> = None;
+
+    while let Some(tag) = scanner.next_tag()? {
+        match tag {
+            Tag::Start { name: "tracks", attrs, .. } => {
+                if let Some(rows_per_beat) = scanner.number_attr::<u32>(&attrs, "rowsPerBeat")? {
+                    tl.get_tempo_mut().set_rows_per_beat(rows_per_beat)?;
+                }
+                if let Some(bpm) = scanner.number_attr::<f64>(&attrs, "beatsPerMin")? {
+                    tl.get_tempo_mut().set_bpm_at(0_f64, bpm)?;
+                }
+            }
+            Tag::Start { name: "track", attrs, empty } => {
+                let name = scanner.attr(&attrs, "name")?;
+                tl.get_track_mut(&name);
+                if !empty {
+                    track = Some((name, BTreeMap::new()));
+                }
+            }
+            Tag::Start { name: "key", attrs, .. } => {
+                let keys = match track {
+                    Some((_, ref mut keys)) => keys,
+                    None => return Err(scanner.error("key outside of a track"))
+                };
+
+                let row = scanner.number_attr::<u32>(&attrs, "row")?.ok_or_else(|| scanner.error("key without a row"))?;
+                let value = scanner.number_attr::<f64>(&attrs, "value")?.ok_or_else(|| scanner.error("key without a value"))?;
+                let key_type = match scanner.number_attr::<u8>(&attrs, "interpolation")? {
+                    Some(key_type) => KeyType::from_u8(key_type).ok_or_else(|| scanner.error("unknown interpolation"))?,
+                    None => KeyType::Step
+                };
+                keys.insert(row, (value, key_type));
+            }
+            Tag::End("track") => {
+                if let Some((name, keys)) = track.take() {
+                    internal_import_track(&mut tl, &name, &keys, &mut inexact)?;
+                }
+            }
+            _ => ()
+        }
+    }
+
+    if track.is_some() {
+        return Err(scanner.error("unclosed track"))
+    }
+
+    Ok((tl, inexact))
+}
+
+// Before its first key Rocket holds the first key's value, which the node at time 0 takes over.
+fn internal_import_track(tl: &mut Timeline, name: &str, keys: &Keys, inexact: &mut Vec<Inexact>) -> Result<(), Error> {
+    let times: Vec<f64> = keys.keys().map(|&row| tl.get_tempo().rows_to_ticks(row as f64)).collect();
+    let track = tl.get_track_mut(name);
+
+    if let Some(&(first, _)) = keys.values().next() {
+        track.update_node_at(0_f64, &Node::new(0_f64, first, InterpType::None))?;
+    }
+
+    let mut prev_type = KeyType::Step;
+    for ((&row, &(value, key_type)), &time) in keys.iter().zip(times.iter()) {
+        if key_type == KeyType::Smooth {
+            inexact.push(Inexact::SmoothKey { track: String::from(name), row });
+        }
+
+        let node = Node::new(time, value, prev_type.to_interp());
+        if time == 0_f64 {
+            track.update_node_at(time, &node)?;
+        } else {
+            track.add_node(&node)?;
+        }
+        prev_type = key_type;
+    }
+
+    Ok(())
+}
+
+fn internal_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+enum Tag<'a> {
+    Start { name: &'a str, attrs: Vec<(&'a str, String)>, empty: bool },
+    End(&'a str),
+}
+
+// Just enough XML for Rocket's files: elements and attributes, with the declaration, comments,
+// doctypes and text skipped.
+struct Scanner<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn error(&self, msg: &str) -> Error {
+        let before = &self.text[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+        Error::Parse { line, column, msg: String::from(msg) }
+    }
+
+    fn rest(&self) -> &'a str { &self.text[self.pos..] }
+
+    fn skip_past(&mut self, end: &str, msg: &str) -> Result<(), Error> {
+        match self.rest().find(end) {
+            Some(index) => { self.pos += index + end.len(); Ok(()) }
+            None => { self.pos = self.text.len(); Err(self.error(msg)) }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn name(&mut self) -> Result<&'a str, Error> {
+        let rest = self.rest();
+        let len = rest.find(|c: char| c.is_whitespace() || c == '/' || c == '>' || c == '=').unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name"))
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn next_tag(&mut self) -> Result<Option<Tag<'a>>, Error> {
+        loop {
+            match self.rest().find('<') {
+                Some(index) => self.pos += index,
+                None => return Ok(None)
+            }
+
+            let rest = self.rest();
+            if rest.starts_with("<?") {
+                self.skip_past("?>", "unclosed declaration")?;
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->", "unclosed comment")?;
+            } else if rest.starts_with("<!") {
+                self.skip_past(">", "unclosed doctype")?;
+            } else if rest.starts_with("</") {
+                self.pos += 2;
+                let name = self.name()?;
+                self.skip_whitespace();
+                if !self.rest().starts_with('>') {
+                    return Err(self.error("expected '>'"))
+                }
+                self.pos += 1;
+                return Ok(Some(Tag::End(name)))
+            } else {
+                self.pos += 1;
+                return self.start_tag().map(Some)
+            }
+        }
+    }
+
+    fn start_tag(&mut self) -> Result<Tag<'a>, Error> {
+        let name = self.name()?;
+        let mut attrs = vec![];
+
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                return Ok(Tag::Start { name, attrs, empty: true })
+            }
+            if rest.starts_with('>') {
+                self.pos += 1;
+                return Ok(Tag::Start { name, attrs, empty: false })
+            }
+
+            let attr = self.name()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(self.error("expected '='"))
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+
+            let quote = match self.rest().chars().next() {
+                Some(quote) if quote == '"' || quote == '\'' => quote,
+                _ => return Err(self.error("expected a quoted attribute value"))
+            };
+            self.pos += 1;
+            let len = match self.rest().find(quote) {
+                Some(len) => len,
+                None => return Err(self.error("unclosed attribute value"))
+            };
+            let value = self.unescape(&self.rest()[..len])?;
+            self.pos += len + 1;
+
+            attrs.push((attr, value));
+        }
+    }
+
+    fn unescape(&self, raw: &str) -> Result<String, Error> {
+        let mut out = String::new();
+        let mut rest = raw;
+        while let Some(index) = rest.find('&') {
+            out.push_str(&rest[..index]);
+            let end = rest[index..].find(';').ok_or_else(|| self.error("unterminated entity"))?;
+            let entity = &rest[index + 1..index + end];
+            let decoded = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(std::char::from_u32),
+                _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(std::char::from_u32),
+                _ => None
+            };
+            out.push(decoded.ok_or_else(|| self.error("unknown entity"))?);
+            rest = &rest[index + end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    fn attr(&self, attrs: &[(&str, String)], name: &str) -> Result<String, Error> {
+        match attrs.iter().find(|&&(attr, _)| attr == name) {
+            Some((_, value)) => Ok(value.clone()),
+            None => Err(self.error(&format!("missing attribute '{}'", name)))
+        }
+    }
+
+    fn number_attr<T: std::str::FromStr>(&self, attrs: &[(&str, String)], name: &str) -> Result<Option<T>, Error> {
+        match attrs.iter().find(|&&(attr, _)| attr == name) {
+            Some((_, value)) => value.trim().parse().map(Some).map_err(|_err| self.error(&format!("attribute '{}' is not a valid number", name))),
+            None => Ok(None)
+        }
+    }
+}
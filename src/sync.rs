@@ -0,0 +1,408 @@
+// Live editing through the GNU Rocket sync protocol. The demo runs a Client that connects to an
+// editor (the Server side) and asks for tracks by name, the editor then streams keys for them which
+// the client applies to the timeline. Keys sit on rows, which map to time through the timeline's
+// tempo map. All integers are big-endian on the wire.
+//
+// Rocket keys pick the interpolation of the segment that starts at them, demy nodes of the segment
+// that ends at them. The client keeps each track's Rocket keys around to move the key type over to
+// the node that follows it.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use rocket;
+use Error;
+use InterpType;
+use Node;
+use TempoMap;
+use Timeline;
+use Track;
+use ValueKind;
+
+pub const DEFAULT_PORT: u16 = 1338;
+
+const CLIENT_GREETING: &[u8] = b"hello, synctracker!";
+const SERVER_GREETING: &[u8] = b"hello, demo!";
+
+const SET_KEY: u8 = 0;
+const DELETE_KEY: u8 = 1;
+const GET_TRACK: u8 = 2;
+const SET_ROW: u8 = 3;
+const PAUSE: u8 = 4;
+const SAVE_TRACKS: u8 = 5;
+
+// Rocket's interpolation modes. Step, linear and ramp match a demy InterpType exactly. Smooth is a
+// smoothstep, which has no exact counterpart: it is mapped to InterpType::SineInOut both ways, which
+// follows it to within 1% of the segment's range. Synced smooth keys therefore don't play back
+// exactly as in the editor, import_rocket lists them as Inexact::SmoothKey.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum KeyType {
+    Step = 0,
+    Linear = 1,
+    Smooth = 2,
+    Ramp = 3
+}
+
+impl KeyType {
+    pub fn from_u8(value: u8) -> Option<KeyType> {
+        match value {
+            0 => Some(KeyType::Step),
+            1 => Some(KeyType::Linear),
+            2 => Some(KeyType::Smooth),
+            3 => Some(KeyType::Ramp),
+            _ => None
+        }
+    }
+
+    // Approximate for Smooth, see above.
+    pub fn to_interp(&self) -> InterpType {
+        match *self {
+            KeyType::Step => InterpType::None,
+            KeyType::Linear => InterpType::Linear,
+            KeyType::Smooth => InterpType::SineInOut,
+            KeyType::Ramp => InterpType::QuadIn
+        }
+    }
+
+    // None for interpolators Rocket has no counterpart for. SineInOut only approximates Smooth.
+    pub fn from_interp(interp: InterpType) -> Option<KeyType> {
+        match interp {
+            InterpType::None => Some(KeyType::Step),
+            InterpType::Linear => Some(KeyType::Linear),
+            InterpType::SineInOut => Some(KeyType::Smooth),
+            InterpType::QuadIn => Some(KeyType::Ramp),
+            _ => None
+        }
+    }
+}
+
+// Track indices count the GET_TRACK requests made on the connection, starting from 0.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Command {
+    SetKey { track: u32, row: u32, value: f32, key_type: KeyType },
+    DeleteKey { track: u32, row: u32 },
+    GetTrack(String),
+    SetRow(u32),
+    Pause(bool),
+    SaveTracks,
+}
+
+impl Command {
+    fn internal_write(&self, out: &mut Vec<u8>) {
+        match *self {
+            Command::SetKey { track, row, value, key_type } => {
+                out.push(SET_KEY);
+                out.extend_from_slice(&track.to_be_bytes());
+                out.extend_from_slice(&row.to_be_bytes());
+                out.extend_from_slice(&value.to_bits().to_be_bytes());
+                out.push(key_type as u8);
+            }
+            Command::DeleteKey { track, row } => {
+                out.push(DELETE_KEY);
+                out.extend_from_slice(&track.to_be_bytes());
+                out.extend_from_slice(&row.to_be_bytes());
+            }
+            Command::GetTrack(ref name) => {
+                out.push(GET_TRACK);
+                out.extend_from_slice(&(name.len() as u32).to_be_bytes());
+                out.extend_from_slice(name.as_bytes());
+            }
+            Command::SetRow(row) => {
+                out.push(SET_ROW);
+                out.extend_from_slice(&row.to_be_bytes());
+            }
+            Command::Pause(pause) => out.extend_from_slice(&[PAUSE, pause as u8]),
+            Command::SaveTracks => out.push(SAVE_TRACKS)
+        }
+    }
+
+    // The command at the start of `buffer` and its length, or None if it hasn't fully arrived.
+    fn internal_read(buffer: &[u8]) -> Result<Option<(Command, usize)>, Error> {
+        let u32_at = |offset: usize| {
+            buffer.get(offset..offset + 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        };
+
+        let command = match buffer.first() {
+            None => None,
+            Some(&SET_KEY) => match (u32_at(1), u32_at(5), u32_at(9), buffer.get(13)) {
+                (Some(track), Some(row), Some(bits), Some(&key_type)) => {
+                    let key_type = KeyType::from_u8(key_type).ok_or(Error::Protocol("unknown key type"))?;
+                    Some((Command::SetKey { track, row, value: f32::from_bits(bits), key_type }, 14))
+                }
+                _ => None
+            },
+            Some(&DELETE_KEY) => match (u32_at(1), u32_at(5)) {
+                (Some(track), Some(row)) => Some((Command::DeleteKey { track, row }, 9)),
+                _ => None
+            },
+            Some(&GET_TRACK) => match u32_at(1) {
+                Some(len) if buffer.len() >= 5 + len as usize => {
+                    let name = std::str::from_utf8(&buffer[5..5 + len as usize])?;
+                    Some((Command::GetTrack(String::from(name)), 5 + len as usize))
+                }
+                _ => None
+            },
+            Some(&SET_ROW) => u32_at(1).map(|row| (Command::SetRow(row), 5)),
+            Some(&PAUSE) => buffer.get(1).map(|&pause| (Command::Pause(pause != 0), 2)),
+            Some(&SAVE_TRACKS) => Some((Command::SaveTracks, 1)),
+            Some(_) => return Err(Error::Protocol("unknown command"))
+        };
+
+        Ok(command)
+    }
+}
+
+// One end of a sync connection, after the greeting.
+pub struct Connection {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl Connection {
+    pub fn send(&mut self, command: &Command) -> Result<(), Error> {
+        let mut out = vec![];
+        command.internal_write(&mut out);
+        self.stream.write_all(&out)?;
+        Ok(())
+    }
+
+    // Waits for the next command.
+    pub fn recv(&mut self) -> Result<Command, Error> {
+        loop {
+            if let Some(command) = self.internal_take()? {
+                return Ok(command)
+            }
+            self.internal_fill()?;
+        }
+    }
+
+    // The next command if one has already arrived, without waiting for one.
+    pub fn try_recv(&mut self) -> Result<Option<Command>, Error> {
+        if let Some(command) = self.internal_take()? {
+            return Ok(Some(command))
+        }
+
+        self.stream.set_nonblocking(true)?;
+        let filled = self.internal_fill();
+        self.stream.set_nonblocking(false)?;
+
+        match filled {
+            Ok(()) => self.internal_take(),
+            Err(Error::Io(ref err)) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err)
+        }
+    }
+
+    // Sends every node of `track` as a key of track `index`, the way export_rocket would write them.
+    pub fn send_track(&mut self, index: u32, track: &Track, tempo: &TempoMap) -> Result<(), Error> {
+        for key in rocket::internal_track_keys(track, tempo, &mut vec![]) {
+            self.send(&Command::SetKey { track: index, row: key.row, value: key.value as f32, key_type: key.key_type })?;
+        }
+        Ok(())
+    }
+
+    fn internal_take(&mut self) -> Result<Option<Command>, Error> {
+        match Command::internal_read(&self.buffer)? {
+            Some((command, len)) => {
+                self.buffer.drain(..len);
+                Ok(Some(command))
+            }
+            None => Ok(None)
+        }
+    }
+
+    fn internal_fill(&mut self) -> Result<(), Error> {
+        let mut chunk = [0_u8; 4096];
+        match self.stream.read(&mut chunk)? {
+            0 => Err(Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "the sync connection was closed"))),
+            len => { self.buffer.extend_from_slice(&chunk[..len]); Ok(()) }
+        }
+    }
+}
+
+// The editor's side: accepts demos and completes their greeting.
+pub struct Server {
+    listener: TcpListener,
+}
+
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Server, Error> {
+        Ok(Server { listener: TcpListener::bind(addr)? })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> { Ok(self.listener.local_addr()?) }
+
+    pub fn accept(&self) -> Result<Connection, Error> {
+        let (mut stream, _addr) = self.listener.accept()?;
+
+        let mut greeting = [0_u8; 19];
+        stream.read_exact(&mut greeting)?;
+        if greeting[..] != *CLIENT_GREETING {
+            return Err(Error::Protocol("the client did not greet as a demo"))
+        }
+        stream.write_all(SERVER_GREETING)?;
+
+        Ok(Connection { stream, buffer: vec![] })
+    }
+}
+
+struct SyncTrack {
+    name: String,
+    // The track's Rocket keys by row, needed to tell which key type governs which segment.
+    keys: BTreeMap<u32, (f32, KeyType)>,
+    // Whether the timeline's nodes were cleared for the editor's keys yet.
+    cleared: bool,
+}
+
+// The demo's side. Tracks requested through get_track are owned by the editor once their first key
+// arrives, its keys then replace whatever the timeline held for them.
+pub struct Client {
+    connection: Connection,
+    tracks: Vec<SyncTrack>,
+    row: Option<u32>,
+    // Commands update collected before failing, returned by the next update.
+    pending: Vec<Command>,
+}
+
+impl Client {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Client, Error> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        stream.write_all(CLIENT_GREETING)?;
+
+        let mut greeting = [0_u8; 12];
+        stream.read_exact(&mut greeting)?;
+        if greeting[..] != *SERVER_GREETING {
+            return Err(Error::Protocol("the server did not greet as an editor"))
+        }
+
+        Ok(Client { connection: Connection { stream, buffer: vec![] }, tracks: vec![], row: None, pending: vec![] })
+    }
+
+    // Asks the editor for the keys of the scalar track `name`. The track keeps its nodes until the
+    // first key arrives, a track the editor has no keys for is left as it is. Returns the track's
+    // index on this connection, asking again for a known track only returns its index.
+    pub fn get_track(&mut self, tl: &mut Timeline, name: &str) -> Result<u32, Error> {
+        if let Some(index) = self.tracks.iter().position(|track| track.name == name) {
+            return Ok(index as u32)
+        }
+
+        tl.get_typed_track_mut(name, ValueKind::Scalar)?;
+        self.connection.send(&Command::GetTrack(String::from(name)))?;
+        self.tracks.push(SyncTrack { name: String::from(name), keys: BTreeMap::new(), cleared: false });
+        Ok((self.tracks.len() - 1) as u32)
+    }
+
+    // Tells the editor where playback is, only sent when the row changed since the last call.
+    pub fn set_row(&mut self, row: u32) -> Result<(), Error> {
+        if self.row == Some(row) {
+            return Ok(())
+        }
+        self.row = Some(row);
+        self.connection.send(&Command::SetRow(row))
+    }
+
+    // Applies the keys that arrived since the last update to `tl`, without waiting for more. Returns
+    // the other commands in the order they arrived: seeking, pausing and saving are up to the demo.
+    // A key that can't be applied fails the update, the commands before it come with the next one.
+    pub fn update(&mut self, tl: &mut Timeline) -> Result<Vec<Command>, Error> {
+        while let Some(command) = self.connection.try_recv()? {
+            match command {
+                Command::SetKey { track, row, value, key_type } => {
+                    self.internal_clear(tl, track)?;
+                    let previous = self.tracks[track as usize].keys.insert(row, (value, key_type));
+                    self.internal_apply_or_restore(tl, track, row, previous)?;
+                }
+                Command::DeleteKey { track, row } => {
+                    self.internal_clear(tl, track)?;
+                    let previous = self.tracks[track as usize].keys.remove(&row);
+                    self.internal_apply_or_restore(tl, track, row, previous)?;
+                }
+                Command::SetRow(row) => {
+                    // the editor already knows, don't echo it back
+                    self.row = Some(row);
+                    self.pending.push(command);
+                }
+                command => self.pending.push(command)
+            }
+        }
+        Ok(std::mem::take(&mut self.pending))
+    }
+
+    // Resets the track to a single node at time 0 before its first key is applied.
+    fn internal_clear(&mut self, tl: &mut Timeline, index: u32) -> Result<(), Error> {
+        let sync = self.tracks.get_mut(index as usize).ok_or(Error::Protocol("key for a track that was never requested"))?;
+        if sync.cleared {
+            return Ok(())
+        }
+
+        let track = tl.get_typed_track_mut(&sync.name, ValueKind::Scalar)?;
+        let times: Vec<f64> = track.nodes().skip(1).map(|node| node.get_time()).collect();
+        for time in times {
+            track.del_node_at(time)?;
+        }
+        track.update_node_at(0_f64, &Node::new(0_f64, 0_f64, InterpType::None))?;
+        sync.cleared = true;
+        Ok(())
+    }
+
+    // A key that fails to apply is taken back out of the track's keys, so that they keep matching
+    // the timeline.
+    fn internal_apply_or_restore(&mut self, tl: &mut Timeline, index: u32, row: u32, previous: Option<(f32, KeyType)>) -> Result<(), Error> {
+        let result = self.internal_apply(tl, index, row);
+        if result.is_err() {
+            let keys = &mut self.tracks[index as usize].keys;
+            match previous {
+                Some(key) => { keys.insert(row, key); }
+                None => { keys.remove(&row); }
+            }
+        }
+        result
+    }
+
+    // Brings the node at `row` in line with the track's keys, along with the nodes whose segment
+    // changed with it: the node after it and, standing in for Rocket holding the first key's value
+    // before it, the node at time 0.
+    fn internal_apply(&self, tl: &mut Timeline, index: u32, row: u32) -> Result<(), Error> {
+        let sync = &self.tracks[index as usize];
+        let time_of = |row: u32| tl.get_tempo().rows_to_ticks(row as f64);
+        let time = time_of(row);
+        let next = sync.keys.range(row.saturating_add(1)..).next().map(|(&next, _)| (next, time_of(next)));
+        let first = sync.keys.values().next().map_or(0_f32, |&(value, _)| value);
+
+        // the key type of the segment ending at `row`
+        let interp_before = |row: u32| {
+            sync.keys.range(..row).next_back().map_or(KeyType::Step, |(_, &(_, key_type))| key_type).to_interp()
+        };
+
+        let track = tl.get_typed_track_mut(&sync.name, ValueKind::Scalar)?;
+
+        match sync.keys.get(&row) {
+            Some(&(value, _)) => {
+                let node = Node::new(time, value as f64, interp_before(row));
+                if time == 0_f64 || track.get_node_at(time).is_some() {
+                    track.update_node_at(time, &node)?;
+                } else {
+                    track.add_node(&node)?;
+                }
+            }
+            // the editor may delete a key it never sent
+            None if time != 0_f64 && track.get_node_at(time).is_some() => track.del_node_at(time)?,
+            None => ()
+        }
+
+        if !sync.keys.contains_key(&0) {
+            track.update_node_at(0_f64, &Node::new(0_f64, first as f64, InterpType::None))?;
+        }
+
+        if let Some((next, next_time)) = next {
+            let mut node = *track.get_node_at(next_time).ok_or(Error::NodeNotFound(next_time))?;
+            node.set_interpolator(interp_before(next));
+            track.update_node_at(next_time, &node)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+// Musical time for a timeline. Ticks are the unit node times are stored in, the tick rate converts
+// them to seconds, and the tempo changes convert seconds to beats. Rows subdivide beats the way
+// GNU Rocket does.
+
+use std::slice;
+
+use Error;
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TempoChange {
+    time: f64,
+    bpm: f64,
+}
+
+impl TempoChange {
+    pub fn get_time(&self) -> f64 { self.time }
+    pub fn get_bpm(&self) -> f64 { self.bpm }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TimeSignature {
+    pub beats_per_bar: u32,
+    pub beat_unit: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TempoMap {
+    ticks_per_second: f64,
+    rows_per_beat: u32,
+    time_signature: TimeSignature,
+    // Sorted by time, the first change is always at time 0.
+    changes: Vec<TempoChange>,
+}
+
+impl Default for TempoMap {
+    fn default() -> Self { TempoMap::new() }
+}
+
+impl TempoMap {
+    // 120 BPM in 4/4 with 8 rows per beat, and millisecond ticks.
+    pub fn new() -> Self {
+        TempoMap {
+            ticks_per_second: 1000_f64,
+            rows_per_beat: 8,
+            time_signature: TimeSignature { beats_per_bar: 4, beat_unit: 4 },
+            changes: vec![TempoChange { time: 0_f64, bpm: 120_f64 }],
+        }
+    }
+
+    pub fn get_ticks_per_second(&self) -> f64 { self.ticks_per_second }
+    pub fn set_ticks_per_second(&mut self, ticks_per_second: f64) -> Result<(), Error> {
+        if !(ticks_per_second > 0_f64 && ticks_per_second.is_finite()) {
+            return Err(Error::InvalidTempo("Ticks per second must be a finite, positive number."));
+        }
+
+        self.ticks_per_second = ticks_per_second;
+        Ok(())
+    }
+
+    pub fn get_rows_per_beat(&self) -> u32 { self.rows_per_beat }
+    pub fn set_rows_per_beat(&mut self, rows_per_beat: u32) -> Result<(), Error> {
+        if rows_per_beat == 0 {
+            return Err(Error::InvalidTempo("Rows per beat must be at least 1."));
+        }
+
+        self.rows_per_beat = rows_per_beat;
+        Ok(())
+    }
+
+    pub fn get_time_signature(&self) -> TimeSignature { self.time_signature }
+    pub fn set_time_signature(&mut self, signature: TimeSignature) -> Result<(), Error> {
+        if signature.beats_per_bar == 0 || signature.beat_unit == 0 {
+            return Err(Error::InvalidTempo("A time signature needs at least one beat per bar and a non-zero beat unit."));
+        }
+
+        self.time_signature = signature;
+        Ok(())
+    }
+
+    pub fn changes(&self) -> slice::Iter<'_, TempoChange> { self.changes.iter() }
+
+    // Sets the tempo from `time` onwards, replacing a change already at that time.
+    pub fn set_bpm_at(&mut self, time: f64, bpm: f64) -> Result<(), Error> {
+        if !(time >= 0_f64 && time.is_finite()) {
+            return Err(Error::InvalidTime(time));
+        }
+
+        if !(bpm > 0_f64 && bpm.is_finite()) {
+            return Err(Error::InvalidBpm(bpm));
+        }
+
+        let index = self.changes.partition_point(|change| change.time < time);
+        match self.changes.get_mut(index) {
+            Some(change) if change.time == time => change.bpm = bpm,
+            _ => self.changes.insert(index, TempoChange { time, bpm })
+        }
+        Ok(())
+    }
+
+    pub fn del_bpm_at(&mut self, time: f64) -> Result<(), Error> {
+        if time == 0_f64 {
+            return Err(Error::ZeroTimeDelete);
+        }
+
+        match self.changes.iter().position(|change| change.time == time) {
+            Some(index) => { self.changes.remove(index); Ok(()) }
+            None => Err(Error::TempoChangeNotFound(time))
+        }
+    }
+
+    pub fn get_bpm_at(&self, time: f64) -> f64 {
+        let index = self.changes.partition_point(|change| change.time <= time);
+        self.changes[index.max(1) - 1].bpm
+    }
+
+    pub fn ticks_to_seconds(&self, ticks: f64) -> f64 { ticks / self.ticks_per_second }
+    pub fn seconds_to_ticks(&self, seconds: f64) -> f64 { seconds * self.ticks_per_second }
+
+    pub fn ticks_to_beats(&self, ticks: f64) -> f64 {
+        let mut beats = 0_f64;
+
+        for (i, change) in self.changes.iter().enumerate() {
+            let end = self.changes.get(i + 1).map_or(f64::INFINITY, |next| next.time);
+            if ticks <= end || i + 1 == self.changes.len() {
+                return beats + self.internal_beats_in(ticks - change.time, change.bpm)
+            }
+
+            beats += self.internal_beats_in(end - change.time, change.bpm);
+        }
+
+        beats
+    }
+
+    pub fn beats_to_ticks(&self, beats: f64) -> f64 {
+        let mut beats_left = beats;
+
+        for (i, change) in self.changes.iter().enumerate() {
+            let end = self.changes.get(i + 1).map_or(f64::INFINITY, |next| next.time);
+            let segment_beats = self.internal_beats_in(end - change.time, change.bpm);
+
+            if beats_left <= segment_beats || i + 1 == self.changes.len() {
+                return change.time + self.seconds_to_ticks(beats_left * 60_f64 / change.bpm)
+            }
+
+            beats_left -= segment_beats;
+        }
+
+        0_f64
+    }
+
+    pub fn ticks_to_rows(&self, ticks: f64) -> f64 { self.ticks_to_beats(ticks) * self.rows_per_beat as f64 }
+    pub fn rows_to_ticks(&self, rows: f64) -> f64 { self.beats_to_ticks(rows / self.rows_per_beat as f64) }
+
+    pub fn ticks_to_bars(&self, ticks: f64) -> f64 {
+        self.ticks_to_beats(ticks) / self.time_signature.beats_per_bar as f64
+    }
+
+    pub fn bars_to_ticks(&self, bars: f64) -> f64 {
+        self.beats_to_ticks(bars * self.time_signature.beats_per_bar as f64)
+    }
+
+    pub(crate) fn internal_validate(&self) -> Result<(), Error> {
+        if !(self.ticks_per_second > 0_f64 && self.ticks_per_second.is_finite()) || self.rows_per_beat == 0 {
+            return Err(Error::InvalidTempo("The tempo map has an invalid tick rate or rows per beat."));
+        }
+
+        if self.time_signature.beats_per_bar == 0 || self.time_signature.beat_unit == 0 {
+            return Err(Error::InvalidTempo("The tempo map has an invalid time signature."));
+        }
+
+        match self.changes.first() {
+            Some(first) if first.time == 0_f64 => (),
+            _ => return Err(Error::InvalidTempo("The tempo map must start with a tempo change at time 0."))
+        }
+
+        let sorted = self.changes.windows(2).all(|pair| pair[0].time < pair[1].time);
+        let valid_bpm = self.changes.iter().all(|change| change.bpm > 0_f64 && change.bpm.is_finite());
+        if !sorted || !valid_bpm {
+            return Err(Error::InvalidTempo("The tempo map has unordered tempo changes or an invalid BPM."));
+        }
+
+        Ok(())
+    }
+
+    fn internal_beats_in(&self, ticks: f64, bpm: f64) -> f64 {
+        self.ticks_to_seconds(ticks) * bpm / 60_f64
+    }
+}
@@ -0,0 +1,150 @@
+// Checks the invariants that Track and EventTrack keep while editing on timelines that were loaded
+// instead: every track has a node at time 0, times are finite, non-negative and strictly increasing,
+// and node values fit the track. A loaded timeline is either rejected with every violation listed or
+// repaired in place.
+
+use std::fmt;
+
+use InterpId;
+use InterpType;
+use Node;
+use Timeline;
+use Value;
+use ValueKind;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Violation {
+    // The track's name in the file differs from the key it is stored under.
+    NameMismatch { track: String, name: String },
+    NoNodes { track: String },
+    MissingZeroNode { track: String, first: f64 },
+    InvalidTime { track: String, time: f64 },
+    OutOfOrder { track: String, time: f64, prev: f64 },
+    DuplicateTime { track: String, time: f64 },
+    InvalidValue { track: String, time: f64 },
+    ValueKindMismatch { track: String, time: f64, expected: ValueKind, found: ValueKind },
+    UnknownInterp { track: String, time: f64, id: InterpId },
+    // A node uses custom interpolation without naming an interpolator.
+    MissingCustomInterp { track: String, time: f64 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Violation::NameMismatch { ref track, ref name } => write!(f, "track '{}' is named '{}'", track, name),
+            Violation::NoNodes { ref track } => write!(f, "track '{}' has no nodes", track),
+            Violation::MissingZeroNode { ref track, first } => write!(f, "track '{}' starts at {} instead of 0", track, first),
+            Violation::InvalidTime { ref track, time } => write!(f, "track '{}' has a node or event at invalid time {}", track, time),
+            Violation::OutOfOrder { ref track, time, prev } => write!(f, "track '{}' has {} after {}", track, time, prev),
+            Violation::DuplicateTime { ref track, time } => write!(f, "track '{}' has more than one node or event at {}", track, time),
+            Violation::InvalidValue { ref track, time } => write!(f, "track '{}' has a non-finite value at {}", track, time),
+            Violation::ValueKindMismatch { ref track, time, expected, found } =>
+                write!(f, "track '{}' has a {:?} value at {} instead of a {:?} value", track, found, time, expected),
+            Violation::UnknownInterp { ref track, time, id } =>
+                write!(f, "track '{}' uses unnamed interpolator id {} at {}", track, id, time),
+            Violation::MissingCustomInterp { ref track, time } =>
+                write!(f, "track '{}' uses custom interpolation without an interpolator at {}", track, time),
+        }
+    }
+}
+
+// Collects the violations of every track and event track. With `repair` it also fixes them: nodes
+// and events with invalid times or values are dropped, the rest sorted with the first of any
+// duplicates kept, unknown interpolator ids cleared, custom interpolation left without an
+// interpolator made linear and a missing time 0 node added back.
+pub(crate) fn internal_validate(tl: &mut Timeline, repair: bool) -> Vec<Violation> {
+    let mut violations = vec![];
+    let interp_count = tl.interps.len();
+
+    for (key, track) in tl.tracks.iter_mut() {
+        if track.name != *key {
+            violations.push(Violation::NameMismatch { track: key.clone(), name: track.name.clone() });
+            if repair { track.name = key.clone() }
+        }
+
+        let kind = track.kind;
+        let mut kept: Vec<Node> = vec![];
+        for node in &track.nodes {
+            let time = node.time;
+            if !(time >= 0_f64 && time.is_finite()) {
+                violations.push(Violation::InvalidTime { track: key.clone(), time });
+                continue
+            }
+
+            if node.value.get_kind() != kind {
+                violations.push(Violation::ValueKindMismatch { track: key.clone(), time, expected: kind, found: node.value.get_kind() });
+                continue
+            }
+
+            let tangents = [node.in_tangent.time, node.in_tangent.value, node.out_tangent.time, node.out_tangent.value];
+            if node.value.components().iter().chain(tangents.iter()).any(|value| !value.is_finite()) {
+                violations.push(Violation::InvalidValue { track: key.clone(), time });
+                continue
+            }
+
+            let mut node = *node;
+            if let Some(id) = node.custom_interp.filter(|&id| id as usize >= interp_count) {
+                violations.push(Violation::UnknownInterp { track: key.clone(), time, id });
+                node.custom_interp = None;
+            } else if node.interp == InterpType::Custom && node.custom_interp.is_none() {
+                violations.push(Violation::MissingCustomInterp { track: key.clone(), time });
+            }
+            if node.interp == InterpType::Custom && node.custom_interp.is_none() {
+                node.interp = InterpType::Linear;
+            }
+            kept.push(node);
+        }
+
+        internal_check_order(key, kept.iter().map(|node| node.time), &mut violations);
+
+        match kept.iter().map(|node| node.time).min_by(|a, b| a.total_cmp(b)) {
+            None if track.nodes.is_empty() => violations.push(Violation::NoNodes { track: key.clone() }),
+            Some(first) if first != 0_f64 => violations.push(Violation::MissingZeroNode { track: key.clone(), first }),
+            _ => ()
+        }
+
+        if repair {
+            kept.sort_by(|a, b| a.time.total_cmp(&b.time));
+            kept.dedup_by(|b, a| a.time == b.time);
+            if kept.first().map(|node| node.time) != Some(0_f64) {
+                kept.insert(0, Node::new_typed(0_f64, Value::zero(kind), InterpType::None));
+            }
+            track.nodes = kept;
+        }
+    }
+
+    for (key, track) in tl.events.iter_mut() {
+        let (name, events) = track.internal_parts_mut();
+        if name != key {
+            violations.push(Violation::NameMismatch { track: key.clone(), name: name.clone() });
+            if repair { *name = key.clone() }
+        }
+
+        for event in events.iter().filter(|event| !(event.get_time() >= 0_f64 && event.get_time().is_finite())) {
+            violations.push(Violation::InvalidTime { track: key.clone(), time: event.get_time() });
+        }
+
+        let valid = |time: f64| time >= 0_f64 && time.is_finite();
+        internal_check_order(key, events.iter().map(|event| event.get_time()).filter(|&time| valid(time)), &mut violations);
+
+        if repair {
+            events.retain(|event| valid(event.get_time()));
+            events.sort_by(|a, b| a.get_time().total_cmp(&b.get_time()));
+            events.dedup_by(|b, a| a.get_time() == b.get_time());
+        }
+    }
+
+    violations
+}
+
+fn internal_check_order<I: Iterator<Item = f64>>(track: &str, times: I, violations: &mut Vec<Violation>) {
+    let mut prev: Option<f64> = None;
+    for time in times {
+        match prev {
+            Some(prev) if time == prev => violations.push(Violation::DuplicateTime { track: String::from(track), time }),
+            Some(prev) if time < prev => violations.push(Violation::OutOfOrder { track: String::from(track), time, prev }),
+            _ => ()
+        }
+        prev = Some(prev.map_or(time, |prev| prev.max(time)));
+    }
+}
@@ -0,0 +1,161 @@
+use std::convert::TryFrom;
+
+use Error;
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum ValueKind {
+    #[default]
+    Scalar = 0,
+    Vec2 = 1,
+    Vec3 = 2,
+    Vec4 = 3,
+    Color = 4,
+    Quat = 5
+}
+
+impl ValueKind {
+    pub fn component_count(&self) -> usize {
+        match *self {
+            ValueKind::Scalar => 1,
+            ValueKind::Vec2 => 2,
+            ValueKind::Vec3 => 3,
+            ValueKind::Vec4 | ValueKind::Color | ValueKind::Quat => 4
+        }
+    }
+
+    pub fn is_scalar(&self) -> bool { *self == ValueKind::Scalar }
+}
+
+impl TryFrom<u32> for ValueKind {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Error> {
+        match value {
+            0 => Ok(ValueKind::Scalar),
+            1 => Ok(ValueKind::Vec2),
+            2 => Ok(ValueKind::Vec3),
+            3 => Ok(ValueKind::Vec4),
+            4 => Ok(ValueKind::Color),
+            5 => Ok(ValueKind::Quat),
+            _ => Err(Error::InvalidEnum { name: "ValueKind", value })
+        }
+    }
+}
+
+// Components past the kind's component count are always zero. Colors are RGBA, quaternions XYZW.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(from = "SerializedValue", into = "SerializedValue")]
+pub struct Value {
+    kind: ValueKind,
+    components: [f64; 4],
+}
+
+impl Value {
+    pub fn scalar(value: f64) -> Self { Value { kind: ValueKind::Scalar, components: [value, 0_f64, 0_f64, 0_f64] } }
+    pub fn vec2(x: f64, y: f64) -> Self { Value { kind: ValueKind::Vec2, components: [x, y, 0_f64, 0_f64] } }
+    pub fn vec3(x: f64, y: f64, z: f64) -> Self { Value { kind: ValueKind::Vec3, components: [x, y, z, 0_f64] } }
+    pub fn vec4(x: f64, y: f64, z: f64, w: f64) -> Self { Value { kind: ValueKind::Vec4, components: [x, y, z, w] } }
+    pub fn color(r: f64, g: f64, b: f64, a: f64) -> Self { Value { kind: ValueKind::Color, components: [r, g, b, a] } }
+    pub fn quat(x: f64, y: f64, z: f64, w: f64) -> Self { Value { kind: ValueKind::Quat, components: [x, y, z, w] } }
+
+    // Zero for vectors and colors, the identity rotation for quaternions.
+    pub fn zero(kind: ValueKind) -> Self {
+        match kind {
+            ValueKind::Quat => Value::quat(0_f64, 0_f64, 0_f64, 1_f64),
+            kind => Value { kind, components: [0_f64; 4] }
+        }
+    }
+
+    pub fn get_kind(&self) -> ValueKind { self.kind }
+    pub fn components(&self) -> &[f64] { &self.components[..self.kind.component_count()] }
+
+    pub fn get(&self, index: usize) -> f64 { self.components()[index] }
+    pub fn set(&mut self, index: usize, value: f64) {
+        let count = self.kind.component_count();
+        self.components[..count][index] = value
+    }
+
+    // Spherical interpolation along the shorter arc between two quaternions.
+    pub fn slerp(&self, to: &Value, t: f64) -> Value {
+        let a = self.components;
+        let mut b = to.components;
+
+        let mut dot = a.iter().zip(b.iter()).map(|(a, b)| a * b).sum::<f64>();
+        if dot < 0_f64 {
+            for component in b.iter_mut() { *component = -*component }
+            dot = -dot;
+        }
+
+        let (weight_a, weight_b) = if dot > 0.9995_f64 {
+            // nearly parallel, fall back to a normalized lerp
+            (1_f64 - t, t)
+        } else {
+            let theta = dot.acos();
+            let sin = theta.sin();
+            (((1_f64 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        let mut result = [0_f64; 4];
+        for i in 0..4 {
+            result[i] = a[i] * weight_a + b[i] * weight_b;
+        }
+
+        let length = result.iter().map(|c| c * c).sum::<f64>().sqrt();
+        if length > 0_f64 {
+            for component in result.iter_mut() { *component /= length }
+        }
+
+        Value { kind: self.kind, components: result }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self { Value::scalar(value) }
+}
+
+// Scalars are written as plain numbers so that files from before typed values keep loading, other
+// kinds are tagged with their name.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SerializedValue {
+    Scalar(f64),
+    Typed(TypedValue),
+}
+
+#[derive(Serialize, Deserialize)]
+enum TypedValue {
+    Vec2([f64; 2]),
+    Vec3([f64; 3]),
+    Vec4([f64; 4]),
+    Color([f64; 4]),
+    Quat([f64; 4]),
+}
+
+impl From<SerializedValue> for Value {
+    fn from(value: SerializedValue) -> Self {
+        match value {
+            SerializedValue::Scalar(v) => Value::scalar(v),
+            SerializedValue::Typed(TypedValue::Vec2(c)) => Value::vec2(c[0], c[1]),
+            SerializedValue::Typed(TypedValue::Vec3(c)) => Value::vec3(c[0], c[1], c[2]),
+            SerializedValue::Typed(TypedValue::Vec4(c)) => Value::vec4(c[0], c[1], c[2], c[3]),
+            SerializedValue::Typed(TypedValue::Color(c)) => Value::color(c[0], c[1], c[2], c[3]),
+            SerializedValue::Typed(TypedValue::Quat(c)) => Value::quat(c[0], c[1], c[2], c[3]),
+        }
+    }
+}
+
+impl From<Value> for SerializedValue {
+    fn from(value: Value) -> Self {
+        let c = value.components;
+        match value.kind {
+            ValueKind::Scalar => SerializedValue::Scalar(c[0]),
+            ValueKind::Vec2 => SerializedValue::Typed(TypedValue::Vec2([c[0], c[1]])),
+            ValueKind::Vec3 => SerializedValue::Typed(TypedValue::Vec3([c[0], c[1], c[2]])),
+            ValueKind::Vec4 => SerializedValue::Typed(TypedValue::Vec4(c)),
+            ValueKind::Color => SerializedValue::Typed(TypedValue::Color(c)),
+            ValueKind::Quat => SerializedValue::Typed(TypedValue::Quat(c)),
+        }
+    }
+}
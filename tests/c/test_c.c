@@ -0,0 +1,211 @@
+/* Exercises the generated demy.h from C. Run by tests/c_api.rs, argv[1] is a scratch file path. */
+
+#include "demy.h"
+
+#include <math.h>
+#include <stdio.h>
+#include <string.h>
+
+static int failures = 0;
+
+#define CHECK(cond) do { \
+    if (!(cond)) { fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); failures++; } \
+} while (0)
+
+static double step(const Key *prev, const Key *from, const Key *to, const Key *next, double t) {
+    (void)prev; (void)next;
+    return t < 0.5 ? from->value : to->value;
+}
+
+int main(int argc, char **argv) {
+    if (argc < 2) { fprintf(stderr, "usage: %s <scratch file>\n", argv[0]); return 2; }
+
+    Timeline *tl = demy_tl_new();
+    TrackHandle tr = demy_tl_track_get(tl, "camera");
+    CHECK(demy_tr_is_valid(tl, tr));
+    CHECK(demy_tr_get_kind(tl, tr) == ValueKind_Scalar);
+
+    CHECK(demy_tr_add_node(tl, tr, 10.0, 1.0, InterpType_Linear));
+    CHECK(demy_tr_add_node(tl, tr, 20.0, 2.0, InterpType_Linear));
+
+    /* duplicates are rejected and reported through the last error */
+    CHECK(!demy_tr_add_node(tl, tr, 20.0, 3.0, InterpType_Linear));
+    CHECK(demy_last_error_code() == ErrorCode_DuplicateTime);
+    CHECK(strlen(demy_last_error_message()) > 0);
+
+    /* time 0, 10 and 20 */
+    int count = 0;
+    double last_time = -1.0;
+    NodeIterHandle it = demy_tr_iter_begin(tl, tr);
+    NodeIterHandle end = demy_tr_iter_end(tl, tr);
+    for (; !demy_tr_iter_are_eq(tl, it, end); demy_tr_iter_next(tl, &it)) {
+        const Node *node = demy_tr_iter_get(tl, it);
+        CHECK(demy_node_get_time(node) > last_time);
+        last_time = demy_node_get_time(node);
+        count++;
+    }
+    demy_tr_iter_free(tl, it);
+    demy_tr_iter_free(tl, end);
+    CHECK(count == 3);
+
+    CHECK(demy_tr_node_count(tl, tr) == 3);
+    CHECK(demy_node_get_time(demy_tr_get_node_by_index(tl, tr, 2)) == 20.0);
+    CHECK(demy_tr_get_node_by_index(tl, tr, 3) == NULL);
+    CHECK(fabs(demy_tr_get_value_at(tl, tr, 15.0) - 1.5) < 1e-9);
+
+    char name[4];
+    CHECK(demy_tr_get_name(tl, tr, name, sizeof(name)) == 6);
+    CHECK(strcmp(name, "cam") == 0);
+
+    Node *edit = demy_node_clone(demy_tr_get_node(tl, tr, 10.0));
+    demy_node_set_value(edit, 5.0);
+    CHECK(demy_node_update_at(tl, tr, 10.0, edit));
+    demy_node_free(edit);
+    CHECK(demy_node_get_value(demy_tr_get_node(tl, tr, 10.0)) == 5.0);
+
+    int id = demy_tl_register_interp(tl, "step", step);
+    CHECK(id == 0);
+    CHECK(demy_tl_register_interp(tl, "step", NULL) == -1);
+    CHECK(demy_last_error_code() == ErrorCode_NullPointer);
+
+    TrackHandle pos = demy_tl_typed_track_get(tl, "position", ValueKind_Vec3);
+    Value v = { ValueKind_Vec3, { 1.0, 2.0, 3.0, 0.0 } };
+    CHECK(demy_tr_add_typed_node(tl, pos, 10.0, v, InterpType_None));
+    CHECK(demy_tl_typed_track_get(tl, "position", ValueKind_Quat).generation == 0);
+    CHECK(demy_last_error_code() == ErrorCode_ValueKindMismatch);
+
+    int track_count = 0;
+    TrackIterHandle tracks = demy_tl_track_iter(tl);
+    for (TrackHandle track = demy_tl_track_iter_next(tl, tracks); track.generation != 0;
+         track = demy_tl_track_iter_next(tl, tracks)) {
+        CHECK(memcmp(&track, &tr, sizeof(track)) == 0 || memcmp(&track, &pos, sizeof(track)) == 0);
+        track_count++;
+    }
+    demy_tl_track_iter_free(tl, tracks);
+    CHECK(track_count == 2);
+
+    /* deleting a track makes its handles stale instead of dangling */
+    CHECK(demy_tl_track_del(tl, "position"));
+    CHECK(!demy_tr_is_valid(tl, pos));
+    CHECK(demy_tr_node_count(tl, pos) == 0);
+    CHECK(demy_last_error_code() == ErrorCode_StaleHandle);
+
+    CHECK(demy_tl_set_bpm(tl, 0.0, 60.0));
+    CHECK(fabs(demy_tl_ticks_to_beats(tl, 2000.0) - 2.0) < 1e-9);
+
+    EventTrackHandle ev = demy_tl_event_track_get(tl, "scenes");
+    CHECK(demy_ev_add_text(tl, ev, 15.0, "tunnel"));
+    EventIterHandle fired = demy_ev_fired(tl, ev, 10.0, 20.0);
+    const Event *event = demy_ev_iter_next(tl, fired);
+    CHECK(event != NULL && demy_event_get_kind(event) == PayloadKind_Text);
+    char text[16];
+    CHECK(demy_event_get_text(event, text, sizeof(text)) == 6);
+    CHECK(strcmp(text, "tunnel") == 0);
+    CHECK(demy_ev_iter_next(tl, fired) == NULL);
+    demy_ev_iter_free(tl, fired);
+
+    CHECK(demy_tl_save(tl, argv[1]));
+
+    CBuffer json = demy_tl_save_to_buffer(tl);
+    CHECK(json.data != NULL && json.len > 0);
+    Timeline *copy = demy_tl_load_from_memory(json.data, json.len);
+    CHECK(copy != NULL);
+    CHECK(demy_tl_load_from_memory(json.data, json.len / 2) == NULL);
+    CHECK(demy_last_error_code() == ErrorCode_Parse);
+    demy_buffer_free(json);
+
+    CBuffer packed = demy_tl_save_binary_to_buffer(copy, Precision_F64);
+    demy_tl_free(copy);
+    copy = demy_tl_load_binary_from_memory(packed.data, packed.len);
+    CHECK(copy != NULL);
+    if (copy != NULL) {
+        TrackHandle camera = demy_tl_track_get(copy, "camera");
+        CHECK(demy_node_get_value(demy_tr_get_node(copy, camera, 10.0)) == 5.0);
+        demy_tl_free(copy);
+    }
+    demy_buffer_free(packed);
+
+    char binary_path[1024];
+    snprintf(binary_path, sizeof(binary_path), "%s.bin", argv[1]);
+    CHECK(demy_tl_save_binary(tl, binary_path, Precision_F32));
+    CHECK(!demy_tl_save_binary(tl, binary_path, 2));
+    CHECK(demy_last_error_code() == ErrorCode_InvalidEnum);
+    demy_tl_free(tl);
+
+    Timeline *binary = demy_tl_load_binary(binary_path);
+    CHECK(binary != NULL);
+    if (binary != NULL) {
+        TrackHandle camera = demy_tl_track_get(binary, "camera");
+        CHECK(demy_tr_node_count(binary, camera) == 3);
+        CHECK(demy_node_get_value(demy_tr_get_node(binary, camera, 10.0)) == 5.0);
+        demy_tl_free(binary);
+    }
+
+    /* a JSON file is not a binary timeline */
+    CHECK(demy_tl_load_binary(argv[1]) == NULL);
+    CHECK(demy_last_error_code() == ErrorCode_InvalidBinary);
+
+    Timeline *loaded = demy_tl_load(argv[1]);
+    CHECK(loaded != NULL);
+    if (loaded != NULL) {
+        TrackHandle camera = demy_tl_track_get(loaded, "camera");
+        CHECK(demy_node_get_value(demy_tr_get_node(loaded, camera, 20.0)) == 2.0);
+        demy_tl_free(loaded);
+    }
+
+    /* a valid file needs no repairs */
+    size_t repaired = 1;
+    loaded = demy_tl_load_repaired(argv[1], &repaired);
+    CHECK(loaded != NULL && repaired == 0);
+    demy_tl_free(loaded);
+
+    /* files using custom interpolators only load with a timeline that registered them */
+    Timeline *custom = demy_tl_new();
+    CHECK(demy_tl_register_interp(custom, "step", step) == 0);
+    TrackHandle wobble = demy_tl_track_get(custom, "wobble");
+    CHECK(demy_tr_add_node(custom, wobble, 10.0, 1.0, InterpType_Linear));
+    Node *node = demy_node_clone(demy_tr_get_node(custom, wobble, 10.0));
+    demy_node_set_interp(node, InterpType_Custom);
+    demy_node_set_custom_interp(node, 0);
+    CHECK(demy_node_update_at(custom, wobble, 10.0, node));
+    demy_node_free(node);
+    CHECK(demy_tl_save(custom, argv[1]));
+
+    CHECK(demy_tl_load(argv[1]) == NULL);
+    CHECK(demy_last_error_code() == ErrorCode_UnregisteredInterp);
+    loaded = demy_tl_load_with(custom, argv[1]);
+    CHECK(loaded != NULL);
+    if (loaded != NULL) {
+        CHECK(demy_tr_get_value_at(loaded, demy_tl_track_get(loaded, "wobble"), 6.0) == 1.0);
+        demy_tl_free(loaded);
+    }
+    CHECK(demy_tl_load_repaired(argv[1], &repaired) == NULL);
+    CHECK(demy_last_error_code() == ErrorCode_UnregisteredInterp);
+    loaded = demy_tl_load_repaired_with(custom, argv[1], &repaired);
+    CHECK(loaded != NULL && repaired == 0);
+    demy_tl_free(loaded);
+
+    CHECK(demy_tl_save_binary(custom, binary_path, Precision_F64));
+    CHECK(demy_tl_load_binary(binary_path) == NULL);
+    CHECK(demy_last_error_code() == ErrorCode_UnregisteredInterp);
+    loaded = demy_tl_load_binary_with(custom, binary_path);
+    CHECK(loaded != NULL);
+    if (loaded != NULL) {
+        CHECK(demy_tr_get_value_at(loaded, demy_tl_track_get(loaded, "wobble"), 6.0) == 1.0);
+        demy_tl_free(loaded);
+    }
+
+    json = demy_tl_save_to_buffer(custom);
+    CHECK(demy_tl_load_from_memory(json.data, json.len) == NULL);
+    CHECK(demy_last_error_code() == ErrorCode_UnregisteredInterp);
+    loaded = demy_tl_load_from_memory_with(custom, json.data, json.len);
+    CHECK(loaded != NULL);
+    demy_tl_free(loaded);
+    demy_buffer_free(json);
+    demy_tl_free(custom);
+
+    CHECK(demy_tl_load(NULL) == NULL);
+    CHECK(demy_last_error_code() == ErrorCode_NullPointer);
+
+    return failures == 0 ? 0 : 1;
+}
@@ -0,0 +1,193 @@
+// Exercises the demy.hpp wrapper. Run by tests/c_api.rs, argv[1] is a scratch file path.
+
+#include "demy.hpp"
+
+#include <cstdio>
+#include <vector>
+
+static int failures = 0;
+
+#define CHECK(cond) do { \
+    if (!(cond)) { std::fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); failures++; } \
+} while (0)
+
+static double step(const Key* prev, const Key* from, const Key* to, const Key* next, double t) {
+    (void)prev; (void)next;
+    return t < 0.5 ? from->value : to->value;
+}
+
+int main(int argc, char** argv) {
+    if (argc < 2) { std::fprintf(stderr, "usage: %s <scratch file>\n", argv[0]); return 2; }
+
+    {
+        demy::Timeline tl;
+        demy::Track camera = tl.track("camera");
+        camera.add_node(20.0, 2.0, InterpType_Linear);
+        camera.add_node(10.0, 1.0, InterpType_Linear);
+
+        std::vector<double> times;
+        for (demy::NodeRef node : camera) {
+            times.push_back(node.time());
+        }
+        CHECK(times.size() == 3);
+        CHECK(times[0] == 0.0 && times[1] == 10.0 && times[2] == 20.0);
+
+        CHECK(camera.name() == "camera");
+        CHECK(camera.node_count() == 3);
+        CHECK(camera.node(1).time() == 10.0);
+        CHECK(camera.value_at(15.0) == 1.5);
+
+        try {
+            camera.add_node(10.0, 5.0, InterpType_None);
+            CHECK(false);
+        } catch (const demy::Error& err) {
+            CHECK(err.code() == ErrorCode_DuplicateTime);
+        }
+
+        demy::NodeRef found(nullptr);
+        CHECK(camera.find_node(10.0, found));
+        CHECK(!camera.find_node(15.0, found));
+
+        demy::Node copy(found);
+        copy.set_value(4.0);
+        copy.set_time(30.0);
+        camera.update_node(10.0, copy);
+        CHECK(!camera.find_node(10.0, found));
+        CHECK(camera.find_node(30.0, found) && found.value() == 4.0);
+
+        // copies own their own node
+        demy::Node other = copy;
+        other.set_value(8.0);
+        CHECK(copy.value() == 4.0);
+
+        try {
+            tl.track("position");
+            tl.typed_track("position", ValueKind_Vec3);
+            CHECK(false);
+        } catch (const demy::Error& err) {
+            CHECK(err.code() == ErrorCode_ValueKindMismatch);
+        }
+
+        CHECK(tl.tracks().size() == 2);
+
+        demy::Track position = tl.track("position");
+        CHECK(tl.del_track("position"));
+        CHECK(!position.valid());
+        try {
+            position.add_node(10.0, 1.0, InterpType_Linear);
+            CHECK(false);
+        } catch (const demy::Error& err) {
+            CHECK(err.code() == ErrorCode_StaleHandle);
+        }
+        // calls whose failure value is also a valid result throw all the same
+        try {
+            position.name();
+            CHECK(false);
+        } catch (const demy::Error& err) {
+            CHECK(err.code() == ErrorCode_StaleHandle);
+        }
+        try {
+            tl.track("\xff");
+            CHECK(false);
+        } catch (const demy::Error& err) {
+            CHECK(err.code() == ErrorCode_InvalidUtf8);
+        }
+        CHECK(camera.valid());
+
+        // recorded edits are undone and redone a transaction at a time
+        tl.begin_transaction();
+        camera.edit_add_node(demy::Node(40.0, 1.0, InterpType_Linear));
+        camera.edit_del_node(20.0);
+        tl.commit_transaction();
+        CHECK(camera.node_count() == 3 && !camera.find_node(20.0, found));
+        CHECK(tl.undo());
+        CHECK(camera.find_node(20.0, found) && !camera.find_node(40.0, found));
+        CHECK(tl.redo());
+        CHECK(tl.undo());
+        CHECK(!tl.undo());
+
+        // range edits refuse to move nodes past each other
+        camera.insert_time(25.0, 10.0);
+        CHECK(camera.find_node(40.0, found));
+        try {
+            camera.shift_range(35.0, 45.0, -25.0);
+            CHECK(false);
+        } catch (const demy::Error& err) {
+            CHECK(err.code() == ErrorCode_RangeCollision);
+        }
+        tl.edit_remove_time(25.0, 35.0);
+        CHECK(camera.node_count() == 3 && camera.find_node(30.0, found));
+        CHECK(tl.undo());
+        CHECK(camera.find_node(40.0, found));
+        CHECK(tl.redo());
+
+        tl.save_pretty(argv[1]);
+    }
+
+    demy::Timeline loaded = demy::Timeline::load(argv[1]);
+    demy::Track camera = loaded.track("camera");
+    int count = 0;
+    for (demy::NodeRef node : camera) {
+        (void)node;
+        count++;
+    }
+    CHECK(count == 3);
+
+    // the first poll starts watching, nothing changed since
+    CHECK(!loaded.poll_reload(argv[1]));
+    CHECK(!loaded.poll_reload(argv[1]));
+    CHECK(camera.valid());
+
+    loaded.save_binary(std::string(argv[1]) + ".bin");
+    demy::Timeline binary = demy::Timeline::load_binary(std::string(argv[1]) + ".bin");
+    CHECK(binary.track("camera").node_count() == 3);
+
+    std::string json = loaded.save_to_string();
+    CHECK(demy::Timeline::load_from_memory(json.data(), json.size()).track("camera").node_count() == 3);
+    std::vector<uint8_t> packed = loaded.save_binary_to_buffer(Precision_F32);
+    CHECK(demy::Timeline::load_binary_from_memory(packed.data(), packed.size()).track("camera").node_count() == 3);
+
+    {
+        demy::Timeline registered;
+        CHECK(registered.register_interp("step", step) == 0);
+        demy::Track wobble = registered.track("wobble");
+        wobble.add_node(10.0, 1.0, InterpType_Linear);
+        demy::NodeRef found(nullptr);
+        CHECK(wobble.find_node(10.0, found));
+        demy::Node custom(found);
+        custom.set_interp(InterpType_Custom);
+        custom.set_custom_interp(0);
+        wobble.update_node(10.0, custom);
+        registered.save(argv[1]);
+
+        try {
+            demy::Timeline::load(argv[1]);
+            CHECK(false);
+        } catch (const demy::Error& err) {
+            CHECK(err.code() == ErrorCode_UnregisteredInterp);
+        }
+
+        demy::Timeline reloaded = demy::Timeline::load_with(argv[1], registered);
+        CHECK(reloaded.track("wobble").find_node(10.0, found) && found.custom_interp() == 0);
+
+        registered.save_binary(std::string(argv[1]) + ".bin");
+        reloaded = demy::Timeline::load_binary_with(std::string(argv[1]) + ".bin", registered);
+        CHECK(reloaded.track("wobble").find_node(10.0, found) && found.custom_interp() == 0);
+
+        std::string saved = registered.save_to_string();
+        reloaded = demy::Timeline::load_from_memory_with(saved.data(), saved.size(), registered);
+        CHECK(reloaded.track("wobble").find_node(10.0, found) && found.custom_interp() == 0);
+        std::vector<uint8_t> bytes = registered.save_binary_to_buffer();
+        reloaded = demy::Timeline::load_binary_from_memory_with(bytes.data(), bytes.size(), registered);
+        CHECK(reloaded.track("wobble").find_node(10.0, found) && found.custom_interp() == 0);
+    }
+
+    try {
+        demy::Timeline::load(std::string(argv[1]) + ".missing");
+        CHECK(false);
+    } catch (const demy::Error& err) {
+        CHECK(err.code() == ErrorCode_Io);
+    }
+
+    return failures == 0 ? 0 : 1;
+}
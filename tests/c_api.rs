@@ -0,0 +1,67 @@
+// Builds the programs in tests/c against include/ and the cdylib, then runs them. CC and CXX
+// override the compilers, as with most build tools.
+#![cfg(unix)]
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn library_dir() -> PathBuf {
+    // target/<profile>/deps/c_api-<hash>. The cdylib next to it is rebuilt by cargo test, the copy
+    // in target/<profile> only by cargo build.
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().to_path_buf()
+}
+
+fn build_and_run(compiler_var: &str, default_compiler: &str, source: &str, std: &str, libs: &[&str]) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let lib_dir = library_dir();
+    let exe = out_dir.join(Path::new(source).file_stem().unwrap());
+    let compiler = env::var(compiler_var).unwrap_or_else(|_| String::from(default_compiler));
+
+    let status = Command::new(&compiler)
+        .arg(std)
+        .args(["-Wall", "-Wextra", "-Werror"])
+        .arg("-I").arg(root.join("include"))
+        .arg(root.join("tests").join("c").join(source))
+        .arg("-o").arg(&exe)
+        .arg("-L").arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-ldemy")
+        .args(libs)
+        .status()
+        .unwrap_or_else(|err| panic!("could not run {}: {}", compiler, err));
+    assert!(status.success(), "{} failed to compile {}", compiler, source);
+
+    let scratch = out_dir.join(format!("{}.json", source));
+    // cargo's library path also lists target/<profile>, which would win over the rpath
+    let status = Command::new(&exe).arg(&scratch).env_remove("LD_LIBRARY_PATH").status().unwrap();
+    assert!(status.success(), "{} reported failures", source);
+}
+
+#[test]
+fn c_program() {
+    build_and_run("CC", "cc", "test_c.c", "-std=c99", &["-lm"]);
+}
+
+#[test]
+fn cpp_wrapper() {
+    build_and_run("CXX", "c++", "test_cpp.cpp", "-std=c++11", &[]);
+}
+
+// Set DEMY_UPDATE_HEADER to copy the generated header over the checked-in one instead.
+#[cfg(feature = "header")]
+#[test]
+fn header_up_to_date() {
+    let checked_in = Path::new(env!("CARGO_MANIFEST_DIR")).join("include").join("demy.h");
+    let generated = std::fs::read_to_string(Path::new(env!("OUT_DIR")).join("demy.h")).unwrap();
+
+    if env::var_os("DEMY_UPDATE_HEADER").is_some() {
+        std::fs::write(&checked_in, &generated).unwrap();
+        return
+    }
+
+    let current = std::fs::read_to_string(&checked_in).unwrap();
+    assert!(current == generated, "include/demy.h is out of date, rerun with DEMY_UPDATE_HEADER=1");
+}
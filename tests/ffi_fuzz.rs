@@ -0,0 +1,33 @@
+// Replays pseudo-random inputs through the fuzz harness in fuzz/ops.rs so it runs with the regular
+// tests. Set DEMY_FUZZ_ITERATIONS for a longer run, or use the libFuzzer target in fuzz/.
+
+extern crate demy;
+
+#[path = "../fuzz/ops.rs"]
+mod ops;
+
+use std::env;
+
+// xorshift64*, fixed seed so failures reproduce
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+#[test]
+fn random_call_sequences() {
+    let iterations = env::var("DEMY_FUZZ_ITERATIONS").ok().and_then(|n| n.parse().ok()).unwrap_or(2000);
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+    for _ in 0..iterations {
+        let len = (rng.next() % 512) as usize;
+        let data = (0..len).map(|_| rng.next() as u8).collect::<Vec<u8>>();
+        ops::run(&data);
+    }
+}